@@ -0,0 +1,120 @@
+use std::collections::HashMap;
+use crate::brain::linalg::{Matrix, Vector, VectorBuf};
+
+/// Mutable access to target, context, subword and Huffman node rows. Training math is
+/// written against this so the same update rule can write straight into the brain or into
+/// a private batch.
+pub trait Rows {
+    fn target(&mut self, idx: usize) -> &mut Vector;
+    fn context(&mut self, idx: usize) -> &mut Vector;
+    fn subword(&mut self, bucket: usize) -> &mut Vector;
+    fn node(&mut self, idx: usize) -> &mut Vector;
+}
+
+/// Rows of the brain's own tables, updated in place.
+pub struct TableRows<'a> {
+    pub targets: &'a mut Matrix,
+    pub contexts: &'a mut Matrix,
+    pub subwords: &'a mut Matrix,
+    pub nodes: &'a mut Matrix,
+}
+
+impl Rows for TableRows<'_> {
+    fn target(&mut self, idx: usize) -> &mut Vector {
+        &mut self.targets[idx]
+    }
+
+    fn context(&mut self, idx: usize) -> &mut Vector {
+        &mut self.contexts[idx]
+    }
+
+    fn subword(&mut self, bucket: usize) -> &mut Vector {
+        &mut self.subwords[bucket]
+    }
+
+    fn node(&mut self, idx: usize) -> &mut Vector {
+        &mut self.nodes[idx]
+    }
+}
+
+/// Copy-on-first-touch view over read-only tables. Workers train against this while
+/// holding only a read lock, then hand the accumulated differences to
+/// `SemanticBrain::apply_delta`.
+pub struct DeltaRows<'a> {
+    targets: &'a Matrix,
+    contexts: &'a Matrix,
+    subwords: &'a Matrix,
+    nodes: &'a Matrix,
+    touched_targets: HashMap<usize, (VectorBuf, VectorBuf)>,
+    touched_contexts: HashMap<usize, (VectorBuf, VectorBuf)>,
+    touched_subwords: HashMap<usize, (VectorBuf, VectorBuf)>,
+    touched_nodes: HashMap<usize, (VectorBuf, VectorBuf)>,
+}
+
+/// Sparse `(row, new - old)` updates produced by one `DeltaRows` batch.
+#[derive(Default)]
+pub struct BrainDelta {
+    pub targets: Vec<(usize, VectorBuf)>,
+    pub contexts: Vec<(usize, VectorBuf)>,
+    pub subwords: Vec<(usize, VectorBuf)>,
+    pub nodes: Vec<(usize, VectorBuf)>,
+}
+
+impl<'a> DeltaRows<'a> {
+    pub fn new(targets: &'a Matrix, contexts: &'a Matrix, subwords: &'a Matrix, nodes: &'a Matrix) -> Self {
+        Self {
+            targets,
+            contexts,
+            subwords,
+            nodes,
+            touched_targets: HashMap::new(),
+            touched_contexts: HashMap::new(),
+            touched_subwords: HashMap::new(),
+            touched_nodes: HashMap::new(),
+        }
+    }
+
+    pub fn into_delta(self) -> BrainDelta {
+        let diff = |(idx, (original, working)): (usize, (VectorBuf, VectorBuf))| (idx, working.sub(&original));
+        BrainDelta {
+            targets: self.touched_targets.into_iter().map(diff).collect(),
+            contexts: self.touched_contexts.into_iter().map(diff).collect(),
+            subwords: self.touched_subwords.into_iter().map(diff).collect(),
+            nodes: self.touched_nodes.into_iter().map(diff).collect(),
+        }
+    }
+}
+
+impl Rows for DeltaRows<'_> {
+    fn target(&mut self, idx: usize) -> &mut Vector {
+        let base = self.targets;
+        &mut self.touched_targets
+            .entry(idx)
+            .or_insert_with(|| (base[idx].to_owned(), base[idx].to_owned()))
+            .1
+    }
+
+    fn context(&mut self, idx: usize) -> &mut Vector {
+        let base = self.contexts;
+        &mut self.touched_contexts
+            .entry(idx)
+            .or_insert_with(|| (base[idx].to_owned(), base[idx].to_owned()))
+            .1
+    }
+
+    fn subword(&mut self, bucket: usize) -> &mut Vector {
+        let base = self.subwords;
+        &mut self.touched_subwords
+            .entry(bucket)
+            .or_insert_with(|| (base[bucket].to_owned(), base[bucket].to_owned()))
+            .1
+    }
+
+    fn node(&mut self, idx: usize) -> &mut Vector {
+        let base = self.nodes;
+        &mut self.touched_nodes
+            .entry(idx)
+            .or_insert_with(|| (base[idx].to_owned(), base[idx].to_owned()))
+            .1
+    }
+}
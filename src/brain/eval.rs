@@ -0,0 +1,381 @@
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::path::{Path, PathBuf};
+use crate::brain::linalg::VectorBuf;
+use crate::brain::model::SemanticBrain;
+use serde::Serialize;
+
+/// Word pairs with human similarity judgements, as in WordSim-353 or SimLex-999.
+pub struct SimilarityPairs {
+    /// Reported with the score, usually the file name.
+    pub name: String,
+    pub pairs: Vec<(String, String, f32)>,
+}
+
+impl SimilarityPairs {
+    /// One `word1 word2 score` pair per line, separated by tabs or spaces. Lines whose
+    /// third field isn't a number, such as a header, are skipped, and so are `#` comments.
+    pub fn parse(name: &str, text: &str) -> Self {
+        let pairs = text
+            .lines()
+            .filter(|line| !line.trim_start().starts_with('#'))
+            .filter_map(|line| {
+                let mut fields = line.split(['\t', ' ']).filter(|f| !f.is_empty());
+                let (a, b, score) = (fields.next()?, fields.next()?, fields.next()?);
+                Some((a.to_string(), b.to_string(), score.parse().ok()?))
+            })
+            .collect();
+        Self { name: name.to_string(), pairs }
+    }
+}
+
+/// Questions in the Google `questions-words.txt` format: `: section` headers followed by
+/// `a b c d` lines, read as "a is to b as c is to d".
+pub struct AnalogyQuestions {
+    pub sections: Vec<AnalogySection>,
+}
+
+pub struct AnalogySection {
+    pub name: String,
+    pub questions: Vec<[String; 4]>,
+}
+
+impl AnalogyQuestions {
+    /// Questions before the first header go in a section named `default`.
+    pub fn parse(text: &str) -> Self {
+        let mut sections: Vec<AnalogySection> = Vec::new();
+        for line in text.lines() {
+            if let Some(name) = line.strip_prefix(':') {
+                sections.push(AnalogySection { name: name.trim().to_string(), questions: Vec::new() });
+                continue;
+            }
+            let words: Vec<&str> = line.split_whitespace().collect();
+            let [a, b, c, d] = words[..] else {
+                continue;
+            };
+            if sections.is_empty() {
+                sections.push(AnalogySection { name: "default".to_string(), questions: Vec::new() });
+            }
+            if let Some(section) = sections.last_mut() {
+                section.questions.push([a, b, c, d].map(str::to_string));
+            }
+        }
+        Self { sections }
+    }
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct SimilarityScore {
+    pub name: String,
+    pub pairs: usize,
+    /// Pairs with both words in the vocabulary; only these are scored.
+    pub covered: usize,
+    /// Spearman correlation between the gold scores and cosine similarity. `None` with
+    /// fewer than two covered pairs.
+    pub spearman: Option<f32>,
+}
+
+/// Answers `score_analogies` searches by default, as in word2vec's `compute-accuracy`.
+pub const ANALOGY_CANDIDATES: usize = 30_000;
+
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct AnalogyScore {
+    pub questions: usize,
+    /// Questions with all four words among the candidates; only these are scored.
+    pub covered: usize,
+    pub correct: usize,
+    /// `correct / covered`.
+    pub accuracy: f32,
+}
+
+impl AnalogyScore {
+    fn add(&mut self, other: &AnalogyScore) {
+        self.questions += other.questions;
+        self.covered += other.covered;
+        self.correct += other.correct;
+        self.accuracy = if self.covered > 0 { self.correct as f32 / self.covered as f32 } else { 0.0 };
+    }
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct SectionScore {
+    pub section: String,
+    #[serde(flatten)]
+    pub score: AnalogyScore,
+}
+
+/// Accuracy over all questions, split into the semantic sections and the syntactic
+/// `gram*` ones the way the Google set is usually reported, and per section.
+#[derive(Clone, Debug, Serialize)]
+pub struct AnalogyReport {
+    #[serde(flatten)]
+    pub total: AnalogyScore,
+    pub semantic: AnalogyScore,
+    pub syntactic: AnalogyScore,
+    pub sections: Vec<SectionScore>,
+}
+
+/// Where benchmarks are looked up when none are named: `questions-words.txt` for
+/// analogies and every `.tsv` file for word similarity.
+pub const EVAL_DIR: &str = "data/eval";
+
+/// The benchmarks a model is scored on: at most one analogy set and any number of
+/// similarity sets.
+#[derive(Default)]
+pub struct Benchmarks {
+    pub analogies: Option<AnalogyQuestions>,
+    pub similarity: Vec<SimilarityPairs>,
+    /// Answers searched per analogy question; see `score_analogies`.
+    pub candidates: usize,
+}
+
+impl Benchmarks {
+    /// Similarity sets are named after their file.
+    pub fn load(analogies: Option<&Path>, similarity: &[PathBuf]) -> Result<Self, String> {
+        let read = |path: &Path| fs::read_to_string(path).map_err(|e| format!("Cannot read {}: {}", path.display(), e));
+        let analogies = analogies.map(read).transpose()?.map(|text| AnalogyQuestions::parse(&text));
+        let similarity = similarity
+            .iter()
+            .map(|path| {
+                let name = path.file_name().map_or_else(|| path.display().to_string(), |n| n.to_string_lossy().into_owned());
+                read(path).map(|text| SimilarityPairs::parse(&name, &text))
+            })
+            .collect::<Result<_, _>>()?;
+        Ok(Self { analogies, similarity, candidates: ANALOGY_CANDIDATES })
+    }
+
+    /// Like `load`, with the defaults in `EVAL_DIR` standing in for whatever isn't named.
+    /// Named files must exist; missing defaults are left out.
+    pub fn load_or_default(analogies: Option<PathBuf>, similarity: Option<Vec<PathBuf>>) -> Result<Self, String> {
+        let dir = Path::new(EVAL_DIR);
+        let analogies = analogies.or_else(|| Some(dir.join("questions-words.txt")).filter(|path| path.exists()));
+        let similarity = similarity.unwrap_or_else(|| {
+            let mut paths: Vec<PathBuf> = fs::read_dir(dir)
+                .map(|entries| entries.filter_map(|e| e.ok().map(|e| e.path())).collect())
+                .unwrap_or_default();
+            paths.retain(|path| path.extension().is_some_and(|ext| ext == "tsv"));
+            paths.sort();
+            paths
+        });
+        Self::load(analogies.as_deref(), &similarity)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.analogies.is_none() && self.similarity.is_empty()
+    }
+
+    pub fn score(&self, brain: &SemanticBrain) -> BenchmarkScores {
+        BenchmarkScores {
+            analogy: self.analogies.as_ref().map(|questions| score_analogies(brain, questions, self.candidates)),
+            similarity: self.similarity.iter().map(|pairs| score_similarity(brain, pairs)).collect(),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct BenchmarkScores {
+    pub analogy: Option<AnalogyReport>,
+    pub similarity: Vec<SimilarityScore>,
+}
+
+/// Difference between two scorings of the same benchmarks, after minus before.
+#[derive(Clone, Debug, Serialize)]
+pub struct ScoreChange {
+    pub analogy_accuracy: Option<f32>,
+    /// Keyed by similarity set, for sets with a correlation both times.
+    pub spearman: BTreeMap<String, f32>,
+}
+
+impl BenchmarkScores {
+    pub fn change_since(&self, before: &BenchmarkScores) -> ScoreChange {
+        let accuracy = |scores: &BenchmarkScores| scores.analogy.as_ref().map(|a| a.total.accuracy);
+        let spearman = self
+            .similarity
+            .iter()
+            .filter_map(|after| {
+                let old = before.similarity.iter().find(|s| s.name == after.name)?.spearman?;
+                Some((after.name.clone(), after.spearman? - old))
+            })
+            .collect();
+        ScoreChange {
+            analogy_accuracy: accuracy(self).zip(accuracy(before)).map(|(after, before)| after - before),
+            spearman,
+        }
+    }
+}
+
+pub fn score_similarity(brain: &SemanticBrain, benchmark: &SimilarityPairs) -> SimilarityScore {
+    let mut gold = Vec::new();
+    let mut predicted = Vec::new();
+    for (a, b, score) in &benchmark.pairs {
+        let (Some(&ia), Some(&ib)) = (brain.vocabulary.get(&brain.key(a)), brain.vocabulary.get(&brain.key(b))) else {
+            continue;
+        };
+        let (va, vb) = (&brain.embeddings[ia], &brain.embeddings[ib]);
+        let cosine = va.dot(vb) / (va.length() * vb.length()).max(1e-12);
+        if cosine.is_finite() {
+            gold.push(*score);
+            predicted.push(cosine);
+        }
+    }
+    SimilarityScore {
+        name: benchmark.name.clone(),
+        pairs: benchmark.pairs.len(),
+        covered: gold.len(),
+        spearman: spearman(&gold, &predicted),
+    }
+}
+
+/// Counts a question as correct when the nearest word to `b - a + c`, leaving out the
+/// three question words, is `d`. Like the original word2vec tool, answers are searched
+/// among the `candidates` most frequent words only, and questions using any other word
+/// are left out.
+pub fn score_analogies(brain: &SemanticBrain, benchmark: &AnalogyQuestions, candidates: usize) -> AnalogyReport {
+    let mut by_count = brain.indices_by_count();
+    by_count.truncate(candidates);
+    let mut rows: Vec<(usize, VectorBuf)> = Vec::with_capacity(by_count.len());
+    for idx in by_count {
+        let mut v = brain.embeddings[idx].to_owned();
+        v.normalize();
+        if v.is_finite() {
+            rows.push((idx, v));
+        }
+    }
+    let position: HashMap<usize, usize> = rows.iter().enumerate().map(|(pos, (idx, _))| (*idx, pos)).collect();
+
+    let mut report = AnalogyReport {
+        total: AnalogyScore::default(),
+        semantic: AnalogyScore::default(),
+        syntactic: AnalogyScore::default(),
+        sections: Vec::new(),
+    };
+    for section in &benchmark.sections {
+        let mut score = AnalogyScore { questions: section.questions.len(), ..AnalogyScore::default() };
+        for question in &section.questions {
+            let found: Vec<usize> = question
+                .iter()
+                .filter_map(|w| brain.vocabulary.get(&brain.key(w)))
+                .filter_map(|idx| position.get(idx).copied())
+                .collect();
+            let [a, b, c, d] = found[..] else {
+                continue;
+            };
+            score.covered += 1;
+
+            let mut target = rows[b].1.clone();
+            target.sub_assign(&rows[a].1);
+            target.add(&rows[c].1);
+            let mut best = None;
+            let mut best_sim = f32::NEG_INFINITY;
+            for (pos, (_, v)) in rows.iter().enumerate() {
+                if pos == a || pos == b || pos == c {
+                    continue;
+                }
+                let sim = target.dot(v);
+                if sim > best_sim {
+                    best_sim = sim;
+                    best = Some(pos);
+                }
+            }
+            if best == Some(d) {
+                score.correct += 1;
+            }
+        }
+        score.accuracy = if score.covered > 0 { score.correct as f32 / score.covered as f32 } else { 0.0 };
+
+        report.total.add(&score);
+        if section.name.starts_with("gram") {
+            report.syntactic.add(&score);
+        } else {
+            report.semantic.add(&score);
+        }
+        report.sections.push(SectionScore { section: section.name.clone(), score });
+    }
+    report
+}
+
+/// Pearson correlation of the ranks, with tied values sharing their mean rank.
+fn spearman(x: &[f32], y: &[f32]) -> Option<f32> {
+    if x.len() < 2 || x.len() != y.len() {
+        return None;
+    }
+    let (rx, ry) = (ranks(x), ranks(y));
+    let n = rx.len() as f64;
+    let (mx, my) = (rx.iter().sum::<f64>() / n, ry.iter().sum::<f64>() / n);
+    let mut cov = 0.0;
+    let mut vx = 0.0;
+    let mut vy = 0.0;
+    for (a, b) in rx.iter().zip(&ry) {
+        cov += (a - mx) * (b - my);
+        vx += (a - mx).powi(2);
+        vy += (b - my).powi(2);
+    }
+    if vx == 0.0 || vy == 0.0 {
+        return None;
+    }
+    Some((cov / (vx * vy).sqrt()) as f32)
+}
+
+fn ranks(values: &[f32]) -> Vec<f64> {
+    let mut order: Vec<usize> = (0..values.len()).collect();
+    order.sort_by(|&a, &b| values[a].total_cmp(&values[b]));
+    let mut ranks = vec![0.0; values.len()];
+    let mut start = 0;
+    while start < order.len() {
+        let mut end = start + 1;
+        while end < order.len() && values[order[end]] == values[order[start]] {
+            end += 1;
+        }
+        let rank = (start + end + 1) as f64 / 2.0;
+        for &i in &order[start..end] {
+            ranks[i] = rank;
+        }
+        start = end;
+    }
+    ranks
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ties_share_their_mean_rank() {
+        assert_eq!(ranks(&[3.0, 1.0, 3.0, 2.0]), [3.5, 1.0, 3.5, 2.0]);
+        assert_eq!(ranks(&[5.0, 5.0, 5.0]), [2.0, 2.0, 2.0]);
+    }
+
+    #[test]
+    fn spearman_correlates_ranks() {
+        assert_eq!(spearman(&[1.0, 2.0, 3.0, 4.0], &[10.0, 20.0, 35.0, 90.0]), Some(1.0));
+        assert_eq!(spearman(&[1.0, 2.0, 3.0, 4.0], &[4.0, 3.0, 2.0, 1.0]), Some(-1.0));
+        // Ranks 1 2 3 against 1.5 1.5 3: cov 1.5, variances 2 and 1.5.
+        let tied = spearman(&[1.0, 2.0, 3.0], &[1.0, 1.0, 2.0]).unwrap();
+        assert!((tied - 1.5 / 3f32.sqrt()).abs() < 1e-6, "{}", tied);
+    }
+
+    #[test]
+    fn spearman_needs_two_varying_pairs() {
+        assert_eq!(spearman(&[1.0], &[2.0]), None);
+        assert_eq!(spearman(&[1.0, 2.0], &[1.0]), None);
+        assert_eq!(spearman(&[1.0, 2.0, 3.0], &[7.0, 7.0, 7.0]), None);
+    }
+
+    #[test]
+    fn similarity_parse_reads_the_first_three_fields() {
+        let text = "Word 1\tWord 2\tHuman (mean)\n# comment\ntiger cat 7.35\nbook\t\tpaper  7.46\nold\tnew\t1.58\t2.72\nlonely\n";
+        let set = SimilarityPairs::parse("set.tsv", text);
+        assert_eq!(set.name, "set.tsv");
+        let pairs: Vec<(&str, &str, f32)> = set.pairs.iter().map(|(a, b, s)| (a.as_str(), b.as_str(), *s)).collect();
+        assert_eq!(pairs, [("tiger", "cat", 7.35), ("book", "paper", 7.46), ("old", "new", 1.58)]);
+    }
+
+    #[test]
+    fn analogy_parse_groups_questions_by_section() {
+        let text = "a b c d\n: capital-common-countries\nAthens Greece Baghdad Iraq\n\ntoo few words\none two three four five\n: gram1-adjective-to-adverb\nquick quickly slow slowly\n";
+        let set = AnalogyQuestions::parse(text);
+        let sections: Vec<(&str, usize)> = set.sections.iter().map(|s| (s.name.as_str(), s.questions.len())).collect();
+        assert_eq!(sections, [("default", 1), ("capital-common-countries", 1), ("gram1-adjective-to-adverb", 1)]);
+        assert_eq!(set.sections[1].questions[0], ["Athens", "Greece", "Baghdad", "Iraq"].map(str::to_string));
+    }
+}
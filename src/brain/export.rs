@@ -0,0 +1,228 @@
+use std::borrow::Cow;
+use std::io::{self, BufRead, Write};
+use std::path::Path;
+use crate::brain::linalg::VectorBuf;
+use crate::brain::model::SemanticBrain;
+use crate::brain::tokenizer::PHRASE_SEPARATOR;
+use serde::Serialize;
+
+/// Largest vector size accepted from a binary file's header. Trained models stay far
+/// below it, and a damaged header would otherwise size a read buffer from garbage.
+const MAX_BINARY_DIM: usize = 10_000;
+
+/// Plain vector files other tools read and write.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum VectorFormat {
+    /// A `count dim` header line, then `word v1 v2 …` per line.
+    Word2vecText,
+    /// The same header, then each word, a space and `dim` little-endian `f32`s.
+    Word2vecBinary,
+    /// `word v1 v2 …` per line with no header.
+    Glove,
+}
+
+impl VectorFormat {
+    /// `word2vec` (or `txt`), `word2vec_bin` (or `bin`) and `glove`.
+    pub fn parse(name: &str) -> Result<Self, String> {
+        match name {
+            "word2vec" | "word2vec_text" | "txt" => Ok(VectorFormat::Word2vecText),
+            "word2vec_bin" | "word2vec_binary" | "bin" => Ok(VectorFormat::Word2vecBinary),
+            "glove" => Ok(VectorFormat::Glove),
+            other => Err(format!("Unknown vector format '{}', expected word2vec, word2vec_bin or glove", other)),
+        }
+    }
+
+    /// Binary word2vec for `.bin` files, text word2vec otherwise.
+    pub fn from_path(path: &Path) -> Self {
+        if path.extension().is_some_and(|ext| ext.eq_ignore_ascii_case("bin")) {
+            VectorFormat::Word2vecBinary
+        } else {
+            VectorFormat::Word2vecText
+        }
+    }
+}
+
+/// Which table an export writes.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum VectorSet {
+    /// `embeddings` alone, the vectors every lookup uses.
+    #[default]
+    Target,
+    /// The mean of each word's target and context vectors, as GloVe's `W + C` output.
+    Average,
+}
+
+impl VectorSet {
+    pub fn parse(name: &str) -> Result<Self, String> {
+        match name {
+            "target" => Ok(VectorSet::Target),
+            "average" => Ok(VectorSet::Average),
+            other => Err(format!("Unknown vector set '{}', expected target or average", other)),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct ExportStats {
+    pub words: usize,
+    /// Rows left out because they hold NaN or infinity, which other tools can't parse.
+    pub skipped: usize,
+}
+
+/// Writes the vocabulary from the most to the least frequent word, the order word2vec
+/// itself writes, so the same model always exports the same file. Whitespace inside a
+/// word, which every reader takes as the end of the word, is written as `_` like the
+/// separator inside phrases.
+pub fn write_vectors(brain: &SemanticBrain, format: VectorFormat, set: VectorSet, out: &mut impl Write) -> io::Result<ExportStats> {
+    let rows: Vec<(usize, VectorBuf)> = brain
+        .indices_by_count()
+        .into_iter()
+        .map(|idx| {
+            let mut v = brain.embeddings[idx].to_owned();
+            if set == VectorSet::Average {
+                v.add(&brain.context_embeddings[idx]);
+                v.scale(0.5);
+            }
+            (idx, v)
+        })
+        .filter(|(_, v)| v.is_finite())
+        .collect();
+    let skipped = brain.embeddings.len() - rows.len();
+
+    if format != VectorFormat::Glove {
+        writeln!(out, "{} {}", rows.len(), brain.dim)?;
+    }
+    for (idx, v) in &rows {
+        let word = file_word(brain.word(*idx).unwrap_or_default());
+        if format == VectorFormat::Word2vecBinary {
+            write!(out, "{} ", word)?;
+            for x in &v.data {
+                out.write_all(&x.to_le_bytes())?;
+            }
+            out.write_all(b"\n")?;
+        } else {
+            write!(out, "{}", word)?;
+            for x in &v.data {
+                write!(out, " {}", x)?;
+            }
+            out.write_all(b"\n")?;
+        }
+    }
+    out.flush()?;
+    Ok(ExportStats { words: rows.len(), skipped })
+}
+
+fn file_word(word: &str) -> Cow<'_, str> {
+    if word.contains(char::is_whitespace) {
+        Cow::Owned(word.split(char::is_whitespace).collect::<Vec<_>>().join(&PHRASE_SEPARATOR.to_string()))
+    } else {
+        Cow::Borrowed(word)
+    }
+}
+
+/// Words and vectors read from a vector file, in file order.
+pub struct ImportedVectors {
+    pub dim: usize,
+    pub rows: Vec<(String, VectorBuf)>,
+}
+
+/// Reads at most `limit` vectors. Text files are read the same way for both formats:
+/// a first line of exactly two integers is taken as a word2vec header and skipped.
+pub fn read_vectors(input: impl BufRead, format: VectorFormat, limit: Option<usize>) -> Result<ImportedVectors, String> {
+    match format {
+        VectorFormat::Word2vecBinary => read_binary(input, limit),
+        VectorFormat::Word2vecText | VectorFormat::Glove => read_text(input, limit),
+    }
+}
+
+fn read_text(input: impl BufRead, limit: Option<usize>) -> Result<ImportedVectors, String> {
+    let mut rows: Vec<(String, VectorBuf)> = Vec::new();
+    let mut dim = None;
+    for (n, line) in input.lines().enumerate() {
+        if limit.is_some_and(|limit| rows.len() >= limit) {
+            break;
+        }
+        let line = line.map_err(|e| format!("Line {}: {}", n + 1, e))?;
+        let fields: Vec<&str> = line.split_whitespace().collect();
+        let Some((word, values)) = fields.split_first() else {
+            continue;
+        };
+        if n == 0 && values.len() == 1 && word.parse::<usize>().is_ok() && values[0].parse::<usize>().is_ok() {
+            continue;
+        }
+        let data = values
+            .iter()
+            .map(|x| x.parse::<f32>())
+            .collect::<Result<Vec<f32>, _>>()
+            .map_err(|e| format!("Line {}: {}", n + 1, e))?;
+        let expected = *dim.get_or_insert(data.len());
+        if data.len() != expected || expected == 0 {
+            return Err(format!("Line {}: {} values where earlier lines have {}", n + 1, data.len(), expected));
+        }
+        rows.push((word.to_string(), VectorBuf::new(data)));
+    }
+    Ok(ImportedVectors { dim: dim.unwrap_or(0), rows })
+}
+
+fn read_binary(mut input: impl BufRead, limit: Option<usize>) -> Result<ImportedVectors, String> {
+    let mut header = String::new();
+    input.read_line(&mut header).map_err(|e| e.to_string())?;
+    let mut fields = header.split_whitespace().map(str::parse::<usize>);
+    let (Some(Ok(count)), Some(Ok(dim))) = (fields.next(), fields.next()) else {
+        return Err(format!("Expected a 'count dim' header, found '{}'", header.trim()));
+    };
+
+    let width = dim
+        .checked_mul(4)
+        .filter(|_| (1..=MAX_BINARY_DIM).contains(&dim))
+        .ok_or_else(|| format!("Header gives {} values per vector, expected 1 to {}", dim, MAX_BINARY_DIM))?;
+
+    // `count` comes from the file too, so rows are only added as they are read.
+    let count = limit.map_or(count, |limit| count.min(limit));
+    let mut rows = Vec::new();
+    let mut values = vec![0u8; width];
+    for n in 0..count {
+        // Some writers end each vector with a newline and some don't.
+        let mut word = Vec::new();
+        input.read_until(b' ', &mut word).map_err(|e| e.to_string())?;
+        if word.last() != Some(&b' ') {
+            return Err(format!("File ends after {} of {} vectors", n, count));
+        }
+        word.pop();
+        let word = String::from_utf8_lossy(&word).trim_start().to_string();
+        input.read_exact(&mut values).map_err(|e| format!("Vector {} ({}): {}", n + 1, word, e))?;
+        let data = values.chunks_exact(4).map(|b| f32::from_le_bytes([b[0], b[1], b[2], b[3]])).collect();
+        rows.push((word, VectorBuf::new(data)));
+    }
+    Ok(ImportedVectors { dim, rows })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn binary_header_dim_is_bounded() {
+        for header in ["1 0\n", "1 10001\n", "1 4611686018427387904\n"] {
+            assert!(read_vectors(header.as_bytes(), VectorFormat::Word2vecBinary, None).is_err(), "{}", header);
+        }
+        let huge_count = b"18446744073709551615 2\nA \x00\x00\x80\x3f\x00\x00\x00\x00\n";
+        assert!(read_vectors(&huge_count[..], VectorFormat::Word2vecBinary, None).is_err());
+    }
+
+    #[test]
+    fn words_with_spaces_stay_one_field() {
+        let mut brain = SemanticBrain::with_dim(4);
+        brain.index_tokens(&["NEW YORK".to_string(), "CITY".to_string()]);
+
+        for format in [VectorFormat::Word2vecText, VectorFormat::Word2vecBinary, VectorFormat::Glove] {
+            let mut out = Vec::new();
+            write_vectors(&brain, format, VectorSet::Target, &mut out).unwrap();
+            let imported = read_vectors(&out[..], format, None).unwrap();
+            let words: Vec<&str> = imported.rows.iter().map(|(word, _)| word.as_str()).collect();
+            assert_eq!(words, ["NEW_YORK", "CITY"], "{:?}", format);
+        }
+    }
+}
@@ -0,0 +1,355 @@
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::fmt;
+use std::path::Path;
+use crate::brain::huffman::HuffmanTree;
+use crate::brain::linalg::Matrix;
+use crate::brain::model::{Loss, Objective};
+use crate::brain::subword::{SubwordConfig, SubwordTable};
+use crate::brain::tokenizer::TokenizerConfig;
+use serde::{Deserialize, Serialize};
+
+pub const MODEL_MAGIC: &[u8; 4] = b"VCRS";
+pub const FORMAT_VERSION: u32 = 6;
+/// Byte alignment of the payload and of every vector table in it, so a mapped file can
+/// be read as `f32`s in place.
+pub const TABLE_ALIGN: usize = 64;
+
+/// Stored as JSON inside the header so fields can be added without a format bump.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TrainingMetadata {
+    pub articles_processed: u64,
+    pub tokens_processed: u64,
+    pub last_title: Option<String>,
+    pub saved_at_unix: u64,
+    /// Where in the dump the weights in this file stop.
+    pub resume: ResumePoint,
+    /// How the vocabulary was tokenized; lookups normalize words the same way.
+    pub tokenizer: TokenizerConfig,
+    /// Objective of the most recent wiki run.
+    pub objective: Objective,
+    /// Output layer of the most recent wiki run.
+    pub loss: Loss,
+}
+
+/// Corpus position covered by a checkpoint. Wiki dumps list pages in ascending id order
+/// and other corpora number their documents in reading order, so everything up to
+/// `page_id` is done; workers finish out of order, so documents past it that were also
+/// trained before the checkpoint are listed in `done_ahead`.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ResumePoint {
+    /// Which corpus the ids refer to; empty for wiki dumps, as in models saved before
+    /// other corpora were supported.
+    pub corpus: String,
+    pub page_id: Option<u64>,
+    pub done_ahead: Vec<u64>,
+}
+
+impl ResumePoint {
+    pub fn is_done(&self, page_id: u64) -> bool {
+        self.page_id.is_some_and(|last| page_id <= last) || self.done_ahead.contains(&page_id)
+    }
+}
+
+/// Written in front of every model file:
+/// `MAGIC | version (u32 LE) | header length (u32 LE) | bincode(ModelHeader) | payload`.
+/// Since version 5 the header length includes zero padding that starts the payload at a
+/// multiple of `TABLE_ALIGN`.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ModelHeader {
+    pub embedding_dim: u32,
+    pub vocab_size: u64,
+    /// CRC-32 of the payload up to version 5. Since version 6 it covers `metadata_json`
+    /// and the payload before its first table, and every table has its own checksum in
+    /// its `TableSpan`, so a mapped load can check the vocabulary without reading the
+    /// vectors.
+    pub checksum: u32,
+    pub metadata_json: String,
+}
+
+impl ModelHeader {
+    /// `body` is the payload before its first table.
+    pub fn new(embedding_dim: usize, vocab_size: usize, body: &[u8], metadata: &TrainingMetadata) -> Result<Self, ModelError> {
+        let metadata_json = serde_json::to_string(metadata).map_err(|e| ModelError::Encode(e.to_string()))?;
+        Ok(Self {
+            embedding_dim: embedding_dim as u32,
+            vocab_size: vocab_size as u64,
+            checksum: crc32_update(crc32(metadata_json.as_bytes()), body),
+            metadata_json,
+        })
+    }
+
+    /// Checks a version 6 checksum against the payload before its first table.
+    pub fn verify_body(&self, body: &[u8]) -> Result<(), ModelError> {
+        let computed = crc32_update(crc32(self.metadata_json.as_bytes()), body);
+        if computed != self.checksum {
+            return Err(ModelError::ChecksumMismatch { header: self.checksum, computed });
+        }
+        Ok(())
+    }
+
+    pub fn metadata(&self) -> Result<TrainingMetadata, ModelError> {
+        serde_json::from_str(&self.metadata_json).map_err(|e| ModelError::Corrupt(format!("metadata: {}", e)))
+    }
+}
+
+pub struct ModelFile<'a> {
+    pub version: u32,
+    pub header: ModelHeader,
+    /// Offset of `payload` from the start of the file.
+    pub payload_start: usize,
+    pub payload: &'a [u8],
+}
+
+/// Payload of format versions 5 and 6: `bincode(FlatPayload) | zero padding | tables`.
+/// The tables are `embeddings`, `context_embeddings`, the subword buckets when
+/// `subwords` is set, and `nodes`, each stored as row-major little-endian `f32`s where
+/// its span says.
+#[derive(Serialize, Deserialize)]
+pub struct FlatPayload<'a, Span = TableSpan> {
+    pub vocabulary: Cow<'a, HashMap<String, usize>>,
+    pub counts: Cow<'a, [u64]>,
+    pub subwords: Option<SubwordConfig>,
+    pub huffman: Option<Cow<'a, HuffmanTree>>,
+    pub tables: Vec<Span>,
+}
+
+/// Where one table sits in a flat payload.
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+pub struct TableSpan {
+    pub rows: u64,
+    /// Bytes from the start of the payload, a multiple of `TABLE_ALIGN`.
+    pub offset: u64,
+    /// CRC-32 of the table's bytes.
+    pub checksum: u32,
+}
+
+/// `TableSpan` of version 5, whose tables were covered by the header checksum.
+#[derive(Clone, Copy, Debug, Deserialize)]
+pub struct TableSpanV5 {
+    pub rows: u64,
+    pub offset: u64,
+}
+
+impl<'a> FlatPayload<'a, TableSpanV5> {
+    /// Spans get a zero checksum; `read_model` already checked the whole payload.
+    pub fn upgrade(self) -> FlatPayload<'a> {
+        FlatPayload {
+            vocabulary: self.vocabulary,
+            counts: self.counts,
+            subwords: self.subwords,
+            huffman: self.huffman,
+            tables: self.tables.into_iter().map(|span| TableSpan { rows: span.rows, offset: span.offset, checksum: 0 }).collect(),
+        }
+    }
+}
+
+impl TableSpan {
+    /// Byte range of the table in the payload, or `None` if it doesn't fit in
+    /// `payload_len` bytes.
+    pub fn range(&self, dim: usize, payload_len: usize) -> Option<std::ops::Range<usize>> {
+        let start = usize::try_from(self.offset).ok()?;
+        let len = usize::try_from(self.rows).ok()?.checked_mul(dim)?.checked_mul(4)?;
+        let end = start.checked_add(len)?;
+        (end <= payload_len && start.is_multiple_of(4)).then_some(start..end)
+    }
+}
+
+/// Lays out a version 6 payload: the body, then each table at the next multiple of
+/// `TABLE_ALIGN`. Also returns where the first table starts, which is what the header
+/// checksum covers.
+pub fn write_flat_payload(mut body: FlatPayload<'_>, tables: &[&Matrix]) -> Result<(Vec<u8>, usize), ModelError> {
+    // Spans are fixed-width in bincode, so the body's size doesn't depend on the offsets
+    // or checksums filled in below.
+    body.tables = tables.iter().map(|table| TableSpan { rows: table.len() as u64, offset: 0, checksum: 0 }).collect();
+    let body_len = bincode::serialized_size(&body).map_err(|e| ModelError::Encode(e.to_string()))? as usize;
+    let mut end = body_len;
+    for (span, table) in body.tables.iter_mut().zip(tables) {
+        let start = end.next_multiple_of(TABLE_ALIGN);
+        span.offset = start as u64;
+        end = start + table.as_slice().len() * 4;
+    }
+
+    let mut payload = vec![0; body_len];
+    for (span, table) in body.tables.iter_mut().zip(tables) {
+        payload.resize(span.offset as usize, 0);
+        table.write_le_bytes(&mut payload);
+        span.checksum = crc32(&payload[span.offset as usize..]);
+    }
+    bincode::serialize_into(&mut payload[..body_len], &body).map_err(|e| ModelError::Encode(e.to_string()))?;
+    let body_end = body.tables.first().map_or(payload.len(), |span| span.offset as usize);
+    Ok((payload, body_end))
+}
+
+/// Field layout of the payload for format versions 0 (raw bincode, no header) and 1.
+#[derive(Deserialize)]
+pub struct PayloadV1 {
+    pub vocabulary: HashMap<String, usize>,
+    pub embeddings: Matrix,
+    pub context_embeddings: Matrix,
+}
+
+/// Payload layout of format version 2, before the optional subword table.
+#[derive(Deserialize)]
+pub struct PayloadV2 {
+    pub vocabulary: HashMap<String, usize>,
+    pub embeddings: Matrix,
+    pub context_embeddings: Matrix,
+    pub counts: Vec<u64>,
+}
+
+/// Payload layout of format version 3, before the Huffman node vectors.
+#[derive(Deserialize)]
+pub struct PayloadV3 {
+    pub vocabulary: HashMap<String, usize>,
+    pub embeddings: Matrix,
+    pub context_embeddings: Matrix,
+    pub counts: Vec<u64>,
+    pub subwords: Option<SubwordTable>,
+}
+
+/// Payload layout of format version 4, the last to bincode every vector separately.
+#[derive(Deserialize)]
+pub struct PayloadV4 {
+    pub vocabulary: HashMap<String, usize>,
+    pub embeddings: Matrix,
+    pub context_embeddings: Matrix,
+    pub counts: Vec<u64>,
+    pub subwords: Option<SubwordTable>,
+    pub huffman: Option<HuffmanTree>,
+    pub nodes: Matrix,
+}
+
+#[derive(Debug)]
+pub enum ModelError {
+    UnsupportedVersion { found: u32, supported: u32 },
+    DimensionMismatch { expected: usize, found: usize },
+    VocabMismatch { header: usize, found: usize },
+    ChecksumMismatch { header: u32, computed: u32 },
+    Truncated,
+    Corrupt(String),
+    Encode(String),
+    Io(String),
+}
+
+impl fmt::Display for ModelError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ModelError::UnsupportedVersion { found, supported } => write!(f, "Model Load Error: format version {} is newer than supported version {}", found, supported),
+            ModelError::DimensionMismatch { expected, found } => write!(f, "Model Load Error: expected {}D embeddings, found {}D", expected, found),
+            ModelError::VocabMismatch { header, found } => write!(f, "Model Load Error: header lists {} words but payload has {}", header, found),
+            ModelError::ChecksumMismatch { header, computed } => write!(f, "Model Load Error: checksum {:08x} does not match payload {:08x}", header, computed),
+            ModelError::Truncated => write!(f, "Model Load Error: file is truncated"),
+            ModelError::Corrupt(e) => write!(f, "Model Load Error: {}", e),
+            ModelError::Encode(e) => write!(f, "Model Save Error: {}", e),
+            ModelError::Io(e) => write!(f, "Model File Error: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for ModelError {}
+
+/// CRC-32 (IEEE 802.3), the same polynomial used by zip and gzip.
+pub fn crc32(bytes: &[u8]) -> u32 {
+    crc32_update(0, bytes)
+}
+
+/// Continues `crc`, the CRC-32 of some earlier bytes, over `bytes`, giving the CRC-32
+/// of the two concatenated. Processes eight bytes per step (slicing-by-8), since a load
+/// from memory checksums every table.
+pub fn crc32_update(crc: u32, bytes: &[u8]) -> u32 {
+    let mut table = [[0u32; 256]; 8];
+    for (i, entry) in table[0].iter_mut().enumerate() {
+        let mut c = i as u32;
+        for _ in 0..8 {
+            c = if c & 1 != 0 { 0xEDB8_8320 ^ (c >> 1) } else { c >> 1 };
+        }
+        *entry = c;
+    }
+    for k in 1..8 {
+        for i in 0..256 {
+            let prev = table[k - 1][i];
+            table[k][i] = (prev >> 8) ^ table[0][(prev & 0xFF) as usize];
+        }
+    }
+
+    let mut crc = !crc;
+    let mut chunks = bytes.chunks_exact(8);
+    for chunk in &mut chunks {
+        let lo = u32::from_le_bytes([chunk[0], chunk[1], chunk[2], chunk[3]]) ^ crc;
+        let hi = u32::from_le_bytes([chunk[4], chunk[5], chunk[6], chunk[7]]);
+        crc = table[7][(lo & 0xFF) as usize]
+            ^ table[6][((lo >> 8) & 0xFF) as usize]
+            ^ table[5][((lo >> 16) & 0xFF) as usize]
+            ^ table[4][(lo >> 24) as usize]
+            ^ table[3][(hi & 0xFF) as usize]
+            ^ table[2][((hi >> 8) & 0xFF) as usize]
+            ^ table[1][((hi >> 16) & 0xFF) as usize]
+            ^ table[0][(hi >> 24) as usize];
+    }
+    for &b in chunks.remainder() {
+        crc = table[0][((crc ^ b as u32) & 0xFF) as usize] ^ (crc >> 8);
+    }
+    !crc
+}
+
+pub fn write_model(header: &ModelHeader, payload: &[u8]) -> Result<Vec<u8>, ModelError> {
+    let mut header_bytes = bincode::serialize(header).map_err(|e| ModelError::Encode(e.to_string()))?;
+    header_bytes.resize((12 + header_bytes.len()).next_multiple_of(TABLE_ALIGN) - 12, 0);
+    let mut out = Vec::with_capacity(12 + header_bytes.len() + payload.len());
+    out.extend_from_slice(MODEL_MAGIC);
+    out.extend_from_slice(&FORMAT_VERSION.to_le_bytes());
+    out.extend_from_slice(&(header_bytes.len() as u32).to_le_bytes());
+    out.extend_from_slice(&header_bytes);
+    out.extend_from_slice(payload);
+    Ok(out)
+}
+
+/// Splits a model file into header and payload and verifies the checksum of versions
+/// up to 5, which covers the whole payload; later versions are checked as their payload
+/// is read. Returns `Ok(None)` for headerless files written before the format was
+/// versioned.
+pub fn read_model(bytes: &[u8]) -> Result<Option<ModelFile<'_>>, ModelError> {
+    if bytes.len() < 4 || &bytes[..4] != MODEL_MAGIC {
+        return Ok(None);
+    }
+    if bytes.len() < 12 {
+        return Err(ModelError::Truncated);
+    }
+
+    let version = u32::from_le_bytes([bytes[4], bytes[5], bytes[6], bytes[7]]);
+    if version > FORMAT_VERSION {
+        return Err(ModelError::UnsupportedVersion { found: version, supported: FORMAT_VERSION });
+    }
+
+    let header_len = u32::from_le_bytes([bytes[8], bytes[9], bytes[10], bytes[11]]) as usize;
+    let payload_start = 12 + header_len;
+    if bytes.len() < payload_start {
+        return Err(ModelError::Truncated);
+    }
+
+    let header: ModelHeader = bincode::deserialize(&bytes[12..payload_start])
+        .map_err(|e| ModelError::Corrupt(format!("header: {}", e)))?;
+    let payload = &bytes[payload_start..];
+
+    if version <= 5 {
+        let computed = crc32(payload);
+        if computed != header.checksum {
+            return Err(ModelError::ChecksumMismatch { header: header.checksum, computed });
+        }
+    }
+
+    Ok(Some(ModelFile { version, header, payload_start, payload }))
+}
+
+/// Writes `bytes` beside `path` and renames the copy over it. A process that has the
+/// old file mapped keeps reading the old contents instead of seeing them change, or
+/// crashing when the file shrinks.
+pub fn replace_file(path: &Path, bytes: &[u8]) -> Result<(), ModelError> {
+    let mut temp = path.as_os_str().to_owned();
+    temp.push(".tmp");
+    std::fs::write(&temp, bytes).map_err(|e| ModelError::Io(format!("{}: {}", Path::new(&temp).display(), e)))?;
+    std::fs::rename(&temp, path).map_err(|e| ModelError::Io(format!("{}: {}", path.display(), e)))
+}
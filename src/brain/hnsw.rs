@@ -0,0 +1,420 @@
+use std::cmp::{Ordering, Reverse};
+use std::collections::{BinaryHeap, HashSet};
+use crate::brain::format::crc32_update;
+use crate::brain::linalg::{Matrix, Vector};
+use serde::{Deserialize, Serialize};
+
+const MAX_LEVEL: usize = 16;
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct HnswParams {
+    /// Links kept per node on the upper layers (layer 0 keeps twice as many).
+    pub m: usize,
+    /// Beam width used while inserting nodes.
+    pub ef_construction: usize,
+    /// Beam width used while answering queries.
+    pub ef_search: usize,
+}
+
+impl Default for HnswParams {
+    fn default() -> Self {
+        Self {
+            m: 16,
+            ef_construction: 200,
+            ef_search: 64,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct RecallReport {
+    pub k: usize,
+    pub queries: usize,
+    pub recall: f32,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+struct Scored {
+    sim: f32,
+    id: u32,
+}
+
+impl Eq for Scored {}
+
+impl PartialOrd for Scored {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Scored {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.sim.total_cmp(&other.sim).then_with(|| other.id.cmp(&self.id))
+    }
+}
+
+/// Hierarchical navigable small world graph over the rows of an embedding table.
+/// The graph only stores links; vectors are always read from the table passed in,
+/// so node ids are the same indices used by `SemanticBrain::vocabulary`.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct HnswIndex {
+    pub params: HnswParams,
+    links: Vec<Vec<Vec<u32>>>,
+    entry_point: Option<u32>,
+    max_level: usize,
+}
+
+/// On-disk form of an index: the graph plus the shape and CRC-32 of the embedding table
+/// it was written against, since the links mean nothing for any other vectors.
+#[derive(Serialize)]
+struct IndexFileRef<'a> {
+    rows: u64,
+    checksum: u32,
+    index: &'a HnswIndex,
+}
+
+#[derive(Deserialize)]
+struct IndexFile {
+    rows: u64,
+    checksum: u32,
+    index: HnswIndex,
+}
+
+/// CRC-32 of the table's little-endian bytes, as `format` would write them.
+fn table_checksum(embeddings: &Matrix) -> u32 {
+    let mut bytes = Vec::new();
+    embeddings.as_slice().chunks(1 << 16).fold(0, |crc, chunk| {
+        bytes.clear();
+        bytes.extend(chunk.iter().flat_map(|x| x.to_le_bytes()));
+        crc32_update(crc, &bytes)
+    })
+}
+
+fn similarity(a: &Vector, b: &Vector) -> f32 {
+    let sim = a.dot(b);
+    if sim.is_nan() { f32::NEG_INFINITY } else { sim }
+}
+
+impl HnswIndex {
+    pub fn new(params: HnswParams) -> Self {
+        Self {
+            params,
+            links: Vec::new(),
+            entry_point: None,
+            max_level: 0,
+        }
+    }
+
+    pub fn build(params: HnswParams, embeddings: &Matrix) -> Self {
+        let mut index = Self::new(params);
+        for id in 0..embeddings.len() {
+            index.insert(id, embeddings);
+        }
+        index
+    }
+
+    pub fn len(&self) -> usize {
+        self.links.len()
+    }
+
+    #[allow(dead_code)]
+    pub fn is_empty(&self) -> bool {
+        self.links.is_empty()
+    }
+
+    /// Reads an index saved by `to_bytes`, refusing it unless `embeddings` is the exact
+    /// table it was saved with.
+    #[allow(dead_code)]
+    pub fn from_bytes(bytes: &[u8], embeddings: &Matrix) -> Result<Self, String> {
+        let file: IndexFile = bincode::deserialize(bytes).map_err(|e| format!("Index Load Error: {}", e))?;
+        if file.rows != embeddings.len() as u64 || file.index.len() != embeddings.len() {
+            return Err(format!("Index Load Error: index was saved for {} words but the model has {}", file.rows, embeddings.len()));
+        }
+        let computed = table_checksum(embeddings);
+        if file.checksum != computed {
+            return Err(format!("Index Load Error: index was saved for vectors with checksum {:08x}, the model's are {:08x}", file.checksum, computed));
+        }
+        Ok(file.index)
+    }
+
+    /// Serializes the graph along with the size and checksum of `embeddings`, which must
+    /// be the rows it was built over.
+    #[allow(dead_code)]
+    pub fn to_bytes(&self, embeddings: &Matrix) -> Result<Vec<u8>, String> {
+        if self.len() != embeddings.len() {
+            return Err(format!("Index Save Error: index covers {} rows but the table has {}", self.len(), embeddings.len()));
+        }
+        let file = IndexFileRef { rows: embeddings.len() as u64, checksum: table_checksum(embeddings), index: self };
+        bincode::serialize(&file).map_err(|e| format!("Index Save Error: {}", e))
+    }
+
+    fn max_connections(&self, layer: usize) -> usize {
+        if layer == 0 { self.params.m * 2 } else { self.params.m }
+    }
+
+    fn random_level(&self, id: usize) -> usize {
+        // SplitMix64 of the node id keeps levels reproducible across rebuilds.
+        let mut z = (id as u64).wrapping_add(0x9E37_79B9_7F4A_7C15);
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^= z >> 31;
+        let u = ((z >> 11) as f64 + 1.0) / (1u64 << 53) as f64;
+        let ml = 1.0 / (self.params.m.max(2) as f64).ln();
+        ((-u.ln() * ml) as usize).min(MAX_LEVEL)
+    }
+
+    /// Adds the row `id` of `embeddings` to the graph. Rows must be inserted in order;
+    /// ids that are already indexed are ignored.
+    pub fn insert(&mut self, id: usize, embeddings: &Matrix) {
+        if id < self.links.len() { return; }
+        while self.links.len() < id {
+            let missing = self.links.len();
+            self.insert(missing, embeddings);
+        }
+
+        let level = self.random_level(id);
+        self.links.push(vec![Vec::new(); level + 1]);
+
+        let entry = match self.entry_point {
+            Some(ep) => ep,
+            None => {
+                self.entry_point = Some(id as u32);
+                self.max_level = level;
+                return;
+            }
+        };
+
+        let query = &embeddings[id];
+        let mut entry_points = vec![Scored { sim: similarity(query, &embeddings[entry as usize]), id: entry }];
+
+        for layer in (level + 1..=self.max_level).rev() {
+            entry_points = self.search_layer(query, &entry_points, 1, layer, embeddings);
+        }
+
+        for layer in (0..=level.min(self.max_level)).rev() {
+            let candidates = self.search_layer(query, &entry_points, self.params.ef_construction, layer, embeddings);
+            let neighbours = Self::select_neighbours(&candidates, self.params.m, embeddings);
+            self.links[id][layer] = neighbours.clone();
+
+            let max_conn = self.max_connections(layer);
+            for n in neighbours {
+                let node_links = &mut self.links[n as usize][layer];
+                node_links.push(id as u32);
+                if node_links.len() > max_conn {
+                    self.shrink_links(n as usize, layer, max_conn, embeddings);
+                }
+            }
+            entry_points = candidates;
+        }
+
+        if level > self.max_level {
+            self.max_level = level;
+            self.entry_point = Some(id as u32);
+        }
+    }
+
+    /// Returns up to `k` `(id, similarity)` pairs, most similar first.
+    pub fn search(&self, query: &Vector, k: usize, embeddings: &Matrix) -> Vec<(usize, f32)> {
+        let entry = match self.entry_point {
+            Some(ep) => ep,
+            None => return vec![],
+        };
+
+        let mut entry_points = vec![Scored { sim: similarity(query, &embeddings[entry as usize]), id: entry }];
+        for layer in (1..=self.max_level).rev() {
+            entry_points = self.search_layer(query, &entry_points, 1, layer, embeddings);
+        }
+
+        let ef = self.params.ef_search.max(k);
+        let mut results = self.search_layer(query, &entry_points, ef, 0, embeddings);
+        results.retain(|s| s.sim.is_finite());
+        results.truncate(k);
+        results.into_iter().map(|s| (s.id as usize, s.sim)).collect()
+    }
+
+    /// Compares the graph against an exact scan for `samples` evenly spaced query rows.
+    pub fn recall_at_k(&self, embeddings: &Matrix, k: usize, samples: usize) -> RecallReport {
+        let total = self.len().min(embeddings.len());
+        if total < 2 || k == 0 || samples == 0 {
+            return RecallReport { k, queries: 0, recall: 0.0 };
+        }
+
+        let stride = (total / samples.min(total)).max(1);
+        let mut queries = 0;
+        let mut hits = 0;
+        let mut expected = 0;
+
+        for q in (0..total).step_by(stride).take(samples) {
+            let query = &embeddings[q];
+            if !query.is_finite() { continue; }
+
+            let mut exact: Vec<(usize, f32)> = (0..total)
+                .filter(|&i| i != q)
+                .map(|i| (i, similarity(query, &embeddings[i])))
+                .filter(|(_, sim)| sim.is_finite())
+                .collect();
+            exact.sort_by(|a, b| b.1.total_cmp(&a.1));
+            exact.truncate(k);
+
+            let approx: HashSet<usize> = self.search(query, k + 1, embeddings)
+                .into_iter()
+                .map(|(i, _)| i)
+                .filter(|&i| i != q)
+                .collect();
+
+            hits += exact.iter().filter(|(i, _)| approx.contains(i)).count();
+            expected += exact.len();
+            queries += 1;
+        }
+
+        let recall = if expected > 0 { hits as f32 / expected as f32 } else { 0.0 };
+        RecallReport { k, queries, recall }
+    }
+
+    fn search_layer(&self, query: &Vector, entry_points: &[Scored], ef: usize, layer: usize, embeddings: &Matrix) -> Vec<Scored> {
+        let ef = ef.max(1);
+        let mut visited: HashSet<u32> = HashSet::new();
+        let mut candidates: BinaryHeap<Scored> = BinaryHeap::new();
+        let mut results: BinaryHeap<Reverse<Scored>> = BinaryHeap::new();
+
+        for &ep in entry_points {
+            if visited.insert(ep.id) {
+                candidates.push(ep);
+                results.push(Reverse(ep));
+            }
+        }
+        while results.len() > ef {
+            results.pop();
+        }
+
+        while let Some(current) = candidates.pop() {
+            let worst = results.peek().map(|r| r.0.sim).unwrap_or(f32::NEG_INFINITY);
+            if current.sim < worst && results.len() >= ef {
+                break;
+            }
+
+            let node_links = match self.links[current.id as usize].get(layer) {
+                Some(l) => l,
+                None => continue,
+            };
+
+            for &n in node_links {
+                if !visited.insert(n) { continue; }
+                let sim = similarity(query, &embeddings[n as usize]);
+                let worst = results.peek().map(|r| r.0.sim).unwrap_or(f32::NEG_INFINITY);
+                if results.len() < ef || sim > worst {
+                    let scored = Scored { sim, id: n };
+                    candidates.push(scored);
+                    results.push(Reverse(scored));
+                    if results.len() > ef {
+                        results.pop();
+                    }
+                }
+            }
+        }
+
+        let mut out: Vec<Scored> = results.into_iter().map(|r| r.0).collect();
+        out.sort_by(|a, b| b.cmp(a));
+        out
+    }
+
+    /// Neighbour selection heuristic from the HNSW paper: prefer candidates that are closer
+    /// to the query than to anything already selected, then top up with the rest.
+    fn select_neighbours(candidates: &[Scored], m: usize, embeddings: &Matrix) -> Vec<u32> {
+        let mut selected: Vec<u32> = Vec::with_capacity(m);
+        let mut pruned: Vec<u32> = Vec::new();
+
+        for c in candidates {
+            if selected.len() >= m { break; }
+            let c_vec = &embeddings[c.id as usize];
+            let diverse = selected
+                .iter()
+                .all(|&s| similarity(c_vec, &embeddings[s as usize]) < c.sim);
+            if diverse {
+                selected.push(c.id);
+            } else {
+                pruned.push(c.id);
+            }
+        }
+
+        for p in pruned {
+            if selected.len() >= m { break; }
+            selected.push(p);
+        }
+        selected
+    }
+
+    fn shrink_links(&mut self, node: usize, layer: usize, max_conn: usize, embeddings: &Matrix) {
+        let node_vec = &embeddings[node];
+        let mut scored: Vec<Scored> = self.links[node][layer]
+            .iter()
+            .map(|&n| Scored { sim: similarity(node_vec, &embeddings[n as usize]), id: n })
+            .collect();
+        scored.sort_by(|a, b| b.cmp(a));
+        self.links[node][layer] = Self::select_neighbours(&scored, max_conn, embeddings);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::brain::model::SemanticBrain;
+
+    fn brain(words: usize) -> SemanticBrain {
+        let mut brain = SemanticBrain::with_dim(16);
+        let tokens: Vec<String> = (0..words)
+            .map(|i| brain.key(&format!("w{}{}", (b'a' + (i / 26) as u8) as char, (b'a' + (i % 26) as u8) as char)))
+            .collect();
+        brain.index_tokens(&tokens);
+        brain
+    }
+
+    #[test]
+    fn search_matches_exact_scan() {
+        let mut brain = brain(600);
+        brain.build_index(HnswParams::default());
+
+        let (mut hits, mut expected) = (0, 0);
+        for word in brain.words().iter().step_by(30) {
+            let exact: HashSet<String> = brain.find_most_similar_exact(word, 10).into_iter().map(|(w, _)| w).collect();
+            let approx = brain.find_most_similar(word, 10);
+            assert_eq!(approx.len(), 10);
+            hits += approx.iter().filter(|(w, _)| exact.contains(w)).count();
+            expected += exact.len();
+        }
+        assert!(hits as f32 / expected as f32 >= 0.9, "recall {}/{}", hits, expected);
+    }
+
+    #[test]
+    fn saved_index_only_loads_for_its_vectors() {
+        let mut brain = brain(200);
+        brain.build_index(HnswParams::default());
+        let bytes = brain.ann_index.as_ref().unwrap().to_bytes(&brain.embeddings).unwrap();
+        assert_eq!(HnswIndex::from_bytes(&bytes, &brain.embeddings).unwrap().len(), 200);
+
+        let mut grown = brain.clone();
+        grown.index_tokens(&["extra".to_string()]);
+        assert!(HnswIndex::from_bytes(&bytes, &grown.embeddings).is_err());
+
+        let mut trained = brain.clone();
+        trained.train_step("wab", &["wac".to_string()], &[], 0.025, 2);
+        assert!(HnswIndex::from_bytes(&bytes, &trained.embeddings).is_err());
+    }
+
+    #[test]
+    fn training_marks_the_index_stale() {
+        let mut brain = brain(200);
+        brain.build_index(HnswParams::default());
+        assert!(!brain.index_is_stale());
+
+        brain.index_tokens(&["extra".to_string()]);
+        assert!(!brain.index_is_stale());
+
+        brain.train_step("wab", &["wac".to_string()], &[], 0.025, 2);
+        assert!(brain.index_is_stale());
+        assert_eq!(brain.find_most_similar("wab", 5), brain.find_most_similar_exact("wab", 5));
+
+        brain.build_index(HnswParams::default());
+        assert!(!brain.index_is_stale());
+    }
+}
@@ -0,0 +1,120 @@
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+use serde::{Deserialize, Serialize};
+
+/// Huffman tree over the vocabulary for hierarchical softmax. Leaves are word rows;
+/// internal node `i` owns row `i` of the brain's node vectors, with the root last.
+/// Frequent words get short paths, so they cost fewer updates.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct HuffmanTree {
+    words: usize,
+    /// Parent of every leaf and internal node, as an internal node index.
+    parent: Vec<u32>,
+    /// Which side of its parent each leaf and internal node hangs on.
+    branch: Vec<bool>,
+}
+
+impl HuffmanTree {
+    pub fn build(counts: &[u64]) -> Self {
+        let words = counts.len();
+        let total = (2 * words).saturating_sub(1);
+        let mut parent = vec![0u32; total];
+        let mut branch = vec![false; total];
+
+        // Ties break on the lower id, so the same counts always give the same tree.
+        let mut heap: BinaryHeap<Reverse<(u64, usize)>> =
+            counts.iter().enumerate().map(|(id, &count)| Reverse((count.max(1), id))).collect();
+        let mut next = words;
+        while heap.len() > 1 {
+            let Reverse((left_count, left)) = heap.pop().expect("heap has two entries");
+            let Reverse((right_count, right)) = heap.pop().expect("heap has two entries");
+            let node = (next - words) as u32;
+            parent[left] = node;
+            parent[right] = node;
+            branch[right] = true;
+            heap.push(Reverse((left_count + right_count, next)));
+            next += 1;
+        }
+
+        Self { words, parent, branch }
+    }
+
+    /// Number of words the tree was built over.
+    pub fn words(&self) -> usize {
+        self.words
+    }
+
+    /// Internal nodes, which is how many node vectors the tree needs.
+    pub fn nodes(&self) -> usize {
+        self.words.saturating_sub(1)
+    }
+
+    /// Whether `word` is a leaf, i.e. was in the vocabulary when the tree was built and
+    /// has a path to predict.
+    pub fn covers(&self, word: usize) -> bool {
+        word < self.words && self.words >= 2
+    }
+
+    /// Internal nodes from `word` up to the root, each with the label the node has to
+    /// predict on the way: 1.0 for a left turn and 0.0 for a right one, as in word2vec.
+    /// Empty for words added after the tree was built.
+    pub fn path(&self, word: usize) -> Vec<(usize, f32)> {
+        let mut path = Vec::new();
+        if !self.covers(word) {
+            return path;
+        }
+        let root = self.words - 2;
+        let mut current = word;
+        loop {
+            let node = self.parent[current] as usize;
+            path.push((node, if self.branch[current] { 0.0 } else { 1.0 }));
+            if node == root {
+                break;
+            }
+            current = self.words + node;
+        }
+        path
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn paths_end_at_the_root() {
+        let counts = [5, 1, 1, 10, 3, 0];
+        let tree = HuffmanTree::build(&counts);
+        assert_eq!((tree.words(), tree.nodes()), (6, 5));
+
+        let root = tree.nodes() - 1;
+        let paths: Vec<Vec<(usize, f32)>> = (0..counts.len()).map(|word| tree.path(word)).collect();
+        for path in &paths {
+            assert_eq!(path.last().map(|&(node, _)| node), Some(root));
+            assert!(path.iter().all(|&(node, _)| node <= root));
+        }
+        // Codes read from the root down differ for every word.
+        let codes: std::collections::HashSet<Vec<bool>> =
+            paths.iter().map(|path| path.iter().rev().map(|&(_, label)| label == 1.0).collect()).collect();
+        assert_eq!(codes.len(), counts.len());
+
+        assert!(tree.path(counts.len()).is_empty());
+        assert!(!tree.covers(counts.len()));
+    }
+
+    #[test]
+    fn frequent_words_get_shorter_paths() {
+        let tree = HuffmanTree::build(&[1000, 1, 1, 1, 1, 1, 1, 1]);
+        let most = tree.path(0).len();
+        assert_eq!(most, 1);
+        assert!((1..8).all(|word| tree.path(word).len() > most));
+        assert_eq!(HuffmanTree::build(&[10, 20, 40, 80]).path(3).len(), 1);
+        assert_eq!(HuffmanTree::build(&[10, 20, 40, 80]).path(0).len(), 3);
+    }
+
+    #[test]
+    fn tiny_vocabularies_have_no_paths() {
+        assert!(HuffmanTree::build(&[4]).path(0).is_empty());
+        assert_eq!(HuffmanTree::build(&[]).nodes(), 0);
+    }
+}
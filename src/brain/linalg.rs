@@ -1,11 +1,26 @@
-use serde::{Serialize, Deserialize};
+use std::borrow::Borrow;
+use std::fmt;
+use std::ops::{Deref, DerefMut, Index, IndexMut};
+#[cfg(not(target_arch = "wasm32"))]
+use std::sync::Arc;
+use serde::de::{SeqAccess, Visitor};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
 
-#[derive(Clone, Debug, Serialize, Deserialize)]
+/// A borrowed vector, usually a row of a `Matrix`. Like `str` to `String`, `Vector` is
+/// the view and `VectorBuf` the owned version; `to_owned` copies a row out.
+#[repr(transparent)]
+#[derive(Debug, PartialEq)]
 pub struct Vector {
+    pub data: [f32],
+}
+
+/// An owned vector, for scratch values and rows taken out of a table.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct VectorBuf {
     pub data: Vec<f32>,
 }
 
-impl Vector {
+impl VectorBuf {
     pub fn new(data: Vec<f32>) -> Self {
         Self { data }
     }
@@ -14,6 +29,47 @@ impl Vector {
     pub fn zeros(n: usize) -> Self {
         Self { data: vec![0.0; n] }
     }
+}
+
+impl Deref for VectorBuf {
+    type Target = Vector;
+
+    fn deref(&self) -> &Vector {
+        Vector::from_slice(&self.data)
+    }
+}
+
+impl DerefMut for VectorBuf {
+    fn deref_mut(&mut self) -> &mut Vector {
+        Vector::from_mut_slice(&mut self.data)
+    }
+}
+
+impl Borrow<Vector> for VectorBuf {
+    fn borrow(&self) -> &Vector {
+        self
+    }
+}
+
+impl ToOwned for Vector {
+    type Owned = VectorBuf;
+
+    fn to_owned(&self) -> VectorBuf {
+        VectorBuf::new(self.data.to_vec())
+    }
+}
+
+impl Vector {
+    pub fn from_slice(data: &[f32]) -> &Self {
+        // SAFETY: `Vector` is a `repr(transparent)` wrapper around `[f32]`, so both
+        // pointers have the same layout and metadata.
+        unsafe { &*(data as *const [f32] as *const Self) }
+    }
+
+    pub fn from_mut_slice(data: &mut [f32]) -> &mut Self {
+        // SAFETY: as in `from_slice`.
+        unsafe { &mut *(data as *mut [f32] as *mut Self) }
+    }
 
     pub fn dot(&self, other: &Self) -> f32 {
         let mut sum: f64 = 0.0;
@@ -38,12 +94,12 @@ impl Vector {
     }
 
     #[allow(dead_code)]
-    pub fn sub(&self, other: &Self) -> Self {
+    pub fn sub(&self, other: &Self) -> VectorBuf {
         let mut result = vec![0.0; self.data.len()];
         for i in 0..self.data.len() {
             result[i] = self.data[i] - other.data[i];
         }
-        Self::new(result)
+        VectorBuf::new(result)
     }
 
     pub fn scale(&mut self, factor: f32) {
@@ -58,6 +114,11 @@ impl Vector {
         }
     }
 
+    /// Overwrites this vector with `other`, which must have the same length.
+    pub fn copy_from(&mut self, other: &Self) {
+        self.data.copy_from_slice(&other.data);
+    }
+
     pub fn length(&self) -> f32 {
         let mut sum: f64 = 0.0;
         for i in 0..self.data.len() {
@@ -79,25 +140,230 @@ impl Vector {
     }
 }
 
-#[allow(dead_code)]
+/// Equal-length vectors stored back to back in one row-major buffer. The buffer is
+/// either owned or, on native targets, a region of a memory-mapped model file that is
+/// read in place; the first write to a mapped table copies it into memory.
+#[derive(Clone)]
 pub struct Matrix {
-    pub rows: Vec<Vector>,
+    dim: usize,
+    rows: usize,
+    storage: Storage,
+}
+
+#[derive(Clone)]
+enum Storage {
+    Owned(Vec<f32>),
+    #[cfg(not(target_arch = "wasm32"))]
+    Mapped { map: Arc<memmap2::Mmap>, offset: usize },
 }
 
 impl Matrix {
+    /// An empty table of `dim`-long rows.
+    pub fn new(dim: usize) -> Self {
+        Self { dim, rows: 0, storage: Storage::Owned(Vec::new()) }
+    }
+
     #[allow(dead_code)]
     pub fn zeros(r: usize, c: usize) -> Self {
-        Self {
-            rows: (0..r).map(|_| Vector::zeros(c)).collect(),
+        Self { dim: c, rows: r, storage: Storage::Owned(vec![0.0; r * c]) }
+    }
+
+    /// Rows stored as consecutive little-endian `f32`s, as `write_le_bytes` writes them.
+    /// `bytes` must hold exactly `rows * dim` values.
+    pub fn from_le_bytes(bytes: &[u8], dim: usize) -> Self {
+        let data: Vec<f32> = bytes.chunks_exact(4).map(|b| f32::from_le_bytes([b[0], b[1], b[2], b[3]])).collect();
+        let rows = data.len().checked_div(dim).unwrap_or(0);
+        Self { dim, rows, storage: Storage::Owned(data) }
+    }
+
+    /// Reads `rows` rows in place from `map`, starting `offset` bytes in. Falls back to a
+    /// copy where the bytes can't be used as `f32`s directly: on big-endian targets, or
+    /// if `offset` isn't 4-byte aligned.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn mapped(map: Arc<memmap2::Mmap>, offset: usize, rows: usize, dim: usize) -> Self {
+        let len = rows * dim * 4;
+        assert!(offset + len <= map.len(), "table runs past the end of the mapped file");
+        let aligned = (map.as_ptr() as usize + offset).is_multiple_of(std::mem::align_of::<f32>());
+        if cfg!(target_endian = "big") || !aligned {
+            return Self::from_le_bytes(&map[offset..offset + len], dim);
         }
+        Self { dim, rows, storage: Storage::Mapped { map, offset } }
+    }
+
+    #[allow(dead_code)]
+    pub fn dim(&self) -> usize {
+        self.dim
     }
 
+    pub fn len(&self) -> usize {
+        self.rows
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.rows == 0
+    }
+
+    /// Whether the rows are still read from a mapped file rather than owned memory.
     #[allow(dead_code)]
-    pub fn multiply_vec(&self, v: &Vector) -> Vector {
-        let mut result = vec![0.0; self.rows.len()];
-        for i in 0..self.rows.len() {
-            result[i] = self.rows[i].dot(v);
+    pub fn is_mapped(&self) -> bool {
+        !matches!(self.storage, Storage::Owned(_))
+    }
+
+    pub fn as_slice(&self) -> &[f32] {
+        match &self.storage {
+            Storage::Owned(data) => data,
+            #[cfg(not(target_arch = "wasm32"))]
+            Storage::Mapped { map, offset } => {
+                let bytes = &map[*offset..*offset + self.rows * self.dim * 4];
+                // SAFETY: `mapped` checked that the range is in bounds and aligned for
+                // `f32`, every bit pattern is a valid `f32`, and the map lives as long as
+                // `self` holds the `Arc`.
+                unsafe { std::slice::from_raw_parts(bytes.as_ptr().cast::<f32>(), self.rows * self.dim) }
+            }
         }
-        Vector::new(result)
+    }
+
+    pub fn as_mut_slice(&mut self) -> &mut [f32] {
+        self.owned_vec()
+    }
+
+    pub fn get(&self, idx: usize) -> Option<&Vector> {
+        (idx < self.rows).then(|| &self[idx])
+    }
+
+    pub fn get_mut(&mut self, idx: usize) -> Option<&mut Vector> {
+        (idx < self.rows).then(|| &mut self[idx])
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Vector> + '_ {
+        (0..self.rows).map(|idx| &self[idx])
+    }
+
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = &mut Vector> + '_ {
+        let dim = self.dim.max(1);
+        self.as_mut_slice().chunks_exact_mut(dim).map(Vector::from_mut_slice)
+    }
+
+    /// Appends a copy of `row`, which must be `dim` long.
+    pub fn push(&mut self, row: &Vector) {
+        assert_eq!(row.data.len(), self.dim, "row length does not match the table");
+        self.owned_vec().extend_from_slice(&row.data);
+        self.rows += 1;
+    }
+
+    /// Grows with zero rows or drops rows from the end.
+    pub fn resize(&mut self, rows: usize) {
+        let dim = self.dim;
+        self.owned_vec().resize(rows * dim, 0.0);
+        self.rows = rows;
+    }
+
+    /// A new table holding copies of the listed rows, in that order.
+    pub fn gather(&self, indices: &[usize]) -> Self {
+        let mut data = Vec::with_capacity(indices.len() * self.dim);
+        for &idx in indices {
+            data.extend_from_slice(&self[idx].data);
+        }
+        Self { dim: self.dim, rows: indices.len(), storage: Storage::Owned(data) }
+    }
+
+    /// Appends every value as a little-endian `f32`, row after row.
+    pub fn write_le_bytes(&self, out: &mut Vec<u8>) {
+        out.reserve(self.rows * self.dim * 4);
+        for x in self.as_slice() {
+            out.extend_from_slice(&x.to_le_bytes());
+        }
+    }
+
+    #[allow(dead_code)]
+    pub fn multiply_vec(&self, v: &Vector) -> VectorBuf {
+        let mut result = vec![0.0; self.rows];
+        for i in 0..self.rows {
+            result[i] = self[i].dot(v);
+        }
+        VectorBuf::new(result)
+    }
+
+    /// The owned buffer, copying a mapped table into memory first.
+    fn owned_vec(&mut self) -> &mut Vec<f32> {
+        if self.is_mapped() {
+            self.storage = Storage::Owned(self.as_slice().to_vec());
+        }
+        match &mut self.storage {
+            Storage::Owned(data) => data,
+            #[cfg(not(target_arch = "wasm32"))]
+            Storage::Mapped { .. } => unreachable!("mapped storage was just copied"),
+        }
+    }
+}
+
+impl Index<usize> for Matrix {
+    type Output = Vector;
+
+    fn index(&self, idx: usize) -> &Vector {
+        assert!(idx < self.rows, "row {} out of range for a table of {}", idx, self.rows);
+        Vector::from_slice(&self.as_slice()[idx * self.dim..(idx + 1) * self.dim])
+    }
+}
+
+impl IndexMut<usize> for Matrix {
+    fn index_mut(&mut self, idx: usize) -> &mut Vector {
+        assert!(idx < self.rows, "row {} out of range for a table of {}", idx, self.rows);
+        let dim = self.dim;
+        Vector::from_mut_slice(&mut self.as_mut_slice()[idx * dim..(idx + 1) * dim])
+    }
+}
+
+impl<'a> IntoIterator for &'a Matrix {
+    type Item = &'a Vector;
+    type IntoIter = Box<dyn Iterator<Item = &'a Vector> + 'a>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        Box::new(self.iter())
+    }
+}
+
+impl fmt::Debug for Matrix {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Matrix").field("rows", &self.rows).field("dim", &self.dim).field("mapped", &self.is_mapped()).finish()
+    }
+}
+
+/// Serialized as a sequence of `VectorBuf`s, the layout tables had when each row was
+/// its own allocation, so payloads of format versions 4 and older still read.
+impl Serialize for Matrix {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_seq(self.iter().map(|row| VectorBuf::new(row.data.to_vec())))
+    }
+}
+
+impl<'de> Deserialize<'de> for Matrix {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct RowsVisitor;
+
+        impl<'de> Visitor<'de> for RowsVisitor {
+            type Value = Matrix;
+
+            fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                f.write_str("a sequence of equal-length vectors")
+            }
+
+            fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Matrix, A::Error> {
+                let mut matrix: Option<Matrix> = None;
+                while let Some(row) = seq.next_element::<VectorBuf>()? {
+                    let matrix = matrix.get_or_insert_with(|| Matrix::new(row.data.len()));
+                    if row.data.len() != matrix.dim {
+                        return Err(serde::de::Error::custom(format!(
+                            "row {} has {} values where earlier rows have {}",
+                            matrix.rows, row.data.len(), matrix.dim
+                        )));
+                    }
+                    matrix.push(&row);
+                }
+                Ok(matrix.unwrap_or_else(|| Matrix::new(0)))
+            }
+        }
+
+        deserializer.deserialize_seq(RowsVisitor)
     }
 }
@@ -1,3 +1,14 @@
+pub mod delta;
+pub mod eval;
+pub mod export;
+pub mod format;
+pub mod hnsw;
+pub mod huffman;
 pub mod linalg;
 pub mod model;
 pub mod projection;
+pub mod quantize;
+pub mod retrofit;
+pub mod sampling;
+pub mod subword;
+pub mod tokenizer;
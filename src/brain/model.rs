@@ -1,8 +1,29 @@
-use std::collections::HashMap;
-use crate::brain::linalg::Vector;
+use std::borrow::Cow;
+use std::collections::{HashMap, HashSet};
+use crate::brain::export::ImportedVectors;
+use crate::brain::linalg::{Matrix, Vector, VectorBuf};
+use crate::brain::format::{self, FlatPayload, ModelError, ModelFile, ModelHeader, PayloadV1, PayloadV2, PayloadV3, PayloadV4, TableSpanV5, TrainingMetadata};
+use crate::brain::hnsw::{HnswIndex, HnswParams, RecallReport};
+use crate::brain::huffman::HuffmanTree;
+use crate::brain::delta::{BrainDelta, DeltaRows, Rows, TableRows};
+use crate::brain::sampling::{AliasTable, Rng};
+use crate::brain::subword::{SubwordConfig, SubwordTable};
+use crate::brain::tokenizer::{Tokenizer, UnicodeTokenizer, PHRASE_SEPARATOR};
+use crate::brain::quantize::{FrontCodedStrings, QuantizedBrain, QuantizedTable, Quantization};
 use serde::{Deserialize, Serialize};
 
-pub const EMBEDDING_DIM: usize = 128;
+pub const DEFAULT_EMBEDDING_DIM: usize = 128;
+/// Words left out of dictionary definition contexts unless a stop word list is configured.
+pub const DEFAULT_STOP_WORDS: &[&str] = &[
+    "THE", "AND", "FOR", "ANY", "NOT", "BUT", "HAD", "WAS", "ARE", "WITH",
+    "THAT", "THIS", "FROM", "THEIR", "WHICH", "ALSO", "BEEN", "HAVE", "WERE",
+    "THEY", "YOU", "YOUR", "THEM", "THESE", "THOSE", "WHEN", "WHERE", "WHO",
+    "HOW", "WHY", "CAN", "WILL", "SOME", "MORE", "MOST", "OTHER", "INTO",
+];
+/// Negative sampling draws from `count^0.75`, as in word2vec.
+const UNIGRAM_POWER: f64 = 0.75;
+/// Training steps between rebuilds of the negative sampling table.
+const SAMPLER_REBUILD_STEPS: usize = 100_000;
 
 #[allow(dead_code)]
 #[derive(Deserialize, Serialize, Debug)]
@@ -15,161 +36,967 @@ pub struct JsonWordData {
     pub synonyms: Vec<String>,
 }
 
-#[derive(Serialize, Deserialize)]
+/// Where a vector returned by `get_embedding` came from.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum VectorSource {
+    /// The word's own row in the vocabulary.
+    Vocabulary,
+    /// Built from character n-grams for a word the model has never seen.
+    Subwords,
+}
+
+pub struct Embedding<'a> {
+    pub vector: Cow<'a, Vector>,
+    pub source: VectorSource,
+}
+
+/// Training objective for running text. Both train the same `embeddings` (input) and
+/// `context_embeddings` (output) tables, so a model can switch between them.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Objective {
+    /// Each word predicts its neighbours.
+    #[default]
+    SkipGram,
+    /// The neighbours, combined into one vector, predict the word. One update per
+    /// position instead of one per pair, so it runs several times faster.
+    Cbow(CbowContext),
+}
+
+/// How CBOW combines the context vectors.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CbowContext {
+    #[default]
+    Mean,
+    Sum,
+}
+
+/// Output layer the objective is trained against.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Loss {
+    /// The predicted word's context row against sampled negatives.
+    #[default]
+    NegativeSampling,
+    /// A walk down the Huffman tree over the vocabulary, through `nodes`. Every word
+    /// is trained on every step instead of only when sampled, which helps rare words.
+    HierarchicalSoftmax,
+}
+
+/// What `prune` removed.
+#[derive(Clone, Debug, Serialize)]
+pub struct PruneStats {
+    pub words_before: usize,
+    pub words_after: usize,
+    /// Lowest corpus count among the words kept.
+    pub min_count_kept: u64,
+}
+
+/// Settings for `train_window_delta`.
+#[derive(Clone, Copy, Debug)]
+pub struct WindowParams {
+    pub objective: Objective,
+    pub loss: Loss,
+    pub window_size: usize,
+    pub learning_rate: f32,
+    pub negative_samples: usize,
+}
+
+/// Everything a training update needs from the brain besides the rows it writes.
+struct StepContext<'a> {
+    learning_rate: f32,
+    negative_samples: usize,
+    sampler: Option<&'a AliasTable>,
+    vocab_len: usize,
+    /// Set when training with hierarchical softmax instead of negative sampling.
+    huffman: Option<&'a HuffmanTree>,
+}
+
+/// Row a prediction is scored against.
+#[derive(Clone, Copy)]
+enum Output {
+    Context(usize),
+    Node(usize),
+}
+
+#[derive(Clone)]
 pub struct SemanticBrain {
+    /// Length of every vector in `embeddings` and `context_embeddings`. Stored in the
+    /// model header rather than the payload.
+    pub dim: usize,
     pub vocabulary: HashMap<String, usize>,
-    pub embeddings: Vec<Vector>,
-    pub context_embeddings: Vec<Vector>,
+    /// Vocabulary keys by index, in the order the words were added; the inverse of
+    /// `vocabulary`. Rebuilt from it on load rather than stored.
+    words: Vec<String>,
+    pub embeddings: Matrix,
+    pub context_embeddings: Matrix,
+    /// Corpus occurrences of each word, indexed like `embeddings`.
+    pub counts: Vec<u64>,
+    /// Character n-gram vectors for out-of-vocabulary lookups; `None` unless enabled.
+    pub subwords: Option<SubwordTable>,
+    /// Tree for hierarchical softmax, rebuilt from `counts` along with the negative
+    /// sampling table once the vocabulary has grown; words added in between are trained
+    /// with negative sampling. `None` until first used.
+    pub huffman: Option<HuffmanTree>,
+    /// Internal node vectors of `huffman`.
+    pub nodes: Matrix,
+    total_count: u64,
+    pub ann_index: Option<HnswIndex>,
+    /// Training passes that have moved target rows, and the count `ann_index` was built
+    /// at. The graph is stale once they differ.
+    updates: u64,
+    index_updates: u64,
+    /// Rows whose target vectors training leaves alone, set with `freeze`.
+    frozen: HashSet<usize>,
+    pub metadata: TrainingMetadata,
+    sampler: Option<AliasTable>,
+    steps_since_sampler: usize,
+    rng: Rng,
 }
 
 impl SemanticBrain {
     pub fn new() -> Self {
+        Self::with_dim(DEFAULT_EMBEDDING_DIM)
+    }
+
+    pub fn with_dim(dim: usize) -> Self {
         Self {
+            dim,
             vocabulary: HashMap::new(),
-            embeddings: Vec::new(),
-            context_embeddings: Vec::new(),
+            words: Vec::new(),
+            embeddings: Matrix::new(dim),
+            context_embeddings: Matrix::new(dim),
+            counts: Vec::new(),
+            subwords: None,
+            huffman: None,
+            nodes: Matrix::new(dim),
+            total_count: 0,
+            ann_index: None,
+            updates: 0,
+            index_updates: 0,
+            frozen: HashSet::new(),
+            metadata: TrainingMetadata::default(),
+            sampler: None,
+            steps_since_sampler: 0,
+            rng: Rng::default(),
+        }
+    }
+
+    /// Loads a model written by `to_bytes`, or a headerless file from before the format
+    /// was versioned, migrating older payload layouts to the current one. The vector
+    /// tables are copied out of `bytes`, one allocation each, and checked against their
+    /// checksums.
+    #[allow(dead_code)]
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, ModelError> {
+        Self::decode(bytes, true, |start, rows, dim| Matrix::from_le_bytes(&bytes[start..start + rows * dim * 4], dim))
+    }
+
+    /// Memory-maps a model file. Tables of the current format are read in place rather
+    /// than copied, until training first writes to them; older formats are migrated as
+    /// in `from_bytes`. Only the metadata and vocabulary are checksummed, since checking
+    /// the tables would read all of them up front. The mapping stays valid when a
+    /// checkpoint replaces the file through `save`, but not if the file is rewritten in
+    /// place by something else.
+    #[cfg(not(target_arch = "wasm32"))]
+    #[allow(dead_code)]
+    pub fn load(path: impl AsRef<std::path::Path>) -> Result<Self, ModelError> {
+        let path = path.as_ref();
+        let io_error = |e: std::io::Error| ModelError::Io(format!("{}: {}", path.display(), e));
+        let file = std::fs::File::open(path).map_err(io_error)?;
+        // SAFETY: the map is only read, and model files are replaced by rename rather
+        // than modified, as described above.
+        let map = std::sync::Arc::new(unsafe { memmap2::Mmap::map(&file) }.map_err(io_error)?);
+        Self::decode(&map, false, |start, rows, dim| Matrix::mapped(map.clone(), start, rows, dim))
+    }
+
+    /// Parses a model, building each vector table of a flat payload with
+    /// `table(offset in bytes, rows, dim)`, after checking its checksum if
+    /// `verify_tables` is set.
+    fn decode(bytes: &[u8], verify_tables: bool, table: impl Fn(usize, usize, usize) -> Matrix) -> Result<Self, ModelError> {
+        let (brain, header) = match format::read_model(bytes)? {
+            Some(file) if file.version >= 5 => (Self::read_flat(&file, verify_tables, table)?, Some(file.header)),
+            Some(file) => (Self::migrate(file.version, file.payload)?, Some(file.header)),
+            None => (Self::migrate(0, bytes)?, None),
+        };
+
+        let mut brain = brain;
+        brain.dim = match &header {
+            Some(header) => header.embedding_dim as usize,
+            None if !brain.embeddings.is_empty() => brain.embeddings.dim(),
+            None => DEFAULT_EMBEDDING_DIM,
+        };
+        let dim = brain.dim;
+        let subword_table = brain.subwords.as_mut().map(|table| &mut table.vectors);
+        for table in [&mut brain.embeddings, &mut brain.context_embeddings, &mut brain.nodes].into_iter().chain(subword_table) {
+            if table.is_empty() {
+                *table = Matrix::new(dim);
+            } else if table.dim() != dim {
+                return Err(ModelError::DimensionMismatch { expected: dim, found: table.dim() });
+            }
+        }
+        let n = brain.vocabulary.len();
+        if brain.embeddings.len() != n || brain.context_embeddings.len() != n || brain.counts.len() != n {
+            return Err(ModelError::Corrupt(format!(
+                "{} words, {} target vectors, {} context vectors, {} counts",
+                n, brain.embeddings.len(), brain.context_embeddings.len(), brain.counts.len()
+            )));
+        }
+
+        brain.words = vec![String::new(); n];
+        for (word, &idx) in &brain.vocabulary {
+            match brain.words.get_mut(idx) {
+                Some(slot) if slot.is_empty() => *slot = word.clone(),
+                Some(slot) => return Err(ModelError::Corrupt(format!("{} and {} share index {}", slot, word, idx))),
+                None => return Err(ModelError::Corrupt(format!("{} has index {} in a vocabulary of {}", word, idx, n))),
+            }
+        }
+
+        let tree_nodes = brain.huffman.as_ref().map_or(0, HuffmanTree::nodes);
+        if brain.nodes.len() != tree_nodes || brain.huffman.as_ref().is_some_and(|tree| tree.words() > n) {
+            return Err(ModelError::Corrupt(format!("{} node vectors for a Huffman tree with {} internal nodes", brain.nodes.len(), tree_nodes)));
+        }
+
+        brain.total_count = brain.counts.iter().sum();
+
+        if let Some(header) = header {
+            if header.vocab_size as usize != brain.vocabulary.len() {
+                return Err(ModelError::VocabMismatch { header: header.vocab_size as usize, found: brain.vocabulary.len() });
+            }
+            brain.metadata = header.metadata()?;
+        }
+        Ok(brain)
+    }
+
+    fn read_flat(file: &ModelFile, verify_tables: bool, table: impl Fn(usize, usize, usize) -> Matrix) -> Result<Self, ModelError> {
+        let corrupt = |e: bincode::Error| ModelError::Corrupt(format!("v{} payload: {}", file.version, e));
+        let body: FlatPayload = if file.version == 5 {
+            bincode::deserialize::<FlatPayload<TableSpanV5>>(file.payload).map_err(corrupt)?.upgrade()
+        } else {
+            let body: FlatPayload = bincode::deserialize(file.payload).map_err(corrupt)?;
+            let body_end = body.tables.first().map_or(file.payload.len(), |span| span.offset as usize);
+            file.header.verify_body(file.payload.get(..body_end).ok_or(ModelError::Truncated)?)?;
+            body
+        };
+        let verify_tables = verify_tables && file.version >= 6;
+        let dim = file.header.embedding_dim as usize;
+        let mut spans = body.tables.iter();
+        let mut next_table = || {
+            let span = spans.next().ok_or_else(|| ModelError::Corrupt("payload lists too few vector tables".to_string()))?;
+            let range = span
+                .range(dim, file.payload.len())
+                .ok_or_else(|| ModelError::Corrupt(format!("table of {} rows at byte {} doesn't fit in the payload", span.rows, span.offset)))?;
+            if verify_tables {
+                let computed = format::crc32(&file.payload[range.clone()]);
+                if computed != span.checksum {
+                    return Err(ModelError::ChecksumMismatch { header: span.checksum, computed });
+                }
+            }
+            Ok::<_, ModelError>(table(file.payload_start + range.start, span.rows as usize, dim))
+        };
+
+        let mut brain = Self::with_dim(dim);
+        brain.embeddings = next_table()?;
+        brain.context_embeddings = next_table()?;
+        if let Some(config) = body.subwords {
+            brain.subwords = Some(SubwordTable { config, vectors: next_table()? });
+        }
+        brain.nodes = next_table()?;
+        if spans.next().is_some() {
+            return Err(ModelError::Corrupt("payload lists more vector tables than it uses".to_string()));
+        }
+        brain.vocabulary = body.vocabulary.into_owned();
+        brain.counts = body.counts.into_owned();
+        brain.huffman = body.huffman.map(Cow::into_owned);
+        Ok(brain)
+    }
+
+    fn migrate(version: u32, payload: &[u8]) -> Result<Self, ModelError> {
+        match version {
+            0 | 1 => {
+                let v1: PayloadV1 = bincode::deserialize(payload)
+                    .map_err(|e| ModelError::Corrupt(format!("v{} payload: {}", version, e)))?;
+                let mut brain = Self::new();
+                // Counts weren't tracked before v2; start everyone equal so the sampler
+                // is uniform until real counts accumulate.
+                brain.counts = vec![1; v1.vocabulary.len()];
+                brain.vocabulary = v1.vocabulary;
+                brain.embeddings = v1.embeddings;
+                brain.context_embeddings = v1.context_embeddings;
+                Ok(brain)
+            }
+            2 => {
+                let v2: PayloadV2 = bincode::deserialize(payload)
+                    .map_err(|e| ModelError::Corrupt(format!("v2 payload: {}", e)))?;
+                let mut brain = Self::new();
+                brain.vocabulary = v2.vocabulary;
+                brain.embeddings = v2.embeddings;
+                brain.context_embeddings = v2.context_embeddings;
+                brain.counts = v2.counts;
+                Ok(brain)
+            }
+            3 => {
+                let v3: PayloadV3 = bincode::deserialize(payload)
+                    .map_err(|e| ModelError::Corrupt(format!("v3 payload: {}", e)))?;
+                let mut brain = Self::new();
+                brain.vocabulary = v3.vocabulary;
+                brain.embeddings = v3.embeddings;
+                brain.context_embeddings = v3.context_embeddings;
+                brain.counts = v3.counts;
+                brain.subwords = v3.subwords;
+                Ok(brain)
+            }
+            4 => {
+                let v4: PayloadV4 = bincode::deserialize(payload)
+                    .map_err(|e| ModelError::Corrupt(format!("v4 payload: {}", e)))?;
+                let mut brain = Self::new();
+                brain.vocabulary = v4.vocabulary;
+                brain.embeddings = v4.embeddings;
+                brain.context_embeddings = v4.context_embeddings;
+                brain.counts = v4.counts;
+                brain.subwords = v4.subwords;
+                brain.huffman = v4.huffman;
+                brain.nodes = v4.nodes;
+                Ok(brain)
+            }
+            other => Err(ModelError::UnsupportedVersion { found: other, supported: format::FORMAT_VERSION }),
+        }
+    }
+
+    #[allow(dead_code)]
+    pub fn to_bytes(&self) -> Result<Vec<u8>, ModelError> {
+        let body = FlatPayload {
+            vocabulary: Cow::Borrowed(&self.vocabulary),
+            counts: Cow::Borrowed(&self.counts),
+            subwords: self.subwords.as_ref().map(|table| table.config.clone()),
+            huffman: self.huffman.as_ref().map(Cow::Borrowed),
+            tables: Vec::new(),
+        };
+        let mut tables = vec![&self.embeddings, &self.context_embeddings];
+        tables.extend(self.subwords.as_ref().map(|table| &table.vectors));
+        tables.push(&self.nodes);
+        let (payload, body_end) = format::write_flat_payload(body, &tables)?;
+        let header = ModelHeader::new(self.dim, self.vocabulary.len(), &payload[..body_end], &self.metadata)?;
+        format::write_model(&header, &payload)
+    }
+
+    /// Writes `to_bytes` to `path` by replacing the file, so brains mapped from the old
+    /// file by `load` are unaffected.
+    #[allow(dead_code)]
+    pub fn save(&self, path: impl AsRef<std::path::Path>) -> Result<(), ModelError> {
+        format::replace_file(path.as_ref(), &self.to_bytes()?)
+    }
+
+    /// Compressed export for shipping: words go into a sorted front-coded table and the
+    /// embedding rows are re-ordered to match it before quantization.
+    #[allow(dead_code)]
+    pub fn to_quantized_bytes(&self, scheme: Quantization, include_context: bool) -> Result<Vec<u8>, String> {
+        let mut entries: Vec<(&str, usize)> = self.words.iter().enumerate().map(|(idx, w)| (w.as_str(), idx)).collect();
+        entries.sort_unstable_by(|a, b| a.0.cmp(b.0));
+
+        let words: Vec<&str> = entries.iter().map(|(w, _)| *w).collect();
+        let order: Vec<usize> = entries.iter().map(|&(_, idx)| idx).collect();
+        let rows = self.embeddings.gather(&order);
+        let context_rows = include_context.then(|| self.context_embeddings.gather(&order));
+
+        let quantized = QuantizedBrain {
+            words: FrontCodedStrings::encode(&words),
+            embeddings: QuantizedTable::encode(&rows, self.dim, scheme)?,
+            context_embeddings: match context_rows {
+                Some(rows) => Some(QuantizedTable::encode(&rows, self.dim, scheme)?),
+                None => None,
+            },
+            metadata_json: serde_json::to_string(&self.metadata).map_err(|e| format!("Quantized Model Save Error: {}", e))?,
+        };
+        quantized.to_bytes()
+    }
+
+    /// Decodes a quantized model back into a trainable brain. Context vectors that were
+    /// left out of the export are re-seeded the same way `ensure_word` seeds new words,
+    /// and counts restart at one.
+    #[allow(dead_code)]
+    pub fn from_quantized_bytes(bytes: &[u8]) -> Result<Self, String> {
+        Self::from_quantized(&QuantizedBrain::from_bytes(bytes)?)
+    }
+
+    /// As `from_quantized_bytes`, for a caller that keeps the quantized model as well.
+    #[allow(dead_code)]
+    pub fn from_quantized(quantized: &QuantizedBrain) -> Result<Self, String> {
+        let words = quantized.words.to_vec().map_err(|e| format!("Quantized Model Load Error: {}", e))?;
+        if words.len() != quantized.embeddings.len() {
+            return Err(format!("Quantized Model Load Error: {} words but {} vectors", words.len(), quantized.embeddings.len()));
+        }
+
+        let dim = quantized.embeddings.dim();
+        let mut brain = Self::with_dim(dim);
+        brain.metadata = quantized.metadata()?;
+        for (row, word) in words.iter().enumerate() {
+            brain.vocabulary.insert(word.clone(), row);
+            brain.words.push(word.clone());
+            brain.counts.push(1);
+            brain.total_count += 1;
+            brain.embeddings.push(&quantized.embeddings.decode_row(row));
+            brain.context_embeddings.push(&match &quantized.context_embeddings {
+                Some(table) => table.decode_row(row),
+                None => Self::generate_initial_vector_static(word, true, dim),
+            });
         }
+        Ok(brain)
     }
 
+    /// A brain holding vectors trained elsewhere. Words are keyed through the default
+    /// tokenizer, so `the` and `The` both become `THE` and the first one read wins.
+    /// Vectors are scaled to unit length like the ones `ensure_word` seeds, rows with
+    /// NaN or infinity are dropped, context vectors are seeded and counts start at one.
     #[allow(dead_code)]
-    pub fn from_bytes(bytes: &[u8]) -> Result<Self, String> {
-        bincode::deserialize(bytes).map_err(|e| format!("Model Load Error: {}", e))
+    pub fn from_vectors(imported: ImportedVectors) -> Self {
+        let mut brain = Self::with_dim(imported.dim);
+        brain.seed_vectors(imported).unwrap_or_default();
+        brain.counts.fill(1);
+        brain.total_count = brain.counts.len() as u64;
+        brain
     }
 
+    /// Adds words from a vector file with their pretrained target vectors, keyed
+    /// through this brain's tokenizer. Words already in the vocabulary keep their
+    /// vectors, and words added later by training are seeded the usual way. Imported
+    /// words start with a count of zero until the corpus is seen. Returns the number of
+    /// words added.
     #[allow(dead_code)]
-    pub fn to_bytes(&self) -> Result<Vec<u8>, String> {
-        bincode::serialize(self).map_err(|e| format!("Model Save Error: {}", e))
+    pub fn seed_vectors(&mut self, imported: ImportedVectors) -> Result<usize, String> {
+        if imported.dim != self.dim {
+            return Err(format!("Vectors are {}D but the brain is {}D", imported.dim, self.dim));
+        }
+        let mut added = 0;
+        for (word, mut vector) in imported.rows {
+            let key = self.key(&word);
+            if key.is_empty() || self.vocabulary.contains_key(&key) || !vector.is_finite() {
+                continue;
+            }
+            vector.normalize();
+            let idx = self.ensure_word(&key);
+            self.embeddings[idx].copy_from(&vector);
+            added += 1;
+        }
+        if let Some(params) = self.ann_index.as_ref().map(|index| index.params.clone()) {
+            self.build_index(params);
+        }
+        Ok(added)
     }
 
+    /// Keeps the target vectors of `words` fixed during training, replacing any earlier
+    /// list; their context vectors still train, so other words can move towards them.
+    /// Words not in the vocabulary are ignored. Returns the number frozen.
     #[allow(dead_code)]
-    pub fn get_embedding(&self, word: &str) -> Option<&Vector> {
-        self.vocabulary.get(&word.to_uppercase()).map(|&idx| &self.embeddings[idx])
+    pub fn freeze(&mut self, words: &[String]) -> usize {
+        self.frozen = words.iter().filter_map(|word| self.vocabulary.get(&self.key(word)).copied()).collect();
+        self.frozen.len()
     }
 
+    /// The word at a row of `embeddings`.
     #[allow(dead_code)]
-    pub fn extract_context(&self, json_str: &str) -> Result<Vec<(String, Vec<String>, Vec<String>)>, String> {
+    pub fn word(&self, idx: usize) -> Option<&str> {
+        self.words.get(idx).map(String::as_str)
+    }
+
+    /// Every vocabulary word, indexed like `embeddings`, which is the order they were
+    /// first seen in.
+    #[allow(dead_code)]
+    pub fn words(&self) -> &[String] {
+        &self.words
+    }
+
+    /// Row indices from the most to the least frequent word, with ties in insertion
+    /// order.
+    #[allow(dead_code)]
+    pub fn indices_by_count(&self) -> Vec<usize> {
+        let mut order: Vec<usize> = (0..self.words.len()).collect();
+        order.sort_by_key(|&idx| std::cmp::Reverse(self.counts[idx]));
+        order
+    }
+
+    /// Tokenizer this brain's vocabulary was built with.
+    pub fn tokenizer(&self) -> UnicodeTokenizer {
+        UnicodeTokenizer::new(self.metadata.tokenizer.clone())
+    }
+
+    /// Vocabulary key for a word typed by a user or read from a dictionary. Multi-word
+    /// input is joined the way phrase tokens are, so "New York" finds `NEW_YORK`.
+    pub fn key(&self, word: &str) -> String {
+        let normalized = self.tokenizer().normalize(word);
+        normalized.split_whitespace().collect::<Vec<_>>().join(&PHRASE_SEPARATOR.to_string())
+    }
+
+    #[allow(dead_code)]
+    pub fn contains_word(&self, word: &str) -> bool {
+        self.vocabulary.contains_key(&self.key(word))
+    }
+
+    /// Adds an n-gram table so words outside the vocabulary can still be looked up. Only
+    /// text trained after this call reaches the n-grams.
+    #[allow(dead_code)]
+    pub fn enable_subwords(&mut self, config: SubwordConfig) {
+        if self.subwords.is_none() {
+            self.subwords = Some(SubwordTable::new(config, self.dim));
+        }
+    }
+
+    /// The word's vector, or one composed from its n-grams when the word is unknown and
+    /// subwords are enabled.
+    #[allow(dead_code)]
+    pub fn get_embedding(&self, word: &str) -> Option<Embedding<'_>> {
+        let key = self.key(word);
+        if let Some(&idx) = self.vocabulary.get(&key) {
+            return Some(Embedding { vector: Cow::Borrowed(&self.embeddings[idx]), source: VectorSource::Vocabulary });
+        }
+        let vector = self.subwords.as_ref()?.compose(&key)?;
+        Some(Embedding { vector: Cow::Owned(vector), source: VectorSource::Subwords })
+    }
+
+    #[allow(dead_code)]
+    pub fn vector_source(&self, word: &str) -> Option<VectorSource> {
+        self.get_embedding(word).map(|e| e.source)
+    }
+
+    /// N-gram buckets of each token, for `train_window_delta`. Empty when subwords are off.
+    #[allow(dead_code)]
+    pub fn subword_buckets(&self, tokens: &[String]) -> Vec<Vec<usize>> {
+        match &self.subwords {
+            Some(table) => tokens.iter().map(|t| table.buckets(t)).collect(),
+            None => Vec::new(),
+        }
+    }
+
+    #[allow(dead_code)]
+    pub fn extract_context(&self, json_str: &str, stop_words: &[String]) -> Result<Vec<(String, Vec<String>, Vec<String>)>, String> {
         let data: HashMap<String, JsonWordData> = serde_json::from_str(json_str)
             .map_err(|e| format!("JSON Parse Error: {}", e))?;
 
-        let stop_words = [
-            "THE", "AND", "FOR", "ANY", "NOT", "BUT", "HAD", "WAS", "ARE", "WITH", 
-            "THAT", "THIS", "FROM", "THEIR", "WHICH", "ALSO", "BEEN", "HAVE", "WERE",
-            "THEY", "YOU", "YOUR", "THEM", "THESE", "THOSE", "WHEN", "WHERE", "WHO",
-            "HOW", "WHY", "CAN", "WILL", "SOME", "MORE", "MOST", "OTHER", "INTO"
-        ];
+        let tokenizer = self.tokenizer();
+        let stop_words: Vec<String> = stop_words.iter().map(|w| tokenizer.normalize(w)).collect();
 
         let mut results = Vec::new();
         for (word, info) in data {
-            let mut pos_context = info.synonyms.clone();
-            let neg_context = info.antonyms.clone();
+            let word = tokenizer.normalize(&word);
+            let mut pos_context: Vec<String> = info.synonyms.iter().map(|w| tokenizer.normalize(w)).collect();
+            let neg_context: Vec<String> = info.antonyms.iter().map(|w| tokenizer.normalize(w)).collect();
 
             for meaning in info.meanings.values() {
                 if let Some(arr) = meaning.as_array() {
                     if let Some(def) = arr.get(1).and_then(|v| v.as_str()) {
-                        for part in def.split_whitespace() {
-                            let clean = part.trim_matches(|c: char| !c.is_alphabetic()).to_uppercase();
-                            if clean.len() > 3 && !stop_words.contains(&clean.as_str()) && clean != word.to_uppercase() {
+                        for clean in tokenizer.tokenize(def) {
+                            if clean.chars().count() > 3 && !stop_words.contains(&clean) && clean != word {
                                 pos_context.push(clean);
                             }
                         }
                     }
                 }
             }
-            results.push((word.to_uppercase(), pos_context, neg_context));
+            results.push((word, pos_context, neg_context));
         }
         Ok(results)
     }
 
+    /// Single-threaded update straight into the tables; the wiki pipeline batches through
+    /// `train_window_delta` instead.
+    #[allow(dead_code)]
     pub fn train_step(&mut self, word: &str, pos_context: &[String], neg_context: &[String], learning_rate: f32, negative_samples: usize) {
-        let word_upper = word.to_uppercase();
-        
-        if self.should_skip(&word_upper) {
-            return;
+        let word_key = self.key(word);
+        let word_idx = self.index_word(&word_key);
+        let ngrams = self.subwords.as_ref().map(|table| table.buckets(&word_key)).unwrap_or_default();
+        let pos_idx: Vec<usize> = pos_context.iter().map(|c| self.index_word(&self.key(c))).collect();
+        let neg_idx: Vec<usize> = neg_context.iter().map(|c| self.ensure_word(&self.key(c))).collect();
+
+        if negative_samples > 0 {
+            self.refresh_sampler(1);
         }
 
-        let word_idx = self.ensure_word(&word_upper);
-        
-        if !self.embeddings[word_idx].is_finite() {
-            let word = word_upper.clone();
-            self.embeddings[word_idx] = Self::generate_initial_vector_static(&word, false);
+        let step = StepContext {
+            learning_rate,
+            negative_samples,
+            sampler: self.sampler.as_ref(),
+            vocab_len: self.embeddings.len(),
+            huffman: None,
+        };
+        let mut no_subwords = Matrix::new(self.dim);
+        let subwords = match &mut self.subwords {
+            Some(table) => &mut table.vectors,
+            None => &mut no_subwords,
+        };
+        let frozen = self.frozen.contains(&word_idx).then(|| self.embeddings[word_idx].to_owned());
+        let mut rows = TableRows {
+            targets: &mut self.embeddings,
+            contexts: &mut self.context_embeddings,
+            subwords,
+            nodes: &mut self.nodes,
+        };
+        Self::train_ids(&mut rows, &step, &mut self.rng, word_idx, &ngrams, &pos_idx, &neg_idx);
+        if let Some(vector) = frozen {
+            self.embeddings[word_idx].copy_from(&vector);
         }
-        
-        let sigmoid = |x: f32| 1.0 / (1.0 + (-x).exp());
+        self.updates += 1;
+    }
 
-        for context in pos_context {
-            let ctx_upper = context.to_uppercase();
-            if self.should_skip(&ctx_upper) { continue; }
-            
-            let context_idx = self.ensure_word(&ctx_upper);
-            if word_idx == context_idx { continue; }
+    /// Trains a window over already indexed tokens without mutating the brain, so several
+    /// workers can run it at once under a read lock. The returned delta is merged with
+    /// `apply_delta`; rows updated by more than one worker simply add up (Hogwild).
+    /// `ngrams` comes from `subword_buckets` and may be empty.
+    #[allow(dead_code)]
+    pub fn train_window_delta(&self, ids: &[usize], ngrams: &[Vec<usize>], params: &WindowParams, rng: &mut Rng) -> BrainDelta {
+        let step = StepContext {
+            learning_rate: params.learning_rate,
+            negative_samples: params.negative_samples,
+            sampler: self.sampler.as_ref(),
+            vocab_len: self.embeddings.len(),
+            huffman: match params.loss {
+                Loss::HierarchicalSoftmax => self.huffman.as_ref(),
+                Loss::NegativeSampling => None,
+            },
+        };
+        let window_size = params.window_size;
+        let no_subwords = Matrix::new(self.dim);
+        let subwords = self.subwords.as_ref().map_or(&no_subwords, |table| &table.vectors);
+        let mut rows = DeltaRows::new(&self.embeddings, &self.context_embeddings, subwords, &self.nodes);
+        for i in 0..ids.len() {
+            let start = i.saturating_sub(window_size);
+            let end = (i + window_size + 1).min(ids.len());
+            let context: Vec<usize> = ids[start..i].iter().chain(ids[i + 1..end].iter()).copied().collect();
+            match params.objective {
+                Objective::SkipGram => {
+                    let word_ngrams = ngrams.get(i).map_or(&[][..], Vec::as_slice);
+                    Self::train_ids(&mut rows, &step, rng, ids[i], word_ngrams, &context, &[]);
+                }
+                Objective::Cbow(combine) => {
+                    let context_ngrams: Vec<&[usize]> = (start..end)
+                        .filter(|&j| j != i)
+                        .filter_map(|j| ngrams.get(j).map(Vec::as_slice))
+                        .collect();
+                    Self::train_cbow(&mut rows, &step, rng, ids[i], &context, &context_ngrams, combine);
+                }
+            }
+        }
+        rows.into_delta()
+    }
 
-            if !self.context_embeddings[context_idx].is_finite() {
-                self.context_embeddings[context_idx] = Self::generate_initial_vector_static(&ctx_upper, true);
+    #[allow(dead_code)]
+    pub fn apply_delta(&mut self, delta: BrainDelta) {
+        if !delta.targets.is_empty() {
+            self.updates += 1;
+        }
+        for (idx, diff) in delta.targets {
+            if !self.frozen.contains(&idx) {
+                self.embeddings[idx].add(&diff);
+            }
+        }
+        for (idx, diff) in delta.contexts {
+            self.context_embeddings[idx].add(&diff);
+        }
+        if let Some(table) = &mut self.subwords {
+            for (bucket, diff) in delta.subwords {
+                table.vectors[bucket].add(&diff);
             }
+        }
+        // A delta trained before the tree was rebuilt may name nodes that have since
+        // moved; the update still lands on a live row, like any other Hogwild overlap.
+        for (idx, diff) in delta.nodes {
+            if let Some(node) = self.nodes.get_mut(idx) {
+                node.add(&diff);
+            }
+        }
+    }
 
-            let w = self.embeddings[word_idx].clone();
-            let c = self.context_embeddings[context_idx].clone();
-            
-            let dot = w.dot(&c);
-            let p = sigmoid(dot);
-            let error = 1.0 - p;
+    /// Builds the Huffman tree and its node vectors so `train_window_delta` can use
+    /// `Loss::HierarchicalSoftmax`. Node vectors start at zero, as in word2vec.
+    #[allow(dead_code)]
+    pub fn enable_hierarchical_softmax(&mut self) {
+        if self.huffman.is_none() {
+            self.rebuild_huffman();
+        }
+    }
+
+    /// Internal nodes are numbered in merge order, so once the layout changes a node row
+    /// would steer an unrelated branch. The rows are then reset to zero, as word2vec starts
+    /// them, and only kept when the rebuilt tree is identical.
+    fn rebuild_huffman(&mut self) {
+        let tree = HuffmanTree::build(&self.counts);
+        if self.huffman.as_ref() != Some(&tree) {
+            self.nodes = Matrix::zeros(tree.nodes(), self.dim);
+        }
+        self.huffman = Some(tree);
+    }
+
+    /// Looks up or adds each token and heals non-finite rows so they can be trained
+    /// through `train_window_delta`. Also advances the negative sampling table. Tokens
+    /// must already be normalized by `tokenizer()`.
+    #[allow(dead_code)]
+    pub fn index_tokens(&mut self, tokens: &[String]) -> Vec<usize> {
+        let ids = tokens.iter().map(|t| self.index_word(t)).collect();
+        self.refresh_sampler(tokens.len());
+        ids
+    }
 
-            self.embeddings[word_idx].add_scaled(&c, learning_rate * error);
-            self.context_embeddings[context_idx].add_scaled(&w, learning_rate * error);
+    fn index_word(&mut self, word: &str) -> usize {
+        let idx = self.ensure_word(word);
+        if !self.embeddings[idx].is_finite() {
+            self.embeddings[idx].copy_from(&Self::generate_initial_vector_static(word, false, self.dim));
+        }
+        if !self.context_embeddings[idx].is_finite() {
+            self.context_embeddings[idx].copy_from(&Self::generate_initial_vector_static(word, true, self.dim));
         }
+        idx
+    }
 
-        for context in neg_context {
-            let ctx_upper = context.to_uppercase();
-            let context_idx = self.ensure_word(&ctx_upper);
+    fn train_ids(rows: &mut impl Rows, step: &StepContext, rng: &mut Rng, word_idx: usize, ngrams: &[usize], pos_idx: &[usize], neg_idx: &[usize]) {
+        for &context_idx in pos_idx {
             if word_idx == context_idx { continue; }
+            for (output, label) in Self::predict(step, context_idx) {
+                Self::subword_pair(rows, ngrams, output, label, step.learning_rate);
+                Self::logistic_pair(rows, word_idx, output, label, step.learning_rate);
+            }
+        }
 
-            let w = self.embeddings[word_idx].clone();
-            let n = self.context_embeddings[context_idx].clone();
-            
-            let dot = w.dot(&n);
-            let p = sigmoid(dot);
-            let error = 0.0 - p;
+        for &context_idx in neg_idx {
+            if word_idx == context_idx { continue; }
+            Self::subword_pair(rows, ngrams, Output::Context(context_idx), 0.0, step.learning_rate * 2.5);
+            Self::logistic_pair(rows, word_idx, Output::Context(context_idx), 0.0, step.learning_rate * 2.5);
+        }
 
-            self.embeddings[word_idx].add_scaled(&n, learning_rate * error * 2.5);
-            self.context_embeddings[context_idx].add_scaled(&w, learning_rate * error * 2.5);
+        for rand_idx in Self::sample_negatives(step, rng, word_idx, pos_idx) {
+            Self::subword_pair(rows, ngrams, Output::Context(rand_idx), 0.0, step.learning_rate * 0.75);
+            Self::logistic_pair(rows, word_idx, Output::Context(rand_idx), 0.0, step.learning_rate * 0.75);
         }
 
-        if negative_samples > 0 && self.embeddings.len() > 10 {
-            use std::collections::hash_map::DefaultHasher;
-            use std::hash::{Hash, Hasher};
-            
-            for i in 0..negative_samples {
-                let mut hasher = DefaultHasher::new();
-                word_upper.hash(&mut hasher);
-                i.hash(&mut hasher);
-                let rand_idx = (hasher.finish() as usize) % self.embeddings.len();
-                
-                if rand_idx == word_idx { continue; }
-                
-                let w = self.embeddings[word_idx].clone();
-                let n = self.context_embeddings[rand_idx].clone();
-                
-                let dot = w.dot(&n);
-                let p = sigmoid(dot);
-                let error = 0.0 - p;
-
-                self.embeddings[word_idx].add_scaled(&n, learning_rate * error * 0.75);
-                self.context_embeddings[rand_idx].add_scaled(&w, learning_rate * error * 0.75);
+        rows.target(word_idx).normalize();
+    }
+
+    /// Whether `word_idx` is predicted through the Huffman tree. Words added since the
+    /// tree was last built have no leaf yet, and rebuilding it for every new word would
+    /// cost a pass over the vocabulary each time, so until `refresh_sampler` rebuilds it
+    /// they are predicted through their context row with negative sampling instead.
+    fn on_tree(step: &StepContext, word_idx: usize) -> bool {
+        step.huffman.is_some_and(|tree| tree.covers(word_idx))
+    }
+
+    /// Outputs and labels for predicting `word_idx`: its context row, or with hierarchical
+    /// softmax every node on its Huffman path.
+    fn predict(step: &StepContext, word_idx: usize) -> Vec<(Output, f32)> {
+        match step.huffman {
+            Some(tree) if tree.covers(word_idx) => tree.path(word_idx).into_iter().map(|(node, label)| (Output::Node(node), label)).collect(),
+            _ => vec![(Output::Context(word_idx), 1.0)],
+        }
+    }
+
+    /// Negative samples for one update of `word_idx` predicting `predicted`. None when
+    /// hierarchical softmax, which already normalizes over the whole vocabulary, covers
+    /// every predicted word.
+    fn sample_negatives(step: &StepContext, rng: &mut Rng, word_idx: usize, predicted: &[usize]) -> Vec<usize> {
+        let on_tree = step.huffman.is_some() && predicted.iter().all(|&idx| Self::on_tree(step, idx));
+        if on_tree || step.negative_samples == 0 || step.vocab_len <= 10 {
+            return Vec::new();
+        }
+        (0..step.negative_samples)
+            .map(|_| match step.sampler {
+                Some(table) => table.sample(rng),
+                None => rng.below(step.vocab_len),
+            })
+            .filter(|&idx| idx != word_idx)
+            .collect()
+    }
+
+    fn output_row(rows: &mut impl Rows, output: Output) -> &mut Vector {
+        match output {
+            Output::Context(idx) => rows.context(idx),
+            Output::Node(idx) => rows.node(idx),
+        }
+    }
+
+    /// word2vec CBOW: the combined context vectors predict `word_idx`, and the summed
+    /// error is added back to every context row. The context words' n-grams get the same
+    /// pairs as in skip-gram.
+    fn train_cbow(rows: &mut impl Rows, step: &StepContext, rng: &mut Rng, word_idx: usize, context: &[usize], context_ngrams: &[&[usize]], combine: CbowContext) {
+        let context: Vec<usize> = context.iter().copied().filter(|&idx| idx != word_idx).collect();
+        if context.is_empty() {
+            return;
+        }
+
+        let mut h = rows.target(context[0]).to_owned();
+        for &idx in &context[1..] {
+            h.add(rows.target(idx));
+        }
+        if combine == CbowContext::Mean {
+            h.scale(1.0 / context.len() as f32);
+        }
+
+        let mut outputs: Vec<(Output, f32, f32)> = Self::predict(step, word_idx)
+            .into_iter()
+            .map(|(output, label)| (output, label, step.learning_rate))
+            .collect();
+        for rand_idx in Self::sample_negatives(step, rng, word_idx, &[word_idx]) {
+            outputs.push((Output::Context(rand_idx), 0.0, step.learning_rate * 0.75));
+        }
+
+        let mut error = VectorBuf::zeros(h.data.len());
+        for &(output, label, learning_rate) in &outputs {
+            for ngrams in context_ngrams {
+                Self::subword_pair(rows, ngrams, output, label, learning_rate);
             }
+            let o = Self::output_row(rows, output).to_owned();
+            let p = 1.0 / (1.0 + (-h.dot(&o)).exp());
+            let g = learning_rate * (label - p);
+            error.add_scaled(&o, g);
+            Self::output_row(rows, output).add_scaled(&h, g);
         }
-        
-        self.embeddings[word_idx].normalize();
+
+        for &idx in &context {
+            let row = rows.target(idx);
+            row.add(&error);
+            row.normalize();
+        }
+    }
+
+    /// One logistic update pulling (`label = 1`) or pushing (`label = 0`) a target and an
+    /// output vector.
+    fn logistic_pair(rows: &mut impl Rows, word_idx: usize, output: Output, label: f32, learning_rate: f32) {
+        let w = rows.target(word_idx).to_owned();
+        let c = Self::output_row(rows, output).to_owned();
+
+        let p = 1.0 / (1.0 + (-w.dot(&c)).exp());
+        let g = learning_rate * (label - p);
+
+        rows.target(word_idx).add_scaled(&c, g);
+        Self::output_row(rows, output).add_scaled(&w, g);
+    }
+
+    /// The same update for the mean of a word's n-gram vectors, so the n-grams learn to
+    /// predict the word's contexts. Only the n-grams move; `logistic_pair` updates the
+    /// output row.
+    fn subword_pair(rows: &mut impl Rows, ngrams: &[usize], output: Output, label: f32, learning_rate: f32) {
+        if ngrams.is_empty() {
+            return;
+        }
+        let c = Self::output_row(rows, output).to_owned();
+        let mut h = VectorBuf::zeros(c.data.len());
+        for &bucket in ngrams {
+            h.add(rows.subword(bucket));
+        }
+        h.scale(1.0 / ngrams.len() as f32);
+
+        let p = 1.0 / (1.0 + (-h.dot(&c)).exp());
+        let g = learning_rate * (label - p);
+        for &bucket in ngrams {
+            rows.subword(bucket).add_scaled(&c, g);
+        }
+    }
+
+    /// Reseeds the RNG used for negative sampling so runs can be reproduced.
+    #[allow(dead_code)]
+    pub fn seed_sampler(&mut self, seed: u64) {
+        self.rng = Rng::new(seed);
+    }
+
+    /// Rebuilds the unigram^0.75 table every `SAMPLER_REBUILD_STEPS` training steps so new
+    /// words and shifting counts are picked up without paying for a rebuild on every step.
+    /// The Huffman tree, when there is one, is rebuilt at the same points if words were
+    /// added since.
+    fn refresh_sampler(&mut self, steps: usize) {
+        self.steps_since_sampler += steps;
+        let stale = match &self.sampler {
+            Some(table) => self.steps_since_sampler >= SAMPLER_REBUILD_STEPS || table.len() * 2 < self.counts.len(),
+            None => true,
+        };
+        if stale {
+            self.sampler = AliasTable::unigram(&self.counts, UNIGRAM_POWER);
+            self.steps_since_sampler = 0;
+            if self.huffman.as_ref().is_some_and(|tree| tree.words() != self.counts.len()) {
+                self.rebuild_huffman();
+            }
+        }
+    }
+
+    #[allow(dead_code)]
+    pub fn build_index(&mut self, params: HnswParams) {
+        self.ann_index = Some(HnswIndex::build(params, &self.embeddings));
+        self.index_updates = self.updates;
+    }
+
+    /// Attaches an index built over this brain's rows as they stood at `built_at`, a value
+    /// of `updates()` from this brain or a snapshot of it, inserting any rows added since.
+    #[allow(dead_code)]
+    pub fn attach_index(&mut self, mut index: HnswIndex, built_at: u64) -> Result<(), String> {
+        if index.len() > self.embeddings.len() {
+            return Err(format!("Index covers {} rows but the model only has {}", index.len(), self.embeddings.len()));
+        }
+        for idx in index.len()..self.embeddings.len() {
+            index.insert(idx, &self.embeddings);
+        }
+        self.ann_index = Some(index);
+        self.index_updates = built_at;
+        Ok(())
+    }
+
+    /// Gives a brain decoded from `live`'s own bytes the similarity index, which is not
+    /// part of the model file, along with whether it is stale.
+    #[allow(dead_code)]
+    pub fn adopt_index(&mut self, live: &SemanticBrain) {
+        self.ann_index = live.ann_index.clone();
+        self.updates = live.updates;
+        self.index_updates = live.index_updates;
+    }
+
+    /// How many training passes have moved target rows since the brain was created or loaded.
+    #[allow(dead_code)]
+    pub fn updates(&self) -> u64 {
+        self.updates
+    }
+
+    /// Whether training has moved rows since the index was built. A stale graph may miss
+    /// true neighbours, so lookups fall back to the exact scan and checkpoints stop saving
+    /// it until `build_index` runs again.
+    #[allow(dead_code)]
+    pub fn index_is_stale(&self) -> bool {
+        self.ann_index.is_some() && self.index_updates != self.updates
+    }
+
+    #[allow(dead_code)]
+    pub fn index_recall(&self, k: usize, samples: usize) -> Option<RecallReport> {
+        self.ann_index.as_ref().map(|index| index.recall_at_k(&self.embeddings, k, samples))
     }
 
     #[allow(dead_code)]
     pub fn find_most_similar(&self, word: &str, top_n: usize) -> Vec<(String, f32)> {
-        let word_upper = word.to_uppercase();
-        let target_idx = match self.vocabulary.get(&word_upper) {
-            Some(&idx) => idx,
+        let index = match &self.ann_index {
+            Some(index) if !self.index_is_stale() => index,
+            _ => return self.find_most_similar_exact(word, top_n),
+        };
+
+        let target = match self.get_embedding(word) {
+            Some(target) => target,
             None => return vec![],
         };
+        let target_idx = self.vocabulary.get(&self.key(word)).copied();
+
+        if !target.vector.is_finite() {
+            return vec![("ERROR: Vector for this word is corrupt (NaN). Run /train/wiki/sanitize".to_string(), 0.0)];
+        }
+
+        let hits: Vec<(usize, f32)> = index.search(&target.vector, top_n + 1, &self.embeddings)
+            .into_iter()
+            .filter(|&(idx, _)| Some(idx) != target_idx)
+            .take(top_n)
+            .collect();
+        self.resolve_words(&hits)
+    }
 
-        let target_vec = &self.embeddings[target_idx];
+    #[allow(dead_code)]
+    pub fn find_most_similar_exact(&self, word: &str, top_n: usize) -> Vec<(String, f32)> {
+        let word_key = self.key(word);
+        let target = match self.get_embedding(word) {
+            Some(target) => target,
+            None => return vec![],
+        };
+        let target_idx = self.vocabulary.get(&word_key).copied();
+        let target_vec = &target.vector;
         
         if !target_vec.is_finite() {
             return vec![("ERROR: Vector for this word is corrupt (NaN). Run /train/wiki/sanitize".to_string(), 0.0)];
@@ -180,8 +1007,8 @@ impl SemanticBrain {
         let mut infinite_count = 0;
         let mut valid_count = 0;
 
-        for (other_word, &idx) in &self.vocabulary {
-            if idx == target_idx { continue; }
+        for (idx, other_word) in self.words.iter().enumerate() {
+            if Some(idx) == target_idx { continue; }
             let other_vec = &self.embeddings[idx];
             let sim = target_vec.dot(other_vec);
             if sim.is_nan() {
@@ -194,7 +1021,7 @@ impl SemanticBrain {
             }
         }
 
-        println!("> Similarity test for {}: {} valid, {} NaNs, {} Infinities", word_upper, valid_count, nan_count, infinite_count);
+        println!("> Similarity test for {}: {} valid, {} NaNs, {} Infinities", word_key, valid_count, nan_count, infinite_count);
 
         similarities.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap_or(std::cmp::Ordering::Equal));
         similarities.truncate(top_n);
@@ -204,23 +1031,17 @@ impl SemanticBrain {
     #[allow(dead_code)]
     pub fn balance_vectors(&mut self) {
         if self.embeddings.is_empty() { return; }
+        self.updates += 1;
         
-        let mut idx_to_word = vec![String::new(); self.embeddings.len()];
-        for (word, &idx) in &self.vocabulary {
-            if idx < idx_to_word.len() {
-                idx_to_word[idx] = word.clone();
-            }
-        }
-
-        let mut mean = Vector::zeros(EMBEDDING_DIM);
+        let mut mean = VectorBuf::zeros(self.dim);
         let mut count = 0;
 
         let mut healed_count = 0;
         for idx in 0..self.embeddings.len() {
             let v_len = self.embeddings[idx].length();
             if !self.embeddings[idx].is_finite() || v_len < 1e-4 || v_len > 10.0 {
-                let word = &idx_to_word[idx];
-                self.embeddings[idx] = Self::generate_initial_vector_static(word, false);
+                let fresh = Self::generate_initial_vector_static(&self.words[idx], false, self.dim);
+                self.embeddings[idx].copy_from(&fresh);
                 healed_count += 1;
             }
             mean.add(&self.embeddings[idx]);
@@ -232,25 +1053,47 @@ impl SemanticBrain {
         for idx in 0..self.context_embeddings.len() {
             let v_len = self.context_embeddings[idx].length();
             if !self.context_embeddings[idx].is_finite() || v_len < 1e-4 || v_len > 10.0 {
-                let word = &idx_to_word[idx];
-                self.context_embeddings[idx] = Self::generate_initial_vector_static(word, true);
+                let fresh = Self::generate_initial_vector_static(&self.words[idx], true, self.dim);
+                self.context_embeddings[idx].copy_from(&fresh);
                 healed_ctx_count += 1;
             }
         }
         println!("> Healed {} context embeddings.", healed_ctx_count);
 
+        if let Some(table) = &mut self.subwords {
+            let mut healed_subwords = 0;
+            for v in table.vectors.iter_mut() {
+                if !v.is_finite() {
+                    v.data.fill(0.0);
+                    healed_subwords += 1;
+                }
+            }
+            println!("> Reset {} subword vectors.", healed_subwords);
+        }
+
+        let mut healed_nodes = 0;
+        for v in self.nodes.iter_mut() {
+            if !v.is_finite() {
+                v.data.fill(0.0);
+                healed_nodes += 1;
+            }
+        }
+        if !self.nodes.is_empty() {
+            println!("> Reset {} Huffman node vectors.", healed_nodes);
+        }
+
         if count > 0 {
             mean.scale(1.0 / count as f32);
             println!("> Global mean length: {}", mean.length());
             
             if mean.length() < 0.9 {
-                for v in &mut self.embeddings {
+                for v in self.embeddings.iter_mut() {
                     v.sub_assign(&mean);
                     v.normalize();
                 }
             } else {
                 println!("> Mean is too dominant, skipping centering to avoid collapse. Just normalizing.");
-                for v in &mut self.embeddings {
+                for v in self.embeddings.iter_mut() {
                     v.normalize();
                 }
             }
@@ -263,16 +1106,27 @@ impl SemanticBrain {
         let vec_b = match self.get_embedding(b) { Some(v) => v, None => return vec![] };
         let vec_c = match self.get_embedding(c) { Some(v) => v, None => return vec![] };
 
-        let mut target = vec_b.clone();
-        target.sub_assign(vec_a);
-        target.add(vec_c);
+        let mut target = vec_b.vector.into_owned();
+        target.sub_assign(&vec_a.vector);
+        target.add(&vec_c.vector);
         target.normalize();
 
+        let skip_list = [self.key(a), self.key(b), self.key(c)];
+
+        if let Some(index) = self.ann_index.as_ref().filter(|_| !self.index_is_stale()) {
+            let skip_idx: Vec<usize> = skip_list.iter().filter_map(|w| self.vocabulary.get(w).copied()).collect();
+            let hits: Vec<(usize, f32)> = index.search(&target, top_n + skip_idx.len(), &self.embeddings)
+                .into_iter()
+                .filter(|(idx, _)| !skip_idx.contains(idx))
+                .take(top_n)
+                .collect();
+            return self.resolve_words(&hits);
+        }
+
         let mut similarities = Vec::new();
-        let skip_list = [a.to_uppercase(), b.to_uppercase(), c.to_uppercase()];
 
-        for (word, &idx) in &self.vocabulary {
-            if skip_list.contains(&word) { continue; }
+        for (idx, word) in self.words.iter().enumerate() {
+            if skip_list.contains(word) { continue; }
             let other_vec = &self.embeddings[idx];
             let sim = target.dot(other_vec);
             if sim.is_finite() {
@@ -297,7 +1151,7 @@ impl SemanticBrain {
 
         for word in context_words {
             if let Some(ctx_vec) = self.get_embedding(word) {
-                let score = (target_vec.dot(ctx_vec) / (EMBEDDING_DIM as f32).sqrt()).exp();
+                let score = (target_vec.vector.dot(&ctx_vec.vector) / (self.dim as f32).sqrt()).exp();
                 attention_scores.push((word.clone(), score));
                 total_score += score;
             }
@@ -320,8 +1174,8 @@ impl SemanticBrain {
         let v_a2 = match self.get_embedding(a2) { Some(v) => v, None => return 0.0 };
         let v_b2 = match self.get_embedding(b2) { Some(v) => v, None => return 0.0 };
 
-        let mut rel1 = v_b1.sub(v_a1);
-        let mut rel2 = v_b2.sub(v_a2);
+        let mut rel1 = v_b1.vector.sub(&v_a1.vector);
+        let mut rel2 = v_b2.vector.sub(&v_a2.vector);
         
         let l1 = rel1.length();
         let l2 = rel2.length();
@@ -334,25 +1188,104 @@ impl SemanticBrain {
         rel1.dot(&rel2)
     }
 
-    fn should_skip(&self, word: &str) -> bool {
-        let common_words = ["THE", "AND", "FOR", "THAT", "THIS", "WITH", "FROM", "WAS", "WERE"];
-        if common_words.contains(&word) {
-            use std::collections::hash_map::DefaultHasher;
-            use std::hash::{Hash, Hasher};
-            let mut h = DefaultHasher::new();
-            word.hash(&mut h);
-            (h.finish() % 100) < 80
-        } else {
-            false
+    /// Maps `(index, score)` hits back to words, keeping the order of `hits`.
+    fn resolve_words(&self, hits: &[(usize, f32)]) -> Vec<(String, f32)> {
+        hits.iter()
+            .filter_map(|&(idx, score)| Some((self.word(idx)?.to_string(), score)))
+            .collect()
+    }
+
+    /// Drops words seen fewer than `min_count` times and, with `max_words`, all but that
+    /// many of the most frequent, then renumbers the rest in their old order. The
+    /// negative sampling table, Huffman tree and similarity index are rebuilt for the
+    /// smaller vocabulary; subword vectors are keyed by n-gram and stay as they are.
+    /// Refuses to remove every word.
+    #[allow(dead_code)]
+    pub fn prune(&mut self, min_count: u64, max_words: Option<usize>) -> Result<PruneStats, String> {
+        let words_before = self.vocabulary.len();
+        let mut kept: Vec<usize> = (0..words_before).filter(|&idx| self.counts[idx] >= min_count).collect();
+        if let Some(max_words) = max_words.filter(|&max| max < kept.len()) {
+            // Stable, so equal counts keep the earlier word.
+            kept.sort_by_key(|&idx| std::cmp::Reverse(self.counts[idx]));
+            kept.truncate(max_words);
+            kept.sort_unstable();
         }
+        if kept.is_empty() {
+            return Err(format!("No word has a count of at least {}", min_count));
+        }
+
+        let mut new_index = vec![None; words_before];
+        for (new, &old) in kept.iter().enumerate() {
+            new_index[old] = Some(new);
+        }
+        fn retain_rows<T>(rows: &mut Vec<T>, new_index: &[Option<usize>]) {
+            let mut kept = new_index.iter();
+            rows.retain(|_| kept.next().is_some_and(Option::is_some));
+        }
+        self.embeddings = self.embeddings.gather(&kept);
+        self.context_embeddings = self.context_embeddings.gather(&kept);
+        retain_rows(&mut self.counts, &new_index);
+        retain_rows(&mut self.words, &new_index);
+        self.vocabulary.retain(|_, idx| match new_index[*idx] {
+            Some(new) => {
+                *idx = new;
+                true
+            }
+            None => false,
+        });
+
+        self.frozen = self.frozen.iter().filter_map(|&idx| new_index[idx]).collect();
+        self.total_count = self.counts.iter().sum();
+        self.sampler = None;
+        self.steps_since_sampler = 0;
+        if self.huffman.is_some() {
+            self.rebuild_huffman();
+        }
+        if let Some(params) = self.ann_index.as_ref().map(|index| index.params.clone()) {
+            self.build_index(params);
+        }
+
+        Ok(PruneStats {
+            words_before,
+            words_after: self.vocabulary.len(),
+            min_count_kept: self.counts.iter().copied().min().unwrap_or(0),
+        })
     }
 
-    fn generate_initial_vector_static(word: &str, is_context: bool) -> Vector {
-        let mut data = vec![0.0; EMBEDDING_DIM];
+    /// Counts one corpus occurrence of each token (as produced by `tokenizer()`). Counts
+    /// drive both subsampling and the negative sampling table, so they're collected
+    /// before any token is dropped.
+    #[allow(dead_code)]
+    pub fn observe_tokens(&mut self, tokens: &[String]) {
+        for token in tokens {
+            let idx = self.ensure_word(token);
+            self.counts[idx] += 1;
+            self.total_count += 1;
+        }
+    }
+
+    /// Mikolov et al. subsampling: a word with corpus frequency `f` is kept with
+    /// probability `sqrt(t / f)`. A threshold of zero keeps everything.
+    #[allow(dead_code)]
+    pub fn keep_token(&mut self, word: &str, threshold: f32) -> bool {
+        if threshold <= 0.0 || self.total_count == 0 {
+            return true;
+        }
+        let count = match self.vocabulary.get(word) {
+            Some(&idx) => self.counts[idx],
+            None => return true,
+        };
+        let freq = count as f64 / self.total_count as f64;
+        let keep = (threshold as f64 / freq).sqrt();
+        keep >= 1.0 || (self.rng.next_f32() as f64) < keep
+    }
+
+    fn generate_initial_vector_static(word: &str, is_context: bool, dim: usize) -> VectorBuf {
+        let mut data = vec![0.0; dim];
         use std::collections::hash_map::DefaultHasher;
         use std::hash::{Hash, Hasher};
         
-        for i in (0..EMBEDDING_DIM).step_by(2) {
+        for i in (0..dim).step_by(2) {
             let mut h1 = DefaultHasher::new();
             if is_context { "ctx".hash(&mut h1); }
             word.hash(&mut h1);
@@ -369,11 +1302,11 @@ impl SemanticBrain {
             let theta = 2.0 * std::f32::consts::PI * u2;
             
             data[i] = r * theta.cos() * 0.1;
-            if i + 1 < EMBEDDING_DIM {
+            if i + 1 < dim {
                 data[i+1] = r * theta.sin() * 0.1;
             }
         }
-        let mut v = Vector::new(data);
+        let mut v = VectorBuf::new(data);
         v.normalize();
         v
     }
@@ -385,10 +1318,271 @@ impl SemanticBrain {
 
         let idx = self.embeddings.len();
         self.vocabulary.insert(word.to_string(), idx);
+        self.words.push(word.to_string());
         
-        self.embeddings.push(Self::generate_initial_vector_static(word, false));
-        self.context_embeddings.push(Self::generate_initial_vector_static(word, true));
+        self.counts.push(0);
+        self.embeddings.push(&Self::generate_initial_vector_static(word, false, self.dim));
+        self.context_embeddings.push(&Self::generate_initial_vector_static(word, true, self.dim));
+
+        if let Some(index) = &mut self.ann_index {
+            index.insert(idx, &self.embeddings);
+        }
         
         idx
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::brain::format::{crc32, MODEL_MAGIC};
+
+    fn trained_brain() -> SemanticBrain {
+        let mut brain = SemanticBrain::with_dim(8);
+        brain.enable_subwords(SubwordConfig { buckets: 50, ..SubwordConfig::default() });
+        let words: Vec<String> = (0..200).map(|i| format!("w{}", i * 7 % 23)).collect();
+        for w in words.windows(3) {
+            brain.train_step(&w[1], &[w[0].clone(), w[2].clone()], &[], 0.025, 2);
+        }
+        brain.enable_hierarchical_softmax();
+        brain.metadata.articles_processed = 7;
+        brain
+    }
+
+    #[test]
+    fn huffman_rebuild_resets_moved_nodes() {
+        let mut brain = trained_brain();
+        brain.nodes.as_mut_slice().fill(0.5);
+
+        brain.rebuild_huffman();
+        assert!(brain.nodes.as_slice().iter().all(|&x| x == 0.5));
+
+        let fresh = brain.key("fresh");
+        brain.index_tokens(&[fresh]);
+        brain.rebuild_huffman();
+        assert_eq!(brain.nodes.len(), brain.huffman.as_ref().unwrap().nodes());
+        assert_eq!(brain.nodes.len(), brain.words().len() - 1);
+        assert!(brain.nodes.as_slice().iter().all(|&x| x == 0.0));
+    }
+
+    #[test]
+    fn words_added_after_the_huffman_tree_still_train() {
+        let mut brain = trained_brain();
+        brain.counts.fill(5);
+        brain.refresh_sampler(0);
+        let tokens: Vec<String> = ["w1", "fresh", "w2", "fresh", "w3"].iter().map(|w| brain.key(w)).collect();
+        let ids = brain.index_tokens(&tokens);
+        assert!(!brain.huffman.as_ref().unwrap().covers(ids[1]));
+
+        for objective in [Objective::SkipGram, Objective::Cbow(CbowContext::default())] {
+            let params = WindowParams {
+                objective,
+                loss: Loss::HierarchicalSoftmax,
+                window_size: 2,
+                learning_rate: 0.025,
+                negative_samples: 2,
+            };
+            let delta = brain.train_window_delta(&ids, &[], &params, &mut Rng::new(1));
+            assert!(delta.contexts.iter().any(|&(idx, _)| idx == ids[1]), "{:?}", objective);
+            assert!(!delta.nodes.is_empty(), "{:?}", objective);
+        }
+    }
+
+    /// A file as versions 1 to 5 wrote it: the header checksums the whole payload, and
+    /// from version 5 is padded so the payload starts aligned.
+    fn legacy_file(version: u32, brain: &SemanticBrain, payload: &[u8]) -> Vec<u8> {
+        let mut header = ModelHeader::new(brain.dim, brain.vocabulary.len(), &[], &brain.metadata).unwrap();
+        header.checksum = crc32(payload);
+        let mut header = bincode::serialize(&header).unwrap();
+        if version >= 5 {
+            header.resize((12 + header.len()).next_multiple_of(format::TABLE_ALIGN) - 12, 0);
+        }
+        let mut bytes = MODEL_MAGIC.to_vec();
+        bytes.extend(version.to_le_bytes());
+        bytes.extend((header.len() as u32).to_le_bytes());
+        bytes.extend(header);
+        bytes.extend(payload);
+        bytes
+    }
+
+    fn assert_same_vectors(loaded: &SemanticBrain, brain: &SemanticBrain) {
+        assert_eq!(loaded.dim, brain.dim);
+        assert_eq!(loaded.vocabulary, brain.vocabulary);
+        assert_eq!(loaded.words(), brain.words());
+        assert_eq!(loaded.embeddings.as_slice(), brain.embeddings.as_slice());
+        assert_eq!(loaded.context_embeddings.as_slice(), brain.context_embeddings.as_slice());
+    }
+
+    #[test]
+    fn legacy_versions_migrate() {
+        let brain = trained_brain();
+        let (vocab, targets, contexts) = (&brain.vocabulary, &brain.embeddings, &brain.context_embeddings);
+
+        let v1 = bincode::serialize(&(vocab, targets, contexts)).unwrap();
+        for bytes in [v1.clone(), legacy_file(1, &brain, &v1)] {
+            let loaded = SemanticBrain::from_bytes(&bytes).unwrap();
+            assert_same_vectors(&loaded, &brain);
+            assert_eq!(loaded.counts, vec![1; brain.counts.len()]);
+        }
+
+        let v2 = legacy_file(2, &brain, &bincode::serialize(&(vocab, targets, contexts, &brain.counts)).unwrap());
+        let loaded = SemanticBrain::from_bytes(&v2).unwrap();
+        assert_same_vectors(&loaded, &brain);
+        assert_eq!(loaded.counts, brain.counts);
+        assert_eq!(loaded.metadata.articles_processed, 7);
+
+        let v3 = legacy_file(3, &brain, &bincode::serialize(&(vocab, targets, contexts, &brain.counts, &brain.subwords)).unwrap());
+        let loaded = SemanticBrain::from_bytes(&v3).unwrap();
+        assert_same_vectors(&loaded, &brain);
+        let subwords = |b: &SemanticBrain| b.subwords.as_ref().map(|table| table.vectors.as_slice().to_vec());
+        assert_eq!(subwords(&loaded), subwords(&brain));
+
+        let v4 = bincode::serialize(&(vocab, targets, contexts, &brain.counts, &brain.subwords, &brain.huffman, &brain.nodes)).unwrap();
+        let loaded = SemanticBrain::from_bytes(&legacy_file(4, &brain, &v4)).unwrap();
+        assert_same_vectors(&loaded, &brain);
+        assert_eq!(subwords(&loaded), subwords(&brain));
+        assert_eq!(loaded.nodes.as_slice(), brain.nodes.as_slice());
+        assert_eq!(loaded.huffman.as_ref().map(HuffmanTree::nodes), brain.huffman.as_ref().map(HuffmanTree::nodes));
+    }
+
+    #[test]
+    fn corrupted_files_are_rejected() {
+        let brain = trained_brain();
+        let payload = bincode::serialize(&(&brain.vocabulary, &brain.embeddings, &brain.context_embeddings, &brain.counts)).unwrap();
+        let mut bytes = legacy_file(2, &brain, &payload);
+        let last = bytes.len() - 1;
+        bytes[last] ^= 0x40;
+        assert!(matches!(SemanticBrain::from_bytes(&bytes), Err(ModelError::ChecksumMismatch { .. })));
+
+        let bytes = legacy_file(2, &brain, &payload);
+        assert!(matches!(SemanticBrain::from_bytes(&bytes[..bytes.len() - payload.len() - 1]), Err(ModelError::Truncated)));
+
+        let mut bytes = brain.to_bytes().unwrap();
+        bytes[4..8].copy_from_slice(&(format::FORMAT_VERSION + 1).to_le_bytes());
+        assert!(matches!(SemanticBrain::from_bytes(&bytes), Err(ModelError::UnsupportedVersion { .. })));
+    }
+
+    fn temp_model(name: &str) -> std::path::PathBuf {
+        let dir = std::env::temp_dir().join(format!("vecors-{}-{}", name, std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        dir.join("model.bin")
+    }
+
+    fn assert_same_model(loaded: &SemanticBrain, brain: &SemanticBrain) {
+        assert_same_vectors(loaded, brain);
+        assert_eq!(loaded.counts, brain.counts);
+        assert_eq!(loaded.nodes.as_slice(), brain.nodes.as_slice());
+        let subwords = |b: &SemanticBrain| b.subwords.as_ref().map(|table| (table.config.clone(), table.vectors.as_slice().to_vec()));
+        assert_eq!(subwords(loaded), subwords(brain));
+        assert_eq!(loaded.metadata.articles_processed, brain.metadata.articles_processed);
+    }
+
+    /// Byte range of the first table, the target vectors, in a file written by `to_bytes`.
+    fn first_table(bytes: &[u8]) -> std::ops::Range<usize> {
+        let file = format::read_model(bytes).unwrap().unwrap();
+        let body: FlatPayload = bincode::deserialize(file.payload).unwrap();
+        let range = body.tables[0].range(file.header.embedding_dim as usize, file.payload.len()).unwrap();
+        file.payload_start + range.start..file.payload_start + range.end
+    }
+
+    #[test]
+    fn flat_payload_round_trips() {
+        let brain = trained_brain();
+        let bytes = brain.to_bytes().unwrap();
+        assert_eq!(u32::from_le_bytes(bytes[4..8].try_into().unwrap()), format::FORMAT_VERSION);
+        let loaded = SemanticBrain::from_bytes(&bytes).unwrap();
+        assert!(!loaded.embeddings.is_mapped());
+        assert_same_model(&loaded, &brain);
+    }
+
+    #[test]
+    fn version_5_files_still_load() {
+        let brain = trained_brain();
+        let mut tables = vec![&brain.embeddings, &brain.context_embeddings];
+        tables.extend(brain.subwords.as_ref().map(|table| &table.vectors));
+        tables.push(&brain.nodes);
+        let subwords = brain.subwords.as_ref().map(|table| &table.config);
+        let body = |spans: &[(u64, u64)]| bincode::serialize(&(&brain.vocabulary, &brain.counts, subwords, &brain.huffman, spans)).unwrap();
+        let mut spans = vec![(0, 0); tables.len()];
+        let mut end = body(&spans).len();
+        for (span, table) in spans.iter_mut().zip(&tables) {
+            let start = end.next_multiple_of(format::TABLE_ALIGN);
+            *span = (table.len() as u64, start as u64);
+            end = start + table.as_slice().len() * 4;
+        }
+        let mut payload = body(&spans);
+        for ((_, offset), table) in spans.iter().zip(&tables) {
+            payload.resize(*offset as usize, 0);
+            table.write_le_bytes(&mut payload);
+        }
+
+        let mut bytes = legacy_file(5, &brain, &payload);
+        assert_same_model(&SemanticBrain::from_bytes(&bytes).unwrap(), &brain);
+        let path = temp_model("v5");
+        std::fs::write(&path, &bytes).unwrap();
+        let mapped = SemanticBrain::load(&path).unwrap();
+        assert!(mapped.embeddings.is_mapped());
+        assert_same_model(&mapped, &brain);
+
+        let last = bytes.len() - 1;
+        bytes[last] ^= 0x01;
+        std::fs::write(&path, &bytes).unwrap();
+        assert!(matches!(SemanticBrain::from_bytes(&bytes), Err(ModelError::ChecksumMismatch { .. })));
+        assert!(matches!(SemanticBrain::load(&path), Err(ModelError::ChecksumMismatch { .. })));
+        std::fs::remove_dir_all(path.parent().unwrap()).ok();
+    }
+
+    #[test]
+    fn mapped_tables_copy_on_write() {
+        let brain = trained_brain();
+        let path = temp_model("mapped");
+        brain.save(&path).unwrap();
+
+        let mut mapped = SemanticBrain::load(&path).unwrap();
+        assert!(mapped.embeddings.is_mapped() && mapped.context_embeddings.is_mapped() && mapped.nodes.is_mapped());
+        assert_same_model(&mapped, &brain);
+        let untouched = mapped.clone();
+
+        mapped.train_step("w3", &["w4".to_string()], &[], 0.025, 2);
+        assert!(!mapped.embeddings.is_mapped());
+        assert_ne!(mapped.embeddings.as_slice(), brain.embeddings.as_slice());
+        assert!(untouched.embeddings.is_mapped());
+        assert_same_model(&untouched, &brain);
+
+        // Saving over the file a brain is mapped from leaves that brain reading the old one.
+        mapped.save(&path).unwrap();
+        assert_same_model(&untouched, &brain);
+        assert_same_model(&SemanticBrain::load(&path).unwrap(), &mapped);
+        assert_same_model(&SemanticBrain::from_bytes(&std::fs::read(&path).unwrap()).unwrap(), &mapped);
+        std::fs::remove_dir_all(path.parent().unwrap()).ok();
+    }
+
+    #[test]
+    fn table_checksums_are_checked_in_memory() {
+        let brain = trained_brain();
+        let path = temp_model("checksums");
+        let clean = brain.to_bytes().unwrap();
+
+        let mut bytes = clean.clone();
+        bytes[first_table(&clean).start] ^= 0x01;
+        std::fs::write(&path, &bytes).unwrap();
+        assert!(matches!(SemanticBrain::from_bytes(&bytes), Err(ModelError::ChecksumMismatch { .. })));
+        // A mapped load leaves the tables unread, so only the copy shows the damage.
+        let mapped = SemanticBrain::load(&path).unwrap();
+        assert_ne!(mapped.embeddings.as_slice(), brain.embeddings.as_slice());
+
+        // The vocabulary sits in the body, before the first table.
+        let file = format::read_model(&clean).unwrap().unwrap();
+        let mut bytes = clean.clone();
+        bytes[file.payload_start + 8] ^= 0x01;
+        std::fs::write(&path, &bytes).unwrap();
+        assert!(SemanticBrain::from_bytes(&bytes).is_err());
+        assert!(SemanticBrain::load(&path).is_err());
+
+        let mut bytes = clean.clone();
+        let metadata = bytes.windows(2).position(|w| w == b"{\"").unwrap();
+        bytes[metadata + 2] ^= 0x20;
+        assert!(matches!(SemanticBrain::from_bytes(&bytes), Err(ModelError::ChecksumMismatch { .. })));
+        std::fs::remove_dir_all(path.parent().unwrap()).ok();
+    }
+}
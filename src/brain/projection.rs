@@ -1,31 +1,30 @@
-use crate::brain::linalg::{Vector, Matrix};
-use crate::brain::model::EMBEDDING_DIM;
+use crate::brain::linalg::{Matrix, Vector, VectorBuf};
 
 #[allow(dead_code)]
 pub struct Projector {
     pub projection_matrix: Matrix,
-    pub mean_vector: Vector,
+    pub mean_vector: VectorBuf,
     pub scales: (f32, f32, f32),
 }
 
 impl Projector {
     #[allow(dead_code)]
-    pub fn new() -> Self {
-        let mut mat = Matrix::zeros(3, EMBEDDING_DIM);
+    pub fn new(dim: usize) -> Self {
+        let mut mat = Matrix::zeros(3, dim);
         
-        for i in 0..EMBEDDING_DIM {
-            mat.rows[0].data[i] = (i as f32 * 0.1).sin();
-            mat.rows[1].data[i] = (i as f32 * 0.15).cos();
-            mat.rows[2].data[i] = (i as f32 * 0.2).sin() * (i as f32 * 0.05).cos();
+        for i in 0..dim {
+            mat[0].data[i] = (i as f32 * 0.1).sin();
+            mat[1].data[i] = (i as f32 * 0.15).cos();
+            mat[2].data[i] = (i as f32 * 0.2).sin() * (i as f32 * 0.05).cos();
         }
         
-        mat.rows[0].normalize();
-        mat.rows[1].normalize();
-        mat.rows[2].normalize();
+        mat[0].normalize();
+        mat[1].normalize();
+        mat[2].normalize();
 
         Self { 
             projection_matrix: mat,
-            mean_vector: Vector::zeros(EMBEDDING_DIM),
+            mean_vector: VectorBuf::zeros(dim),
             scales: (1.0, 1.0, 1.0),
         }
     }
@@ -42,11 +41,18 @@ impl Projector {
         )
     }
 
+    /// Fits the projection to `embeddings`, adopting their dimension if it differs
+    /// from the one the projector was created with.
     #[allow(dead_code)]
-    pub fn fit(&mut self, embeddings: &[Vector]) {
+    pub fn fit(&mut self, embeddings: &Matrix) {
         if embeddings.is_empty() { return; }
 
-        let mut mean = Vector::zeros(EMBEDDING_DIM);
+        let dim = embeddings.dim();
+        if dim != self.mean_vector.data.len() {
+            *self = Self::new(dim);
+        }
+
+        let mut mean = VectorBuf::zeros(dim);
         for v in embeddings {
             mean.add(v);
         }
@@ -54,14 +60,14 @@ impl Projector {
         self.mean_vector = mean;
 
         for axis_idx in 0..3 {
-            let mut current_axis = Vector::zeros(EMBEDDING_DIM);
-            for i in 0..EMBEDDING_DIM {
+            let mut current_axis = VectorBuf::zeros(dim);
+            for i in 0..dim {
                 current_axis.data[i] = ((axis_idx + i + 7) as f32 * 0.123).sin() + ((axis_idx * i) as f32 * 0.456).cos();
             }
             current_axis.normalize();
 
             for _ in 0..30 {
-                let mut new_axis = Vector::zeros(EMBEDDING_DIM);
+                let mut new_axis = VectorBuf::zeros(dim);
                 
                 for v in embeddings {
                     let centered_v = v.sub(&self.mean_vector);
@@ -70,7 +76,7 @@ impl Projector {
                 }
 
                 for prev_idx in 0..axis_idx {
-                    let prev_axis = &self.projection_matrix.rows[prev_idx];
+                    let prev_axis = &self.projection_matrix[prev_idx];
                     let overlap = new_axis.dot(prev_axis);
                     new_axis.add_scaled(prev_axis, -overlap);
                 }
@@ -80,7 +86,7 @@ impl Projector {
                     current_axis = new_axis;
                 }
             }
-            self.projection_matrix.rows[axis_idx] = current_axis;
+            self.projection_matrix[axis_idx].copy_from(&current_axis);
         }
 
         let mut vars = [0.0f32; 3];
@@ -0,0 +1,642 @@
+use crate::brain::format::TrainingMetadata;
+use crate::brain::linalg::{Matrix, Vector, VectorBuf};
+use crate::brain::tokenizer::{Tokenizer, UnicodeTokenizer};
+use serde::{Deserialize, Serialize};
+
+pub const QUANTIZED_MAGIC: &[u8; 4] = b"VCQ2";
+/// Exports from before the training metadata (and with it the tokenizer) was included.
+const QUANTIZED_MAGIC_V1: &[u8; 4] = b"VCQ1";
+const FRONT_CODING_BUCKET: usize = 16;
+const KMEANS_ITERATIONS: usize = 12;
+const KMEANS_SAMPLE: usize = 20_000;
+
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+pub enum Quantization {
+    /// One byte per dimension plus one `f32` scale per row.
+    Int8,
+    /// `subspaces` bytes per row, each indexing one of 256 centroids of its sub-vector.
+    Product { subspaces: usize },
+}
+
+/// Sorted strings stored as a full head every `bucket_size` entries followed by
+/// `(shared prefix, suffix)` deltas against the previous entry.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct FrontCodedStrings {
+    bucket_size: usize,
+    len: usize,
+    bucket_offsets: Vec<u32>,
+    data: Vec<u8>,
+}
+
+fn write_varint(out: &mut Vec<u8>, mut value: usize) {
+    while value >= 0x80 {
+        out.push((value as u8) | 0x80);
+        value >>= 7;
+    }
+    out.push(value as u8);
+}
+
+fn read_varint(data: &[u8], pos: &mut usize) -> Result<usize, String> {
+    let mut value = 0usize;
+    let mut shift = 0;
+    loop {
+        let byte = *data.get(*pos).ok_or("string table ends inside a length")?;
+        *pos += 1;
+        if shift >= usize::BITS {
+            return Err("string table length overflows".to_string());
+        }
+        value |= ((byte & 0x7F) as usize) << shift;
+        if byte & 0x80 == 0 {
+            return Ok(value);
+        }
+        shift += 7;
+    }
+}
+
+/// The next `len` bytes at `pos`, advancing past them.
+fn read_bytes<'a>(data: &'a [u8], pos: &mut usize, len: usize) -> Result<&'a [u8], String> {
+    let bytes = pos
+        .checked_add(len)
+        .and_then(|end| data.get(*pos..end))
+        .ok_or("string table ends inside a string")?;
+    *pos += len;
+    Ok(bytes)
+}
+
+impl FrontCodedStrings {
+    /// `words` must already be sorted.
+    pub fn encode(words: &[&str]) -> Self {
+        let mut bucket_offsets = Vec::new();
+        let mut data = Vec::new();
+        let mut prev: &[u8] = &[];
+
+        for (i, word) in words.iter().enumerate() {
+            let bytes = word.as_bytes();
+            if i % FRONT_CODING_BUCKET == 0 {
+                bucket_offsets.push(data.len() as u32);
+                write_varint(&mut data, bytes.len());
+                data.extend_from_slice(bytes);
+            } else {
+                let shared = prev.iter().zip(bytes).take_while(|(a, b)| a == b).count();
+                write_varint(&mut data, shared);
+                write_varint(&mut data, bytes.len() - shared);
+                data.extend_from_slice(&bytes[shared..]);
+            }
+            prev = bytes;
+        }
+
+        Self {
+            bucket_size: FRONT_CODING_BUCKET,
+            len: words.len(),
+            bucket_offsets,
+            data,
+        }
+    }
+
+    #[allow(dead_code)]
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    #[allow(dead_code)]
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Checks that every bucket decodes, so a table read from a damaged file is refused
+    /// up front.
+    pub fn validate(&self) -> Result<(), String> {
+        if self.bucket_size == 0 {
+            return Err("string table has an empty bucket size".to_string());
+        }
+        if self.bucket_offsets.len() != self.len.div_ceil(self.bucket_size) {
+            return Err(format!("string table has {} buckets for {} strings", self.bucket_offsets.len(), self.len));
+        }
+        (0..self.bucket_offsets.len()).try_for_each(|bucket| self.decode_bucket(bucket, |_, _| true))
+    }
+
+    fn decode_bucket(&self, bucket: usize, mut visit: impl FnMut(usize, &[u8]) -> bool) -> Result<(), String> {
+        let mut pos = *self.bucket_offsets.get(bucket).ok_or("string table bucket out of range")? as usize;
+        let first = bucket * self.bucket_size;
+        let last = (first + self.bucket_size).min(self.len);
+        let mut current: Vec<u8> = Vec::new();
+
+        for i in first..last {
+            if i == first {
+                let len = read_varint(&self.data, &mut pos)?;
+                current.extend_from_slice(read_bytes(&self.data, &mut pos, len)?);
+            } else {
+                let shared = read_varint(&self.data, &mut pos)?;
+                let suffix = read_varint(&self.data, &mut pos)?;
+                if shared > current.len() {
+                    return Err("string table shares more than the previous string".to_string());
+                }
+                current.truncate(shared);
+                current.extend_from_slice(read_bytes(&self.data, &mut pos, suffix)?);
+            }
+            if !visit(i, &current) {
+                break;
+            }
+        }
+        Ok(())
+    }
+
+    #[allow(dead_code)]
+    pub fn get(&self, index: usize) -> Option<String> {
+        if index >= self.len { return None; }
+        let mut found = None;
+        self.decode_bucket(index / self.bucket_size, |i, bytes| {
+            if i == index {
+                found = Some(String::from_utf8_lossy(bytes).into_owned());
+                false
+            } else {
+                true
+            }
+        }).ok()?;
+        found
+    }
+
+    pub fn find(&self, word: &str) -> Option<usize> {
+        let target = word.as_bytes();
+        let head = |bucket: usize| -> Result<&[u8], String> {
+            let mut pos = *self.bucket_offsets.get(bucket).ok_or("string table bucket out of range")? as usize;
+            let len = read_varint(&self.data, &mut pos)?;
+            read_bytes(&self.data, &mut pos, len)
+        };
+
+        let buckets = self.bucket_offsets.len();
+        if buckets == 0 { return None; }
+
+        let mut lo = 0;
+        let mut hi = buckets;
+        while hi - lo > 1 {
+            let mid = (lo + hi) / 2;
+            if head(mid).ok()? <= target { lo = mid; } else { hi = mid; }
+        }
+
+        let mut found = None;
+        self.decode_bucket(lo, |i, bytes| {
+            if bytes == target {
+                found = Some(i);
+            }
+            found.is_none() && bytes < target
+        }).ok()?;
+        found
+    }
+
+    pub fn to_vec(&self) -> Result<Vec<String>, String> {
+        let mut out = Vec::with_capacity(self.len);
+        for bucket in 0..self.bucket_offsets.len() {
+            self.decode_bucket(bucket, |_, bytes| {
+                out.push(String::from_utf8_lossy(bytes).into_owned());
+                true
+            })?;
+        }
+        Ok(out)
+    }
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub enum QuantizedTable {
+    Int8 {
+        dim: usize,
+        scales: Vec<f32>,
+        codes: Vec<i8>,
+    },
+    Product {
+        dim: usize,
+        subspaces: usize,
+        /// `subspaces * 256` centroids of `dim / subspaces` floats each.
+        centroids: Vec<f32>,
+        codes: Vec<u8>,
+    },
+}
+
+impl QuantizedTable {
+    pub fn encode(rows: &Matrix, dim: usize, scheme: Quantization) -> Result<Self, String> {
+        match scheme {
+            Quantization::Int8 => {
+                let mut scales = Vec::with_capacity(rows.len());
+                let mut codes = Vec::with_capacity(rows.len() * dim);
+                for row in rows {
+                    let max = row.data.iter().filter(|x| x.is_finite()).fold(0.0f32, |m, x| m.max(x.abs()));
+                    let scale = if max > 0.0 { max / 127.0 } else { 1.0 };
+                    scales.push(scale);
+                    for &x in &row.data {
+                        let q = if x.is_finite() { (x / scale).round().clamp(-127.0, 127.0) } else { 0.0 };
+                        codes.push(q as i8);
+                    }
+                }
+                Ok(QuantizedTable::Int8 { dim, scales, codes })
+            }
+            Quantization::Product { subspaces } => {
+                if subspaces == 0 || !dim.is_multiple_of(subspaces) {
+                    return Err(format!("Embedding dimension {} is not divisible into {} subspaces", dim, subspaces));
+                }
+                let sub_dim = dim / subspaces;
+                let mut centroids = Vec::with_capacity(subspaces * 256 * sub_dim);
+                let mut codes = vec![0u8; rows.len() * subspaces];
+
+                for s in 0..subspaces {
+                    let range = s * sub_dim..(s + 1) * sub_dim;
+                    let book = Self::train_codebook(rows, range.clone());
+                    for (r, row) in rows.iter().enumerate() {
+                        codes[r * subspaces + s] = Self::nearest_centroid(&book, &row.data[range.clone()]) as u8;
+                    }
+                    centroids.extend(book);
+                }
+                Ok(QuantizedTable::Product { dim, subspaces, centroids, codes })
+            }
+        }
+    }
+
+    /// Plain k-means with 256 centroids over an evenly spaced sample of the sub-vectors.
+    fn train_codebook(rows: &Matrix, range: std::ops::Range<usize>) -> Vec<f32> {
+        let sub_dim = range.len();
+        let stride = (rows.len() / KMEANS_SAMPLE).max(1);
+        let sample: Vec<&[f32]> = rows
+            .iter()
+            .step_by(stride)
+            .map(|v| &v.data[range.clone()])
+            .filter(|s| s.iter().all(|x| x.is_finite()))
+            .collect();
+
+        let mut book = vec![0.0f32; 256 * sub_dim];
+        if sample.is_empty() { return book; }
+        for c in 0..256 {
+            let src = sample[(c * sample.len()) / 256];
+            book[c * sub_dim..(c + 1) * sub_dim].copy_from_slice(src);
+        }
+
+        for _ in 0..KMEANS_ITERATIONS {
+            let mut sums = vec![0.0f64; 256 * sub_dim];
+            let mut counts = [0usize; 256];
+            for s in &sample {
+                let c = Self::nearest_centroid(&book, s);
+                counts[c] += 1;
+                for (j, &x) in s.iter().enumerate() {
+                    sums[c * sub_dim + j] += x as f64;
+                }
+            }
+            for c in 0..256 {
+                if counts[c] == 0 { continue; }
+                for j in 0..sub_dim {
+                    book[c * sub_dim + j] = (sums[c * sub_dim + j] / counts[c] as f64) as f32;
+                }
+            }
+        }
+        book
+    }
+
+    fn nearest_centroid(book: &[f32], sub: &[f32]) -> usize {
+        let sub_dim = sub.len();
+        let mut best = 0;
+        let mut best_dist = f32::INFINITY;
+        for (c, centroid) in book.chunks_exact(sub_dim).enumerate() {
+            let dist: f32 = centroid.iter().zip(sub).map(|(a, b)| (a - b) * (a - b)).sum();
+            if dist < best_dist {
+                best_dist = dist;
+                best = c;
+            }
+        }
+        best
+    }
+
+    /// Checks that the codes, scales and centroids agree with `dim`, so rows can be sliced
+    /// out of a table read from a file.
+    pub fn validate(&self) -> Result<(), String> {
+        match self {
+            QuantizedTable::Int8 { dim, scales, codes } => {
+                if scales.len().checked_mul(*dim) != Some(codes.len()) {
+                    return Err(format!("{} int8 codes for {} rows of {} dimensions", codes.len(), scales.len(), dim));
+                }
+            }
+            QuantizedTable::Product { dim, subspaces, centroids, codes } => {
+                if *subspaces == 0 || !dim.is_multiple_of(*subspaces) {
+                    return Err(format!("Embedding dimension {} is not divisible into {} subspaces", dim, subspaces));
+                }
+                if dim.checked_mul(256) != Some(centroids.len()) {
+                    return Err(format!("{} centroid values for {} dimensions", centroids.len(), dim));
+                }
+                if !codes.len().is_multiple_of(*subspaces) {
+                    return Err(format!("{} product codes do not split into rows of {}", codes.len(), subspaces));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    pub fn dim(&self) -> usize {
+        match self {
+            QuantizedTable::Int8 { dim, .. } | QuantizedTable::Product { dim, .. } => *dim,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        match self {
+            QuantizedTable::Int8 { scales, .. } => scales.len(),
+            QuantizedTable::Product { subspaces, codes, .. } => codes.len() / subspaces,
+        }
+    }
+
+    #[allow(dead_code)]
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn decode_row(&self, row: usize) -> VectorBuf {
+        match self {
+            QuantizedTable::Int8 { dim, scales, codes } => {
+                let scale = scales[row];
+                VectorBuf::new(codes[row * dim..(row + 1) * dim].iter().map(|&q| q as f32 * scale).collect())
+            }
+            QuantizedTable::Product { dim, subspaces, centroids, codes } => {
+                let sub_dim = dim / subspaces;
+                let mut data = Vec::with_capacity(*dim);
+                for s in 0..*subspaces {
+                    let c = codes[row * subspaces + s] as usize;
+                    let start = (s * 256 + c) * sub_dim;
+                    data.extend_from_slice(&centroids[start..start + sub_dim]);
+                }
+                VectorBuf::new(data)
+            }
+        }
+    }
+
+    /// Asymmetric distance: the query stays in full precision and is scored against the
+    /// codes without decoding rows. Returns up to `k` `(row, dot)` pairs, best first.
+    pub fn top_k(&self, query: &Vector, k: usize, skip: &[usize]) -> Vec<(usize, f32)> {
+        if query.data.len() != self.dim() {
+            return Vec::new();
+        }
+        let mut scores: Vec<(usize, f32)> = match self {
+            QuantizedTable::Int8 { dim, scales, codes } => scales
+                .iter()
+                .enumerate()
+                .map(|(r, &scale)| {
+                    let row = &codes[r * dim..(r + 1) * dim];
+                    let dot: f32 = row.iter().zip(&query.data).map(|(&q, &x)| q as f32 * x).sum();
+                    (r, dot * scale)
+                })
+                .collect(),
+            QuantizedTable::Product { dim, subspaces, centroids, codes } => {
+                let sub_dim = dim / subspaces;
+                let mut table = vec![0.0f32; subspaces * 256];
+                for s in 0..*subspaces {
+                    let q = &query.data[s * sub_dim..(s + 1) * sub_dim];
+                    for c in 0..256 {
+                        let start = (s * 256 + c) * sub_dim;
+                        table[s * 256 + c] = centroids[start..start + sub_dim].iter().zip(q).map(|(a, b)| a * b).sum();
+                    }
+                }
+                codes
+                    .chunks_exact(*subspaces)
+                    .enumerate()
+                    .map(|(r, row)| (r, row.iter().enumerate().map(|(s, &c)| table[s * 256 + c as usize]).sum()))
+                    .collect()
+            }
+        };
+
+        scores.retain(|(r, sim)| sim.is_finite() && !skip.contains(r));
+        scores.sort_by(|a, b| b.1.total_cmp(&a.1));
+        scores.truncate(k);
+        scores
+    }
+}
+
+/// Compressed, read-only form of `SemanticBrain`. Rows are stored in the sorted order of
+/// `words`, so the row of a word is its position in the front-coded table.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct QuantizedBrain {
+    pub words: FrontCodedStrings,
+    pub embeddings: QuantizedTable,
+    pub context_embeddings: Option<QuantizedTable>,
+    /// `TrainingMetadata` as JSON, as in the full model header.
+    pub metadata_json: String,
+}
+
+/// `VCQ1` layout.
+#[derive(Deserialize)]
+struct QuantizedBrainV1 {
+    words: FrontCodedStrings,
+    embeddings: QuantizedTable,
+    context_embeddings: Option<QuantizedTable>,
+}
+
+impl QuantizedBrain {
+    pub fn is_quantized(bytes: &[u8]) -> bool {
+        bytes.len() >= 4 && (&bytes[..4] == QUANTIZED_MAGIC || &bytes[..4] == QUANTIZED_MAGIC_V1)
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, String> {
+        if !Self::is_quantized(bytes) {
+            return Err("Quantized Model Load Error: missing VCQ2 header".to_string());
+        }
+        let brain = if &bytes[..4] == QUANTIZED_MAGIC_V1 {
+            let v1: QuantizedBrainV1 = bincode::deserialize(&bytes[4..]).map_err(|e| format!("Quantized Model Load Error: {}", e))?;
+            Self {
+                words: v1.words,
+                embeddings: v1.embeddings,
+                context_embeddings: v1.context_embeddings,
+                metadata_json: "{}".to_string(),
+            }
+        } else {
+            bincode::deserialize(&bytes[4..]).map_err(|e| format!("Quantized Model Load Error: {}", e))?
+        };
+        brain.validate().map_err(|e| format!("Quantized Model Load Error: {}", e))?;
+        Ok(brain)
+    }
+
+    /// Every table must decode and hold one row of the same dimension per word.
+    fn validate(&self) -> Result<(), String> {
+        self.words.validate()?;
+        for table in std::iter::once(&self.embeddings).chain(&self.context_embeddings) {
+            table.validate()?;
+            if table.len() != self.words.len() || table.dim() != self.embeddings.dim() {
+                return Err(format!("{} words but {} vectors of {} dimensions", self.words.len(), table.len(), table.dim()));
+            }
+        }
+        Ok(())
+    }
+
+    pub fn metadata(&self) -> Result<TrainingMetadata, String> {
+        serde_json::from_str(&self.metadata_json).map_err(|e| format!("Quantized Model Load Error: metadata: {}", e))
+    }
+
+    pub fn to_bytes(&self) -> Result<Vec<u8>, String> {
+        let body = bincode::serialize(self).map_err(|e| format!("Quantized Model Save Error: {}", e))?;
+        let mut bytes = Vec::with_capacity(body.len() + 4);
+        bytes.extend_from_slice(QUANTIZED_MAGIC);
+        bytes.extend(body);
+        Ok(bytes)
+    }
+
+    fn row(&self, word: &str) -> Option<usize> {
+        let tokenizer = UnicodeTokenizer::new(self.metadata().unwrap_or_default().tokenizer);
+        self.words.find(&tokenizer.normalize(word))
+    }
+
+    /// The decoded target vector of `word`, without decoding any other row.
+    #[allow(dead_code)]
+    pub fn get_embedding(&self, word: &str) -> Option<VectorBuf> {
+        self.row(word).map(|row| self.embeddings.decode_row(row))
+    }
+
+    /// Decodes at most `max_rows` evenly spaced target rows, e.g. to fit a projection
+    /// without holding the whole table in full precision.
+    #[allow(dead_code)]
+    pub fn sample_rows(&self, max_rows: usize) -> Matrix {
+        let mut rows = Matrix::new(self.embeddings.dim());
+        let total = self.embeddings.len();
+        if max_rows == 0 || total == 0 {
+            return rows;
+        }
+        let step = total.div_ceil(max_rows);
+        for row in (0..total).step_by(step) {
+            rows.push(&self.embeddings.decode_row(row));
+        }
+        rows
+    }
+
+    #[allow(dead_code)]
+    pub fn find_most_similar(&self, word: &str, top_n: usize) -> Vec<(String, f32)> {
+        let row = match self.row(word) {
+            Some(row) => row,
+            None => return vec![],
+        };
+        let mut query = self.embeddings.decode_row(row);
+        query.normalize();
+        self.embeddings
+            .top_k(&query, top_n, &[row])
+            .into_iter()
+            .filter_map(|(r, sim)| self.words.get(r).map(|w| (w, sim)))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::brain::export::ImportedVectors;
+    use crate::brain::model::SemanticBrain;
+    use crate::brain::sampling::Rng;
+
+    fn sorted_words() -> Vec<String> {
+        let mut words: Vec<String> = (0..100).map(|i| format!("w{:03}{}", i * 7 % 100, "x".repeat(i % 5))).collect();
+        words.push("straße".to_string());
+        words.sort();
+        words.dedup();
+        words
+    }
+
+    fn brain(words: usize, dim: usize) -> SemanticBrain {
+        let mut rng = Rng::new(3);
+        let rows = (0..words)
+            .map(|i| (format!("word{}", i), VectorBuf::new((0..dim).map(|_| rng.next_f32() - 0.5).collect())))
+            .collect();
+        SemanticBrain::from_vectors(ImportedVectors { dim, rows })
+    }
+
+    #[test]
+    fn varints_round_trip() {
+        for value in [0, 1, 127, 128, 300, 1 << 35, usize::MAX] {
+            let mut data = Vec::new();
+            write_varint(&mut data, value);
+            let mut pos = 0;
+            assert_eq!(read_varint(&data, &mut pos), Ok(value));
+            assert_eq!(pos, data.len());
+            assert!(read_varint(&data[..data.len() - 1], &mut 0).is_err());
+        }
+        assert!(read_varint(&[0x80; 11], &mut 0).is_err());
+    }
+
+    #[test]
+    fn front_coded_strings_round_trip() {
+        let words = sorted_words();
+        let refs: Vec<&str> = words.iter().map(String::as_str).collect();
+        let table = FrontCodedStrings::encode(&refs);
+        assert_eq!(table.validate(), Ok(()));
+        assert_eq!(table.to_vec(), Ok(words.clone()));
+        for (i, word) in words.iter().enumerate() {
+            assert_eq!(table.get(i).as_ref(), Some(word));
+            assert_eq!(table.find(word), Some(i));
+        }
+        assert_eq!(table.get(words.len()), None);
+        assert_eq!(table.find("w0"), None);
+        assert_eq!(table.find("zzz"), None);
+    }
+
+    #[test]
+    fn truncated_front_coded_strings_are_rejected() {
+        let words = sorted_words();
+        let refs: Vec<&str> = words.iter().map(String::as_str).collect();
+        let table = FrontCodedStrings::encode(&refs);
+        for cut in 0..table.data.len() {
+            let mut damaged = table.clone();
+            damaged.data.truncate(cut);
+            assert!(damaged.validate().is_err(), "cut at {}", cut);
+            assert!(damaged.to_vec().is_err());
+            let _ = damaged.find(&words[words.len() - 1]);
+            let _ = damaged.get(words.len() - 1);
+        }
+        let mut damaged = table.clone();
+        damaged.bucket_offsets.pop();
+        assert!(damaged.validate().is_err());
+        let _ = damaged.find(&words[words.len() - 1]);
+    }
+
+    #[test]
+    fn quantized_models_round_trip() {
+        let brain = brain(100, 16);
+        for scheme in [Quantization::Int8, Quantization::Product { subspaces: 4 }] {
+            let bytes = brain.to_quantized_bytes(scheme, true).unwrap();
+            let quantized = QuantizedBrain::from_bytes(&bytes).unwrap();
+            let decoded = SemanticBrain::from_quantized(&quantized).unwrap();
+            assert_eq!(decoded.vocabulary.len(), brain.vocabulary.len());
+            for (word, &idx) in &brain.vocabulary {
+                let row = &decoded.embeddings[decoded.vocabulary[word]];
+                let error: f32 = brain.embeddings[idx].data.iter().zip(&row.data).map(|(a, b)| (a - b).abs()).fold(0.0, f32::max);
+                assert!(error < 0.5, "{:?} row of {} is off by {}", scheme, word, error);
+            }
+        }
+    }
+
+    #[test]
+    fn code_search_matches_decoded_search() {
+        let brain = brain(300, 16);
+        let bytes = brain.to_quantized_bytes(Quantization::Int8, false).unwrap();
+        let quantized = QuantizedBrain::from_bytes(&bytes).unwrap();
+        let decoded = SemanticBrain::from_quantized(&quantized).unwrap();
+        for word in ["word0", "word17", "word299"] {
+            let from_codes: Vec<String> = quantized.find_most_similar(word, 5).into_iter().map(|(w, _)| w).collect();
+            let exact: Vec<String> = decoded.find_most_similar_exact(word, 5).into_iter().map(|(w, _)| w).collect();
+            assert_eq!(from_codes, exact, "neighbours of {}", word);
+        }
+    }
+
+    #[test]
+    fn rows_decode_on_demand() {
+        let brain = brain(50, 8);
+        let quantized = QuantizedBrain::from_bytes(&brain.to_quantized_bytes(Quantization::Int8, false).unwrap()).unwrap();
+        let decoded = SemanticBrain::from_quantized(&quantized).unwrap();
+        assert_eq!(quantized.get_embedding("word7").unwrap().data, decoded.get_embedding("word7").unwrap().vector.data);
+        assert!(quantized.get_embedding("missing").is_none());
+
+        let sample = quantized.sample_rows(20);
+        assert_eq!((sample.len(), sample.dim()), (17, 8));
+        assert_eq!(sample[1].data, quantized.embeddings.decode_row(3).data);
+        assert_eq!(quantized.sample_rows(500).len(), 50);
+        assert!(quantized.sample_rows(0).is_empty());
+    }
+
+    #[test]
+    fn truncated_quantized_models_are_rejected() {
+        let brain = brain(20, 4);
+        for scheme in [Quantization::Int8, Quantization::Product { subspaces: 2 }] {
+            let bytes = brain.to_quantized_bytes(scheme, false).unwrap();
+            for cut in 0..bytes.len() {
+                assert!(QuantizedBrain::from_bytes(&bytes[..cut]).is_err(), "{:?} cut at {}", scheme, cut);
+            }
+        }
+    }
+}
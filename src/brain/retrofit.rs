@@ -0,0 +1,142 @@
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use crate::brain::model::{JsonWordData, SemanticBrain};
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RetrofitConfig {
+    pub iterations: usize,
+    /// Pull of a word's original vector.
+    pub alpha: f32,
+    /// Combined pull of its lexicon neighbours, shared evenly between them.
+    pub beta: f32,
+}
+
+impl Default for RetrofitConfig {
+    /// The settings from Faruqui et al., 2015.
+    fn default() -> Self {
+        Self { iterations: 10, alpha: 1.0, beta: 1.0 }
+    }
+}
+
+/// Undirected synonym graph over vocabulary keys.
+#[derive(Default)]
+pub struct Lexicon {
+    neighbours: BTreeMap<String, BTreeSet<String>>,
+}
+
+impl Lexicon {
+    /// `SYNONYMS` of every headword in the dictionary JSON format that `extract_context`
+    /// reads. Antonyms and definitions are ignored.
+    pub fn from_dictionary(brain: &SemanticBrain, json: &str) -> Result<Self, String> {
+        let data: HashMap<String, JsonWordData> = serde_json::from_str(json)
+            .map_err(|e| format!("JSON Parse Error: {}", e))?;
+        let mut lexicon = Self::default();
+        for (word, info) in data {
+            for synonym in &info.synonyms {
+                lexicon.link(brain.key(&word), brain.key(synonym));
+            }
+        }
+        Ok(lexicon)
+    }
+
+    /// One word per line followed by its neighbours, as in the retrofitting paper's
+    /// lexicon files. Fields are split on tabs when the line has any, so multi-word
+    /// entries can be written with spaces; otherwise on whitespace.
+    pub fn from_edge_list(brain: &SemanticBrain, text: &str) -> Self {
+        let mut lexicon = Self::default();
+        for line in text.lines().filter(|line| !line.trim_start().starts_with('#')) {
+            let mut fields: Vec<&str> = if line.contains('\t') {
+                line.split('\t').collect()
+            } else {
+                line.split_whitespace().collect()
+            };
+            fields.retain(|f| !f.trim().is_empty());
+            let Some((word, rest)) = fields.split_first() else {
+                continue;
+            };
+            for neighbour in rest {
+                lexicon.link(brain.key(word), brain.key(neighbour));
+            }
+        }
+        lexicon
+    }
+
+    fn link(&mut self, a: String, b: String) {
+        if a.is_empty() || b.is_empty() || a == b {
+            return;
+        }
+        self.neighbours.entry(a.clone()).or_default().insert(b.clone());
+        self.neighbours.entry(b).or_default().insert(a);
+    }
+
+    /// Undirected edges in the graph.
+    pub fn edges(&self) -> usize {
+        self.neighbours.values().map(BTreeSet::len).sum::<usize>() / 2
+    }
+}
+
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct RetrofitStats {
+    /// Vocabulary words with at least one neighbour in the vocabulary.
+    pub words: usize,
+    /// Lexicon edges with both ends in the vocabulary.
+    pub edges: usize,
+    /// Mean cosine distance each retrofitted word moved from its original vector.
+    pub mean_shift: f32,
+}
+
+/// Retrofits `brain.embeddings` to `lexicon` (Faruqui et al., 2015): every word with
+/// neighbours moves to the weighted mean of its original vector and its neighbours'
+/// current vectors, repeated `config.iterations` times. Words outside the lexicon and
+/// the context vectors are left alone, and the similarity index is rebuilt to match.
+#[allow(dead_code)]
+pub fn retrofit(brain: &mut SemanticBrain, lexicon: &Lexicon, config: &RetrofitConfig) -> RetrofitStats {
+    let graph: Vec<(usize, Vec<usize>)> = lexicon
+        .neighbours
+        .iter()
+        .filter_map(|(word, neighbours)| {
+            let idx = *brain.vocabulary.get(word)?;
+            let neighbours: Vec<usize> = neighbours.iter().filter_map(|n| brain.vocabulary.get(n).copied()).collect();
+            (!neighbours.is_empty() && brain.embeddings[idx].is_finite()).then_some((idx, neighbours))
+        })
+        .collect();
+    let original: HashMap<usize, _> = graph.iter().map(|(idx, _)| (*idx, brain.embeddings[*idx].to_owned())).collect();
+
+    for _ in 0..config.iterations {
+        for (idx, neighbours) in &graph {
+            let mut updated = original[idx].clone();
+            updated.scale(config.alpha);
+            let share = config.beta / neighbours.len() as f32;
+            let mut weight = config.alpha;
+            for &n in neighbours {
+                if brain.embeddings[n].is_finite() {
+                    updated.add_scaled(&brain.embeddings[n], share);
+                    weight += share;
+                }
+            }
+            if weight > 0.0 {
+                updated.scale(1.0 / weight);
+                brain.embeddings[*idx].copy_from(&updated);
+            }
+        }
+    }
+
+    let mut shift = 0.0;
+    for (idx, _) in &graph {
+        brain.embeddings[*idx].normalize();
+        let mut before = original[idx].clone();
+        before.normalize();
+        shift += 1.0 - brain.embeddings[*idx].dot(&before);
+    }
+
+    if let Some(params) = brain.ann_index.as_ref().map(|index| index.params.clone()) {
+        brain.build_index(params);
+    }
+
+    RetrofitStats {
+        words: graph.len(),
+        edges: graph.iter().map(|(_, n)| n.len()).sum::<usize>() / 2,
+        mean_shift: if graph.is_empty() { 0.0 } else { shift / graph.len() as f32 },
+    }
+}
@@ -0,0 +1,156 @@
+/// Small seeded xorshift64* generator so training runs are reproducible without an
+/// external RNG crate. Not suitable for anything security related.
+#[derive(Clone, Debug)]
+pub struct Rng {
+    state: u64,
+}
+
+impl Default for Rng {
+    fn default() -> Self {
+        Self::new(0x5EED)
+    }
+}
+
+impl Rng {
+    pub fn new(seed: u64) -> Self {
+        // Run the seed through SplitMix64 so nearby seeds give unrelated streams.
+        let mut z = seed.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^= z >> 31;
+        Self { state: if z == 0 { 0x5EED } else { z } }
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        let mut x = self.state;
+        x ^= x >> 12;
+        x ^= x << 25;
+        x ^= x >> 27;
+        self.state = x;
+        x.wrapping_mul(0x2545_F491_4F6C_DD1D)
+    }
+
+    /// Uniform in `[0, 1)`.
+    pub fn next_f32(&mut self) -> f32 {
+        (self.next_u64() >> 40) as f32 / (1u64 << 24) as f32
+    }
+
+    /// Uniform in `0..n`; `n` must be non-zero.
+    pub fn below(&mut self, n: usize) -> usize {
+        (self.next_u64() % n as u64) as usize
+    }
+}
+
+/// Walker's alias method: O(1) draws from a fixed discrete distribution.
+#[derive(Clone)]
+pub struct AliasTable {
+    prob: Vec<f32>,
+    alias: Vec<u32>,
+}
+
+impl AliasTable {
+    /// Distribution proportional to `count^power` (word2vec uses 0.75). Returns `None`
+    /// when every count is zero.
+    pub fn unigram(counts: &[u64], power: f64) -> Option<Self> {
+        let weights: Vec<f64> = counts.iter().map(|&c| (c as f64).powf(power)).collect();
+        let total: f64 = weights.iter().sum();
+        if counts.is_empty() || total <= 0.0 {
+            return None;
+        }
+
+        let n = weights.len();
+        let mut scaled: Vec<f64> = weights.iter().map(|w| w * n as f64 / total).collect();
+        let mut prob = vec![1.0f32; n];
+        let mut alias: Vec<u32> = (0..n as u32).collect();
+
+        let mut small: Vec<usize> = Vec::new();
+        let mut large: Vec<usize> = Vec::new();
+        for (i, &p) in scaled.iter().enumerate() {
+            if p < 1.0 { small.push(i); } else { large.push(i); }
+        }
+
+        while let (Some(s), Some(&l)) = (small.pop(), large.last()) {
+            prob[s] = scaled[s] as f32;
+            alias[s] = l as u32;
+            scaled[l] -= 1.0 - scaled[s];
+            if scaled[l] < 1.0 {
+                large.pop();
+                small.push(l);
+            }
+        }
+        // Rounding can leave entries in `small` with no partner; they keep `prob` 1.0,
+        // except that a word with no weight at all must never be drawn.
+        if let Some(drawable) = weights.iter().position(|&w| w > 0.0) {
+            for s in small.into_iter().filter(|&s| weights[s] == 0.0) {
+                prob[s] = 0.0;
+                alias[s] = drawable as u32;
+            }
+        }
+
+        Some(Self { prob, alias })
+    }
+
+    pub fn len(&self) -> usize {
+        self.prob.len()
+    }
+
+    #[allow(dead_code)]
+    pub fn is_empty(&self) -> bool {
+        self.prob.is_empty()
+    }
+
+    pub fn sample(&self, rng: &mut Rng) -> usize {
+        let i = rng.below(self.prob.len());
+        if rng.next_f32() < self.prob[i] { i } else { self.alias[i] as usize }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rng_is_seeded() {
+        let draws = |seed| {
+            let mut rng = Rng::new(seed);
+            (0..8).map(|_| rng.next_u64()).collect::<Vec<_>>()
+        };
+        assert_eq!(draws(1), draws(1));
+        assert_ne!(draws(1), draws(2));
+
+        let mut rng = Rng::new(3);
+        for _ in 0..1000 {
+            assert!(rng.below(7) < 7);
+            assert!((0.0..1.0).contains(&rng.next_f32()));
+        }
+    }
+
+    #[test]
+    fn alias_table_draws_count_to_the_power() {
+        // Weights 0, 1, 8, 27, 0 out of 36.
+        let table = AliasTable::unigram(&[0, 1, 16, 81, 0], 0.75).unwrap();
+        let mut rng = Rng::new(7);
+        let mut drawn = [0usize; 5];
+        let draws = 200_000;
+        for _ in 0..draws {
+            drawn[table.sample(&mut rng)] += 1;
+        }
+        assert_eq!((drawn[0], drawn[4]), (0, 0));
+        for (idx, expected) in [(1, 1.0 / 36.0), (2, 8.0 / 36.0), (3, 27.0 / 36.0)] {
+            let share = drawn[idx] as f64 / draws as f64;
+            assert!((share - expected).abs() < 0.01, "word {} drawn {} of the time, expected {}", idx, share, expected);
+        }
+    }
+
+    #[test]
+    fn zero_counts_are_never_drawn() {
+        let counts: Vec<u64> = (0..1000).map(|i| if i % 3 == 0 { 0 } else { i }).collect();
+        let table = AliasTable::unigram(&counts, 0.75).unwrap();
+        let mut rng = Rng::new(11);
+        for _ in 0..100_000 {
+            assert_ne!(counts[table.sample(&mut rng)], 0);
+        }
+        assert!(AliasTable::unigram(&[0, 0], 0.75).is_none());
+        assert!(AliasTable::unigram(&[], 0.75).is_none());
+    }
+}
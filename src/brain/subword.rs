@@ -0,0 +1,84 @@
+use crate::brain::linalg::{Matrix, VectorBuf};
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SubwordConfig {
+    /// Shortest character n-gram, counting the `<` and `>` word boundary markers.
+    pub min_n: usize,
+    pub max_n: usize,
+    /// N-grams are hashed into this many shared vectors.
+    pub buckets: usize,
+}
+
+impl Default for SubwordConfig {
+    fn default() -> Self {
+        Self { min_n: 3, max_n: 6, buckets: 200_000 }
+    }
+}
+
+/// fastText-style character n-gram vectors. Each word's n-grams are trained against the
+/// same contexts as the word itself, so a word missing from the vocabulary still gets a
+/// vector from the n-grams it shares with known words.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct SubwordTable {
+    pub config: SubwordConfig,
+    pub vectors: Matrix,
+}
+
+impl SubwordTable {
+    /// Buckets start at zero, so an n-gram only contributes once it has been trained.
+    pub fn new(config: SubwordConfig, dim: usize) -> Self {
+        let vectors = Matrix::zeros(config.buckets, dim);
+        Self { config, vectors }
+    }
+
+    /// Bucket of every n-gram of `<word>`, as fastText does. The word as a whole is left
+    /// out since it has its own row in the vocabulary.
+    pub fn buckets(&self, word: &str) -> Vec<usize> {
+        let chars: Vec<char> = std::iter::once('<').chain(word.chars()).chain(std::iter::once('>')).collect();
+        let mut ids = Vec::new();
+        if self.vectors.is_empty() {
+            return ids;
+        }
+        for start in 0..chars.len() {
+            for n in self.config.min_n..=self.config.max_n {
+                let end = start + n;
+                if end > chars.len() {
+                    break;
+                }
+                if start == 0 && end == chars.len() {
+                    continue;
+                }
+                let gram: String = chars[start..end].iter().collect();
+                ids.push(fnv1a(gram.as_bytes()) as usize % self.vectors.len());
+            }
+        }
+        ids
+    }
+
+    /// Mean of the n-gram vectors of `word`, normalized like the word vectors. `None`
+    /// when none of its n-grams have been trained.
+    pub fn compose(&self, word: &str) -> Option<VectorBuf> {
+        let buckets = self.buckets(word);
+        let mut sum = VectorBuf::zeros(self.vectors.dim());
+        for &bucket in &buckets {
+            sum.add(&self.vectors[bucket]);
+        }
+        if !sum.is_finite() || sum.length() < 1e-6 {
+            return None;
+        }
+        sum.normalize();
+        Some(sum)
+    }
+}
+
+/// 32-bit FNV-1a, the n-gram hash fastText uses.
+fn fnv1a(bytes: &[u8]) -> u32 {
+    let mut hash = 2_166_136_261u32;
+    for &b in bytes {
+        hash ^= b as u32;
+        hash = hash.wrapping_mul(16_777_619);
+    }
+    hash
+}
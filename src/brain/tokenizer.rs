@@ -0,0 +1,209 @@
+use serde::{Deserialize, Serialize};
+use unicode_normalization::char::is_combining_mark;
+use unicode_normalization::UnicodeNormalization;
+use unicode_segmentation::UnicodeSegmentation;
+
+/// Joins the words of a learned phrase into one token, e.g. `NEW_YORK`.
+pub const PHRASE_SEPARATOR: char = '_';
+
+/// Turns text into vocabulary keys. Training and every lookup on a brain have to agree,
+/// so a brain carries the configuration its vocabulary was built with.
+pub trait Tokenizer {
+    /// Splits running text into normalized tokens.
+    fn tokenize(&self, text: &str) -> Vec<String>;
+
+    /// Normalizes a single word the same way `tokenize` would, for lookups.
+    fn normalize(&self, word: &str) -> String;
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CaseMode {
+    /// Upper case, as every model before the tokenizer was configurable.
+    #[default]
+    Upper,
+    /// Unicode lower case mapping.
+    Fold,
+    Preserve,
+}
+
+/// Stored in the model metadata as JSON, so new options need a `Default` that matches
+/// how older models were tokenized.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TokenizerConfig {
+    /// Apply NFKC first, so ligatures, full-width forms and the like match their plain
+    /// spellings.
+    pub nfkc: bool,
+    pub case: CaseMode,
+    /// Drop combining marks, so "CAFÉ" and "CAFE" are the same token.
+    pub strip_diacritics: bool,
+    /// Keep tokens with no letters in them, such as "1912".
+    pub keep_numbers: bool,
+    /// Keep hyphenated compounds like "COVID-19" as one token.
+    pub join_hyphens: bool,
+    /// Shortest token kept, in characters.
+    pub min_length: usize,
+}
+
+impl Default for TokenizerConfig {
+    fn default() -> Self {
+        Self {
+            nfkc: true,
+            case: CaseMode::Upper,
+            strip_diacritics: false,
+            keep_numbers: true,
+            join_hyphens: true,
+            min_length: 2,
+        }
+    }
+}
+
+/// Default tokenizer: UAX #29 word boundaries over normalized text.
+#[derive(Clone, Debug, Default)]
+pub struct UnicodeTokenizer {
+    pub config: TokenizerConfig,
+}
+
+impl UnicodeTokenizer {
+    pub fn new(config: TokenizerConfig) -> Self {
+        Self { config }
+    }
+
+    fn normalize_text(&self, text: &str) -> String {
+        let mut text: String = if self.config.nfkc {
+            text.nfkc().collect()
+        } else {
+            text.to_string()
+        };
+        if self.config.strip_diacritics {
+            let decomposed: String = if self.config.nfkc {
+                text.nfkd().filter(|c| !is_combining_mark(*c)).collect()
+            } else {
+                text.nfd().filter(|c| !is_combining_mark(*c)).collect()
+            };
+            text = decomposed.nfc().collect();
+        }
+        match self.config.case {
+            CaseMode::Upper => text.to_uppercase(),
+            CaseMode::Fold => text.to_lowercase(),
+            CaseMode::Preserve => text,
+        }
+    }
+
+    fn flush(&self, current: &mut String, tokens: &mut Vec<String>) {
+        if current.is_empty() {
+            return;
+        }
+        let token = std::mem::take(current);
+        let long_enough = token.chars().count() >= self.config.min_length;
+        if long_enough && (self.config.keep_numbers || token.chars().any(char::is_alphabetic)) {
+            tokens.push(token);
+        }
+    }
+}
+
+fn is_hyphen_char(c: char) -> bool {
+    matches!(c, '-' | '\u{2010}' | '\u{2011}')
+}
+
+fn is_hyphen(segment: &str) -> bool {
+    let mut chars = segment.chars();
+    chars.next().is_some_and(is_hyphen_char) && chars.next().is_none()
+}
+
+impl Tokenizer for UnicodeTokenizer {
+    fn tokenize(&self, text: &str) -> Vec<String> {
+        let text = self.normalize_text(text);
+        let mut tokens = Vec::new();
+        let mut current = String::new();
+        let mut pending_hyphen = false;
+
+        for segment in text.split_word_bounds() {
+            if segment.chars().any(char::is_alphanumeric) {
+                if pending_hyphen {
+                    current.push('-');
+                } else {
+                    self.flush(&mut current, &mut tokens);
+                }
+                current.push_str(segment);
+                pending_hyphen = false;
+            } else if self.config.join_hyphens && is_hyphen(segment) && !current.is_empty() && !pending_hyphen {
+                pending_hyphen = true;
+            } else {
+                self.flush(&mut current, &mut tokens);
+                pending_hyphen = false;
+            }
+        }
+        self.flush(&mut current, &mut tokens);
+        tokens
+    }
+
+    /// Hyphens are written as `-`, as `tokenize` joins them.
+    fn normalize(&self, word: &str) -> String {
+        self.normalize_text(word.trim())
+            .chars()
+            .map(|c| if is_hyphen_char(c) { '-' } else { c })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tokens(config: TokenizerConfig, text: &str) -> Vec<String> {
+        UnicodeTokenizer::new(config).tokenize(text)
+    }
+
+    #[test]
+    fn diacritics_are_kept_unless_stripped() {
+        assert_eq!(tokens(TokenizerConfig::default(), "Café au lait"), ["CAFÉ", "AU", "LAIT"]);
+        let strip = TokenizerConfig { strip_diacritics: true, ..TokenizerConfig::default() };
+        assert_eq!(tokens(strip.clone(), "Café au lait"), ["CAFE", "AU", "LAIT"]);
+        assert_eq!(UnicodeTokenizer::new(strip).normalize("café"), "CAFE");
+    }
+
+    #[test]
+    fn hyphenated_compounds_join() {
+        assert_eq!(tokens(TokenizerConfig::default(), "COVID-19 cases"), ["COVID-19", "CASES"]);
+        assert_eq!(tokens(TokenizerConfig::default(), "COVID\u{2011}19 and well\u{2010}known"), ["COVID-19", "AND", "WELL-KNOWN"]);
+        assert_eq!(tokens(TokenizerConfig::default(), "trailing- dash"), ["TRAILING", "DASH"]);
+        let split = TokenizerConfig { join_hyphens: false, ..TokenizerConfig::default() };
+        assert_eq!(tokens(split, "COVID-19 cases"), ["COVID", "19", "CASES"]);
+    }
+
+    #[test]
+    fn lookups_match_joined_hyphens() {
+        let tokenizer = UnicodeTokenizer::default();
+        for word in ["covid-19", "COVID\u{2010}19", "Covid\u{2011}19"] {
+            assert_eq!(tokenizer.normalize(word), tokenizer.tokenize("COVID-19 ")[0], "{:?}", word);
+        }
+    }
+
+    #[test]
+    fn numbers_and_short_tokens() {
+        assert_eq!(tokens(TokenizerConfig::default(), "in 1912 a cat"), ["IN", "1912", "CAT"]);
+        let no_numbers = TokenizerConfig { keep_numbers: false, ..TokenizerConfig::default() };
+        assert_eq!(tokens(no_numbers, "in 1912 a cat, COVID-19"), ["IN", "CAT", "COVID-19"]);
+        let single = TokenizerConfig { min_length: 1, ..TokenizerConfig::default() };
+        assert_eq!(tokens(single, "in 1912 a cat"), ["IN", "1912", "A", "CAT"]);
+        let long = TokenizerConfig { min_length: 4, ..TokenizerConfig::default() };
+        assert_eq!(tokens(long, "in 1912 a cat"), ["1912"]);
+    }
+
+    #[test]
+    fn nfkc_folds_compatibility_forms() {
+        assert_eq!(tokens(TokenizerConfig::default(), "\u{FF57}\u{FF49}\u{FF4B}\u{FF49} page"), ["WIKI", "PAGE"]);
+        let raw = TokenizerConfig { nfkc: false, ..TokenizerConfig::default() };
+        assert_eq!(tokens(raw, "\u{FF57}\u{FF49}\u{FF4B}\u{FF49} page"), ["\u{FF37}\u{FF29}\u{FF2B}\u{FF29}", "PAGE"]);
+    }
+
+    #[test]
+    fn case_modes() {
+        let case = |case| tokens(TokenizerConfig { case, ..TokenizerConfig::default() }, "Hello Straße");
+        assert_eq!(case(CaseMode::Upper), ["HELLO", "STRASSE"]);
+        assert_eq!(case(CaseMode::Fold), ["hello", "straße"]);
+        assert_eq!(case(CaseMode::Preserve), ["Hello", "Straße"]);
+    }
+}
@@ -2,13 +2,17 @@ use bevy::prelude::*;
 use wasm_bindgen::prelude::*;
 use std::sync::{Arc, Mutex};
 use lazy_static::lazy_static;
-use crate::brain::model::SemanticBrain;
+use crate::brain::linalg::VectorBuf;
+use crate::brain::model::{SemanticBrain, DEFAULT_EMBEDDING_DIM};
 use crate::brain::projection::Projector;
+use crate::brain::quantize::QuantizedBrain;
 use crate::turso::TursoClient;
 
 lazy_static! {
     static ref SEMANTIC_BRAIN: Arc<Mutex<SemanticBrain>> = Arc::new(Mutex::new(SemanticBrain::new()));
-    static ref PROJECTOR: Arc<Mutex<Projector>> = Arc::new(Mutex::new(Projector::new()));
+    /// The embedded model when it was shipped quantized; similarity queries run on its codes.
+    static ref QUANTIZED_BRAIN: Arc<Mutex<Option<QuantizedBrain>>> = Arc::new(Mutex::new(None));
+    static ref PROJECTOR: Arc<Mutex<Projector>> = Arc::new(Mutex::new(Projector::new(DEFAULT_EMBEDDING_DIM)));
     static ref TURSO_CLIENT: Arc<Mutex<Option<TursoClient>>> = Arc::new(Mutex::new(None));
     static ref NODE_QUEUE: Arc<Mutex<Vec<String>>> = Arc::new(Mutex::new(Vec::new()));
 }
@@ -61,12 +65,21 @@ pub fn main() {
 
     let mut brain = SEMANTIC_BRAIN.lock().unwrap();
     
-    // EMBEDDED BRAIN: This will cause Cloudflare to fail but works perfectly locally
+    // EMBEDDED BRAIN: Export a quantized model (POST /model/export/quantized) to keep this small
+    // enough for Cloudflare; the full-precision format still works locally. A quantized model
+    // stays in QUANTIZED_BRAIN only and SEMANTIC_BRAIN stays blank, so the heap never holds a
+    // decoded copy of the table.
     let model_bytes = include_bytes!("../trained_brain.bin");
     if model_bytes.len() > 0 {
-        match SemanticBrain::from_bytes(model_bytes) {
-            Ok(loaded_brain) => {
-                *brain = loaded_brain;
+        let loaded = if QuantizedBrain::is_quantized(model_bytes) {
+            QuantizedBrain::from_bytes(model_bytes).map(|quantized| {
+                *QUANTIZED_BRAIN.lock().unwrap() = Some(quantized);
+            })
+        } else {
+            SemanticBrain::from_bytes(model_bytes).map(|loaded_brain| *brain = loaded_brain).map_err(|e| e.to_string())
+        };
+        match loaded {
+            Ok(()) => {
                 web_sys::console::log_1(&"Embedded Semantic Brain Loaded!".into());
             }
             Err(e) => {
@@ -76,6 +89,15 @@ pub fn main() {
     }
 }
 
+/// The `top_n` words closest to `word` as a JSON array of `[word, similarity]` pairs. A
+/// quantized embedded model answers from its codes without decoding the table.
+#[wasm_bindgen]
+pub fn find_similar(word: &str, top_n: usize) -> String {
+    let quantized = QUANTIZED_BRAIN.lock().unwrap().as_ref().map(|quantized| quantized.find_most_similar(word, top_n));
+    let hits = quantized.unwrap_or_else(|| SEMANTIC_BRAIN.lock().unwrap().find_most_similar(word, top_n));
+    serde_json::to_string(&hits).unwrap_or_else(|_| "[]".to_string())
+}
+
 #[wasm_bindgen]
 pub fn run_bevy_app() {
     App::new()
@@ -105,13 +127,28 @@ pub fn run_bevy_app() {
         .run();
 }
 
+/// Rows decoded from a quantized model to fit the projection; the principal axes settle
+/// long before the whole vocabulary is seen.
+const PROJECTOR_SAMPLE_ROWS: usize = 2000;
+
 fn fit_projector() {
+    let sample = QUANTIZED_BRAIN.lock().unwrap().as_ref().map(|quantized| quantized.sample_rows(PROJECTOR_SAMPLE_ROWS));
     let brain = SEMANTIC_BRAIN.lock().unwrap();
+    let rows = sample.as_ref().unwrap_or(&brain.embeddings);
     let mut projector = PROJECTOR.lock().unwrap();
-    if !brain.embeddings.is_empty() {
-        projector.fit(&brain.embeddings);
+    if !rows.is_empty() {
+        projector.fit(rows);
         web_sys::console::log_1(&"3D Projection Space calibrated to semantic brain!".into());
     }
 }
 
+/// The vector of `word`, decoding just its row when the embedded model is quantized.
+#[allow(dead_code)]
+fn word_vector(word: &str) -> Option<VectorBuf> {
+    if let Some(quantized) = QUANTIZED_BRAIN.lock().unwrap().as_ref() {
+        return quantized.get_embedding(word);
+    }
+    SEMANTIC_BRAIN.lock().unwrap().get_embedding(word).map(|embedding| embedding.vector.into_owned())
+}
+
 // ... (rest of the file stays same)
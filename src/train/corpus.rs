@@ -0,0 +1,347 @@
+use std::collections::VecDeque;
+use std::fmt;
+use std::fs;
+use std::io::{BufRead, BufReader, Lines};
+use std::path::{Path, PathBuf};
+use crate::train::wiki::DumpSource;
+use serde::{Deserialize, Serialize};
+
+/// Paragraphs of plain text and markdown are cut into documents of at most this many
+/// characters, at a line break.
+const MAX_DOCUMENT_CHARS: usize = 20_000;
+
+/// One unit of training text.
+pub struct Document {
+    /// Position in the corpus, increasing in reading order, so a run can resume after
+    /// the last one trained.
+    pub id: Option<u64>,
+    pub title: String,
+    /// Plain text, with any markup already removed.
+    pub text: String,
+}
+
+/// Yields the documents of a corpus in a fixed order.
+pub trait CorpusSource: Send {
+    /// Next document, or `Ok(None)` at the end of the corpus. Sources that can tell a
+    /// document is already trained without reading it may return it with empty text.
+    fn next_document(&mut self) -> Result<Option<Document>, String>;
+}
+
+/// What `/train/wiki/start` trains on.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CorpusSpec {
+    /// MediaWiki pages-articles XML.
+    Wiki(DumpSource),
+    /// `.txt` files; blank lines separate documents.
+    Text(PathBuf),
+    /// `.jsonl` files with one document per line, its text under `text_field`.
+    Jsonl { path: PathBuf, text_field: String },
+    /// `.md` and `.markdown` files; code blocks and link targets are dropped.
+    Markdown(PathBuf),
+}
+
+impl CorpusSpec {
+    /// `format` is `wiki`, `text`, `jsonl` or `markdown`. `path` is a file or a
+    /// directory, read recursively; wiki dumps may also be URLs.
+    pub fn parse(format: &str, path: &str, text_field: Option<String>) -> Result<Self, String> {
+        if format == "wiki" {
+            return DumpSource::parse(path).map(CorpusSpec::Wiki);
+        }
+        let path = PathBuf::from(path);
+        if !path.exists() {
+            return Err(format!("Corpus path {} does not exist", path.display()));
+        }
+        match format {
+            "text" => Ok(CorpusSpec::Text(path)),
+            "jsonl" => Ok(CorpusSpec::Jsonl { path, text_field: text_field.unwrap_or_else(|| "text".to_string()) }),
+            "markdown" => Ok(CorpusSpec::Markdown(path)),
+            other => Err(format!("Unknown corpus format '{}', expected wiki, text, jsonl or markdown", other)),
+        }
+    }
+
+    /// Identifies the document numbering a resume point refers to. Every copy of the
+    /// wiki dump shares page ids; other corpora number their own documents.
+    pub fn resume_key(&self) -> String {
+        match self {
+            CorpusSpec::Wiki(_) => String::new(),
+            other => other.to_string(),
+        }
+    }
+
+    /// Opens a file-based corpus. Wiki dumps need the download and resume handling in
+    /// `WikipediaTrainer` and are opened there.
+    pub fn open_files(&self) -> Result<Box<dyn CorpusSource>, String> {
+        let (path, format, extensions): (&Path, FileFormat, &[&str]) = match self {
+            CorpusSpec::Wiki(_) => return Err("Wiki dumps are not a file corpus".to_string()),
+            CorpusSpec::Text(path) => (path, FileFormat::Text, &["txt"]),
+            CorpusSpec::Jsonl { path, text_field } => (path, FileFormat::Jsonl(text_field.clone()), &["jsonl"]),
+            CorpusSpec::Markdown(path) => (path, FileFormat::Markdown, &["md", "markdown"]),
+        };
+        let files = if path.is_dir() {
+            let mut files = Vec::new();
+            collect_files(path, extensions, &mut files)?;
+            files.sort();
+            if files.is_empty() {
+                return Err(format!("No .{} files in {}", extensions.join(" or ."), path.display()));
+            }
+            files
+        } else {
+            vec![path.to_path_buf()]
+        };
+        Ok(Box::new(FileCorpus { files: files.into(), format, current: None, next_id: 1 }))
+    }
+}
+
+impl fmt::Display for CorpusSpec {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CorpusSpec::Wiki(dump) => write!(f, "{}", dump),
+            CorpusSpec::Text(path) => write!(f, "text:{}", path.display()),
+            CorpusSpec::Jsonl { path, text_field } => write!(f, "jsonl:{} ({})", path.display(), text_field),
+            CorpusSpec::Markdown(path) => write!(f, "markdown:{}", path.display()),
+        }
+    }
+}
+
+fn collect_files(dir: &Path, extensions: &[&str], files: &mut Vec<PathBuf>) -> Result<(), String> {
+    let entries = fs::read_dir(dir).map_err(|e| format!("Cannot read {}: {}", dir.display(), e))?;
+    for entry in entries.flatten() {
+        let path = entry.path();
+        if path.is_dir() {
+            collect_files(&path, extensions, files)?;
+        } else if path.extension().is_some_and(|ext| extensions.iter().any(|e| ext.eq_ignore_ascii_case(e))) {
+            files.push(path);
+        }
+    }
+    Ok(())
+}
+
+#[derive(Clone)]
+enum FileFormat {
+    Text,
+    Jsonl(String),
+    Markdown,
+}
+
+/// The file being read and where in it the reader is.
+enum OpenFile {
+    /// Read line by line; `line` is the number of the last line read.
+    Lines { path: PathBuf, lines: Lines<BufReader<fs::File>>, line: usize },
+    /// Markdown is cleaned as a whole and then handed out paragraph by paragraph.
+    Paragraphs { path: PathBuf, paragraphs: VecDeque<String> },
+}
+
+/// Text, JSONL and markdown files, read one after another. Documents are numbered from
+/// one across all files, so the numbering is stable as long as the files are.
+struct FileCorpus {
+    files: VecDeque<PathBuf>,
+    format: FileFormat,
+    current: Option<OpenFile>,
+    next_id: u64,
+}
+
+impl FileCorpus {
+    fn open_next(&mut self) -> Result<bool, String> {
+        let Some(path) = self.files.pop_front() else {
+            return Ok(false);
+        };
+        println!("> Reading {}", path.display());
+        self.current = Some(match self.format {
+            FileFormat::Markdown => {
+                let text = fs::read_to_string(&path).map_err(|e| format!("Cannot read {}: {}", path.display(), e))?;
+                OpenFile::Paragraphs { path, paragraphs: paragraphs(&markdown_to_plain(&text)).into() }
+            }
+            _ => {
+                let file = fs::File::open(&path).map_err(|e| format!("Cannot open {}: {}", path.display(), e))?;
+                OpenFile::Lines { path, lines: BufReader::new(file).lines(), line: 0 }
+            }
+        });
+        Ok(true)
+    }
+
+    fn document(&mut self, title: String, text: String) -> Document {
+        let id = self.next_id;
+        self.next_id += 1;
+        Document { id: Some(id), title, text }
+    }
+}
+
+impl CorpusSource for FileCorpus {
+    fn next_document(&mut self) -> Result<Option<Document>, String> {
+        loop {
+            let Some(current) = &mut self.current else {
+                if !self.open_next()? {
+                    return Ok(None);
+                }
+                continue;
+            };
+
+            let found = match current {
+                OpenFile::Paragraphs { path, paragraphs } => {
+                    paragraphs.pop_front().map(|text| (path.display().to_string(), text))
+                }
+                OpenFile::Lines { path, lines, line } => match &self.format {
+                    FileFormat::Jsonl(field) => next_json_text(path, lines, line, field)?,
+                    _ => next_paragraph(path, lines, line)?,
+                },
+            };
+            match found {
+                Some((title, text)) => return Ok(Some(self.document(title, text))),
+                None => self.current = None,
+            }
+        }
+    }
+}
+
+/// Next line of a JSONL file with a string under `field`, titled `file:line`. Lines that
+/// aren't JSON objects or lack the field are skipped with a warning.
+fn next_json_text(path: &Path, lines: &mut Lines<BufReader<fs::File>>, line: &mut usize, field: &str) -> Result<Option<(String, String)>, String> {
+    for text in lines {
+        let text = text.map_err(|e| format!("Cannot read {}: {}", path.display(), e))?;
+        *line += 1;
+        if text.trim().is_empty() {
+            continue;
+        }
+        let title = format!("{}:{}", path.display(), line);
+        match serde_json::from_str::<serde_json::Value>(&text) {
+            Ok(value) => match value.get(field).and_then(|v| v.as_str()) {
+                Some(body) => return Ok(Some((title, body.to_string()))),
+                None => println!("> Skipping {}: no string field '{}'", title, field),
+            },
+            Err(e) => println!("> Skipping {}: {}", title, e),
+        }
+    }
+    Ok(None)
+}
+
+/// Next run of non-blank lines, titled with the file and its first line.
+fn next_paragraph(path: &Path, lines: &mut Lines<BufReader<fs::File>>, line: &mut usize) -> Result<Option<(String, String)>, String> {
+    let mut text = String::new();
+    let mut first_line = 0;
+    for next in lines {
+        let next = next.map_err(|e| format!("Cannot read {}: {}", path.display(), e))?;
+        *line += 1;
+        if next.trim().is_empty() {
+            if text.is_empty() {
+                continue;
+            }
+            break;
+        }
+        if text.is_empty() {
+            first_line = *line;
+        }
+        text.push_str(&next);
+        text.push('\n');
+        if text.len() >= MAX_DOCUMENT_CHARS {
+            break;
+        }
+    }
+    if text.is_empty() {
+        return Ok(None);
+    }
+    Ok(Some((format!("{}:{}", path.display(), first_line), text)))
+}
+
+/// Splits text at blank lines, cutting paragraphs longer than `MAX_DOCUMENT_CHARS`.
+fn paragraphs(text: &str) -> Vec<String> {
+    let mut out = Vec::new();
+    let mut current = String::new();
+    for line in text.lines() {
+        if line.trim().is_empty() || current.len() >= MAX_DOCUMENT_CHARS {
+            if !current.trim().is_empty() {
+                out.push(std::mem::take(&mut current));
+            }
+            current.clear();
+        }
+        if !line.trim().is_empty() {
+            current.push_str(line);
+            current.push('\n');
+        }
+    }
+    if !current.trim().is_empty() {
+        out.push(current);
+    }
+    out
+}
+
+/// Drops what a reader never sees as prose: YAML front matter, fenced code blocks,
+/// HTML comments, link and image targets and bare autolinks. Heading, list and emphasis
+/// markers are punctuation the tokenizer discards anyway.
+pub fn markdown_to_plain(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut lines = text.lines().peekable();
+
+    if lines.peek().is_some_and(|first| first.trim_end() == "---") {
+        lines.next();
+        for line in lines.by_ref() {
+            if matches!(line.trim_end(), "---" | "...") {
+                break;
+            }
+        }
+    }
+
+    let mut fence: Option<&str> = None;
+    let mut in_comment = false;
+    for line in lines {
+        let trimmed = line.trim_start();
+        if let Some(marker) = fence {
+            if trimmed.starts_with(marker) {
+                fence = None;
+            }
+            continue;
+        }
+        if trimmed.starts_with("```") || trimmed.starts_with("~~~") {
+            fence = Some(&trimmed[..3]);
+            continue;
+        }
+        strip_inline(line, &mut in_comment, &mut out);
+        out.push('\n');
+    }
+    out
+}
+
+fn strip_inline(line: &str, in_comment: &mut bool, out: &mut String) {
+    let mut rest = line;
+    while !rest.is_empty() {
+        if *in_comment {
+            match rest.find("-->") {
+                Some(end) => {
+                    rest = &rest[end + 3..];
+                    *in_comment = false;
+                }
+                None => return,
+            }
+        } else if let Some(after) = rest.strip_prefix("<!--") {
+            *in_comment = true;
+            rest = after;
+        } else if let Some(after) = rest.strip_prefix("](") {
+            // `[label](target)`: the label is already out, skip the target.
+            rest = after.find(')').map_or("", |end| &after[end + 1..]);
+        } else if rest.starts_with("<http://") || rest.starts_with("<https://") {
+            rest = rest.find('>').map_or("", |end| &rest[end + 1..]);
+        } else if let Some(after) = rest.strip_prefix(['[', ']']) {
+            rest = after;
+        } else {
+            let c = rest.chars().next().expect("rest is not empty");
+            out.push(c);
+            rest = &rest[c.len_utf8()..];
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn markdown_keeps_prose_only() {
+        let text = markdown_to_plain("---\ntitle: Notes\n---\n# Heading\n\nSee [the docs](https://example.com/docs) or <https://example.com>.\n<!-- draft\nnote -->\n```rust\nfn main() {}\n```\nDone.\n");
+        assert_eq!(text, "# Heading\n\nSee the docs or .\n\n\nDone.\n");
+    }
+
+    #[test]
+    fn paragraphs_split_at_blank_lines() {
+        assert_eq!(paragraphs("one\ntwo\n\n\nthree\n"), vec!["one\ntwo\n", "three\n"]);
+    }
+}
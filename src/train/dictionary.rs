@@ -0,0 +1,140 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use parking_lot::RwLock;
+use crate::brain::model::SemanticBrain;
+use crate::brain::sampling::Rng;
+use crate::train::snapshot::BrainSnapshot;
+use crate::train::wiki::{TrainerState, WikipediaTrainer};
+use serde::{Deserialize, Serialize};
+
+/// Settings for one `/train/dictionary` run.
+#[derive(Clone, Debug)]
+pub struct DictionaryConfig {
+    /// A dictionary JSON file (`{"WORD": {"MEANINGS": …, "SYNONYMS": […], "ANTONYMS": […]}}`)
+    /// or a directory of them, such as one file per letter.
+    pub path: PathBuf,
+    pub epochs: usize,
+    /// Starting rate, decayed linearly to a tenth of it over the run. Lower than for
+    /// running text so a few epochs refine a trained brain instead of overwriting it.
+    pub learning_rate: f32,
+    pub negative_samples: usize,
+    pub seed: u64,
+    /// Dropped from definition contexts.
+    pub stop_words: Vec<String>,
+    /// Headwords whose vectors stay fixed; see `SemanticBrain::freeze`.
+    pub frozen_words: Vec<String>,
+}
+
+/// Shown by `/train/wiki/status` while a dictionary run is going and after it ends.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct DictionaryProgress {
+    pub path: String,
+    pub epoch: usize,
+    pub epochs: usize,
+    pub entries: usize,
+    /// Entries trained in the current epoch.
+    pub entries_done: usize,
+    pub learning_rate: f32,
+}
+
+/// A headword with what it should be pulled towards (synonyms and definition words) and
+/// pushed away from (antonyms).
+struct Entry {
+    word: String,
+    attract: Vec<String>,
+    repel: Vec<String>,
+}
+
+/// Reads every dictionary file under `path` through `extract_context`. Entries are sorted
+/// so a seeded run visits them in the same order however the files list their words.
+fn load_entries(brain: &SemanticBrain, path: &Path, stop_words: &[String]) -> Result<Vec<Entry>, String> {
+    let files = if path.is_dir() {
+        let entries = fs::read_dir(path).map_err(|e| format!("Cannot read {}: {}", path.display(), e))?;
+        let mut files: Vec<PathBuf> = entries
+            .filter_map(|entry| entry.ok().map(|e| e.path()))
+            .filter(|p| p.is_file() && p.extension().is_some_and(|ext| ext.eq_ignore_ascii_case("json")))
+            .collect();
+        files.sort();
+        if files.is_empty() {
+            return Err(format!("No .json dictionary files in {}", path.display()));
+        }
+        files
+    } else {
+        vec![path.to_path_buf()]
+    };
+
+    let mut entries = Vec::new();
+    for file in files {
+        let json = fs::read_to_string(&file).map_err(|e| format!("Cannot read {}: {}", file.display(), e))?;
+        let context = brain.extract_context(&json, stop_words).map_err(|e| format!("{}: {}", file.display(), e))?;
+        entries.extend(context.into_iter().map(|(word, attract, repel)| Entry { word, attract, repel }));
+    }
+    entries.sort_by(|a, b| a.word.cmp(&b.word));
+    entries.retain(|entry| !entry.word.is_empty() && (!entry.attract.is_empty() || !entry.repel.is_empty()));
+    Ok(entries)
+}
+
+/// Runs `config.epochs` passes of synonym attraction and antonym repulsion over the
+/// dictionary, checkpointing after every epoch. Each entry takes the brain's write lock
+/// on its own, so a stop request lands within one entry; the entries trained so far are
+/// then saved like a finished epoch.
+pub fn run(
+    brain: &Arc<RwLock<SemanticBrain>>,
+    snapshot: &BrainSnapshot,
+    state: &Arc<RwLock<TrainerState>>,
+    config: &DictionaryConfig,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    println!("> Loading dictionary from {}...", config.path.display());
+    let mut entries = load_entries(&brain.read(), &config.path, &config.stop_words)?;
+    println!("> {} dictionary entries, {} epochs.", entries.len(), config.epochs);
+    if let Some(dictionary) = &mut state.write().dictionary {
+        dictionary.entries = entries.len();
+    }
+
+    {
+        let mut b = brain.write();
+        b.seed_sampler(config.seed);
+        b.freeze(&config.frozen_words);
+    }
+    let mut rng = Rng::new(config.seed);
+    let total_steps = (entries.len() * config.epochs).max(1);
+
+    let mut stopped = false;
+    'epochs: for epoch in 0..config.epochs {
+        // Fisher-Yates; in a fixed order the same entries would always train last.
+        for i in (1..entries.len()).rev() {
+            entries.swap(i, rng.below(i + 1));
+        }
+
+        for (done, entry) in entries.iter().enumerate() {
+            if !state.read().running {
+                println!("> Dictionary training stop requested.");
+                stopped = true;
+                break 'epochs;
+            }
+            let progress = (epoch * entries.len() + done) as f32 / total_steps as f32;
+            let learning_rate = config.learning_rate * (1.0 - 0.9 * progress);
+
+            let mut b = brain.write();
+            b.train_step(&entry.word, &entry.attract, &entry.repel, learning_rate, config.negative_samples);
+            let mut s = state.write();
+            if let Some(dictionary) = &mut s.dictionary {
+                dictionary.epoch = epoch + 1;
+                dictionary.entries_done = done + 1;
+                dictionary.learning_rate = learning_rate;
+            }
+        }
+
+        println!("> Dictionary epoch {}/{} done.", epoch + 1, config.epochs);
+        let saved = WikipediaTrainer::save_checkpoint_sync(brain, state, None)?;
+        snapshot.replace(saved);
+    }
+    if stopped {
+        let saved = WikipediaTrainer::save_checkpoint_sync(brain, state, None)?;
+        snapshot.replace(saved);
+    } else {
+        println!("> Dictionary training finished.");
+    }
+    Ok(())
+}
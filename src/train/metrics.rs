@@ -0,0 +1,160 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::io::Write;
+use std::path::Path;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use crate::brain::eval::Benchmarks;
+use crate::brain::model::SemanticBrain;
+use serde::{Deserialize, Serialize};
+
+pub const METRICS_PATH: &str = "data/metrics_history.jsonl";
+/// Records `/train/wiki/status` returns; the file keeps all of them.
+const STATUS_HISTORY: usize = 500;
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CheckpointEvalConfig {
+    /// Evaluate at every Nth checkpoint of a wiki run. Zero turns evaluation off.
+    pub every_checkpoints: usize,
+    /// Most frequent words searched for analogy answers. Lower than for `/eval/run` so
+    /// a checkpoint doesn't stall the workers for long.
+    pub candidates: usize,
+    /// Questions kept from each analogy section.
+    pub questions_per_section: usize,
+    /// Words whose nearest neighbours are logged. Read from `data/probe_words.txt` (one
+    /// word per line) when present.
+    pub probe_words: Vec<String>,
+    pub probe_neighbours: usize,
+}
+
+const DEFAULT_PROBE_WORDS: &[&str] = &["KING", "PARIS", "COMPUTER", "MUSIC", "WATER", "THREE"];
+
+impl Default for CheckpointEvalConfig {
+    fn default() -> Self {
+        Self {
+            every_checkpoints: 10,
+            candidates: 10_000,
+            questions_per_section: 50,
+            probe_words: DEFAULT_PROBE_WORDS.iter().map(|w| w.to_string()).collect(),
+            probe_neighbours: 5,
+        }
+    }
+}
+
+pub fn load_probe_words() -> Vec<String> {
+    match fs::read_to_string("data/probe_words.txt") {
+        Ok(text) => text
+            .lines()
+            .map(|l| l.trim().to_string())
+            .filter(|l| !l.is_empty() && !l.starts_with('#'))
+            .collect(),
+        Err(_) => DEFAULT_PROBE_WORDS.iter().map(|w| w.to_string()).collect(),
+    }
+}
+
+/// What a run evaluates at its checkpoints, loaded once when the run starts.
+pub struct CheckpointEval {
+    pub config: CheckpointEvalConfig,
+    pub benchmarks: Benchmarks,
+    checkpoints: AtomicUsize,
+}
+
+impl CheckpointEval {
+    pub fn new(config: CheckpointEvalConfig, mut benchmarks: Benchmarks) -> Self {
+        benchmarks.candidates = config.candidates;
+        if let Some(analogies) = &mut benchmarks.analogies {
+            for section in &mut analogies.sections {
+                section.questions.truncate(config.questions_per_section);
+            }
+        }
+        Self { config, benchmarks, checkpoints: AtomicUsize::new(0) }
+    }
+
+    /// Counts a checkpoint and says whether it should be evaluated.
+    pub fn next_is_due(&self) -> bool {
+        let checkpoint = self.checkpoints.fetch_add(1, Ordering::Relaxed) + 1;
+        self.config.every_checkpoints > 0 && checkpoint.is_multiple_of(self.config.every_checkpoints)
+    }
+
+    pub fn evaluate(&self, brain: &SemanticBrain, articles_processed: usize, tokens_processed: u64, saved_at_unix: u64) -> MetricsRecord {
+        let mut non_finite = 0;
+        let mut norm_sum = 0.0f64;
+        for v in brain.embeddings.iter() {
+            if v.is_finite() {
+                norm_sum += v.length() as f64;
+            } else {
+                non_finite += 1;
+            }
+        }
+        let finite = brain.embeddings.len() - non_finite;
+
+        let scores = self.benchmarks.score(brain);
+        // Exact search: a similarity index built before the run doesn't know the words
+        // added since.
+        let probes = self
+            .config
+            .probe_words
+            .iter()
+            .filter(|word| brain.contains_word(word))
+            .map(|word| (brain.key(word), brain.find_most_similar_exact(word, self.config.probe_neighbours)))
+            .collect();
+
+        MetricsRecord {
+            saved_at_unix,
+            articles_processed,
+            tokens_processed,
+            vocab_size: brain.vocabulary.len(),
+            non_finite,
+            mean_norm: if finite > 0 { (norm_sum / finite as f64) as f32 } else { 0.0 },
+            analogy_accuracy: scores.analogy.map(|report| report.total.accuracy),
+            spearman: scores.similarity.into_iter().filter_map(|s| Some((s.name, s.spearman?))).collect(),
+            probes,
+        }
+    }
+}
+
+/// One line of `data/metrics_history.jsonl`.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct MetricsRecord {
+    pub saved_at_unix: u64,
+    pub articles_processed: usize,
+    pub tokens_processed: u64,
+    pub vocab_size: usize,
+    /// Word vectors holding NaN or infinity. Anything above zero means training has
+    /// started to diverge.
+    pub non_finite: usize,
+    /// Mean length of the finite word vectors; drifting far from 1 is another early sign.
+    pub mean_norm: f32,
+    pub analogy_accuracy: Option<f32>,
+    /// Spearman correlation per similarity set.
+    pub spearman: BTreeMap<String, f32>,
+    /// Nearest neighbours of each probe word in the vocabulary.
+    pub probes: BTreeMap<String, Vec<(String, f32)>>,
+}
+
+pub fn append(record: &MetricsRecord) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    if !Path::new("data").exists() {
+        fs::create_dir_all("data")?;
+    }
+    let mut file = fs::OpenOptions::new().create(true).append(true).open(METRICS_PATH)?;
+    writeln!(file, "{}", serde_json::to_string(record)?)?;
+    Ok(())
+}
+
+/// The most recent records of the history file, skipping lines that don't parse.
+pub fn load_recent() -> Vec<MetricsRecord> {
+    let Ok(text) = fs::read_to_string(METRICS_PATH) else {
+        return Vec::new();
+    };
+    let mut records: Vec<MetricsRecord> = text.lines().filter_map(|line| serde_json::from_str(line).ok()).collect();
+    let excess = records.len().saturating_sub(STATUS_HISTORY);
+    records.drain(..excess);
+    records
+}
+
+/// Adds `record` to the series kept for `/train/wiki/status`.
+pub fn push_recent(series: &mut Vec<MetricsRecord>, record: MetricsRecord) {
+    series.push(record);
+    let excess = series.len().saturating_sub(STATUS_HISTORY);
+    series.drain(..excess);
+}
@@ -1 +1,8 @@
+pub mod corpus;
+pub mod dictionary;
+pub mod metrics;
+pub mod phrases;
+pub mod resume;
+pub mod snapshot;
 pub mod wiki;
+pub mod wikitext;
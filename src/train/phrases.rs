@@ -0,0 +1,159 @@
+use std::collections::{HashMap, HashSet};
+use serde::{Deserialize, Serialize};
+use crate::brain::tokenizer::PHRASE_SEPARATOR;
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct PhraseConfig {
+    /// Articles read from the start of the dump to learn phrases from. Zero disables
+    /// phrase detection.
+    pub sample_articles: usize,
+    /// Bigrams, and words in them, seen fewer times than this are never joined.
+    pub min_count: u64,
+    /// word2phrase score a bigram needs to become a phrase in the first pass. Each
+    /// later pass halves it.
+    pub threshold: f64,
+    /// Each pass can join one more word onto an existing phrase, so two passes give
+    /// trigrams such as `NEW_YORK_CITY`.
+    pub passes: usize,
+}
+
+impl Default for PhraseConfig {
+    fn default() -> Self {
+        Self {
+            sample_articles: 10_000,
+            min_count: 5,
+            threshold: 100.0,
+            passes: 2,
+        }
+    }
+}
+
+/// Tokenized sample articles, interned so a large sample fits in memory.
+#[derive(Default)]
+pub struct PhraseSample {
+    ids: HashMap<String, u32>,
+    words: Vec<String>,
+    articles: Vec<Vec<u32>>,
+}
+
+impl PhraseSample {
+    pub fn add(&mut self, tokens: &[String]) {
+        let article = tokens.iter().map(|t| self.intern(t)).collect();
+        self.articles.push(article);
+    }
+
+    pub fn len(&self) -> usize {
+        self.articles.len()
+    }
+
+    #[allow(dead_code)]
+    pub fn is_empty(&self) -> bool {
+        self.articles.is_empty()
+    }
+
+    fn intern(&mut self, word: &str) -> u32 {
+        if let Some(&id) = self.ids.get(word) {
+            return id;
+        }
+        let id = self.words.len() as u32;
+        self.ids.insert(word.to_string(), id);
+        self.words.push(word.to_string());
+        id
+    }
+}
+
+/// Learned collocations, applied pass by pass to every article before training.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct PhraseModel {
+    /// For each pass, first word to the second words it joins with.
+    passes: Vec<HashMap<String, HashSet<String>>>,
+}
+
+impl PhraseModel {
+    /// word2phrase (Mikolov et al., 2013): a bigram `ab` is joined when
+    /// `(count(ab) - min_count) / (count(a) * count(b)) * total` exceeds the threshold.
+    pub fn learn(mut sample: PhraseSample, config: &PhraseConfig) -> Self {
+        let mut model = Self::default();
+        let mut threshold = config.threshold;
+
+        for _ in 0..config.passes {
+            let mut unigrams = vec![0u64; sample.words.len()];
+            let mut bigrams: HashMap<(u32, u32), u64> = HashMap::new();
+            let mut total = 0u64;
+            for article in &sample.articles {
+                for (i, &id) in article.iter().enumerate() {
+                    unigrams[id as usize] += 1;
+                    total += 1;
+                    if let Some(&next) = article.get(i + 1) {
+                        *bigrams.entry((id, next)).or_insert(0) += 1;
+                    }
+                }
+            }
+
+            let mut joins: HashMap<String, HashSet<String>> = HashMap::new();
+            let mut joined_ids: HashMap<(u32, u32), u32> = HashMap::new();
+            for (&(a, b), &count) in &bigrams {
+                let (count_a, count_b) = (unigrams[a as usize], unigrams[b as usize]);
+                if count < config.min_count || count_a < config.min_count || count_b < config.min_count {
+                    continue;
+                }
+                let score = (count - config.min_count) as f64 / count_a as f64 / count_b as f64 * total as f64;
+                if score > threshold {
+                    let (word_a, word_b) = (sample.words[a as usize].clone(), sample.words[b as usize].clone());
+                    joined_ids.insert((a, b), sample.intern(&format!("{}{}{}", word_a, PHRASE_SEPARATOR, word_b)));
+                    joins.entry(word_a).or_default().insert(word_b);
+                }
+            }
+            if joins.is_empty() {
+                break;
+            }
+
+            for article in &mut sample.articles {
+                *article = merge(article, |&a, &b| joined_ids.get(&(a, b)).copied());
+            }
+            model.passes.push(joins);
+            threshold /= 2.0;
+        }
+        model
+    }
+
+    pub fn len(&self) -> usize {
+        self.passes.iter().flat_map(|pass| pass.values()).map(HashSet::len).sum()
+    }
+
+    #[allow(dead_code)]
+    pub fn is_empty(&self) -> bool {
+        self.passes.iter().flat_map(|pass| pass.values()).all(HashSet::is_empty)
+    }
+
+    /// Joins learned phrases in a tokenized article.
+    pub fn apply(&self, tokens: Vec<String>) -> Vec<String> {
+        self.passes.iter().fold(tokens, |tokens, joins| {
+            merge(&tokens, |a, b| {
+                joins
+                    .get(a)
+                    .is_some_and(|seconds| seconds.contains(b))
+                    .then(|| format!("{}{}{}", a, PHRASE_SEPARATOR, b))
+            })
+        })
+    }
+}
+
+/// Greedy left-to-right merge of adjacent pairs that `join` accepts.
+fn merge<T: Clone>(tokens: &[T], join: impl Fn(&T, &T) -> Option<T>) -> Vec<T> {
+    let mut out = Vec::with_capacity(tokens.len());
+    let mut i = 0;
+    while i < tokens.len() {
+        match tokens.get(i + 1).and_then(|next| join(&tokens[i], next)) {
+            Some(joined) => {
+                out.push(joined);
+                i += 2;
+            }
+            None => {
+                out.push(tokens[i].clone());
+                i += 1;
+            }
+        }
+    }
+    out
+}
@@ -0,0 +1,85 @@
+use std::collections::BTreeMap;
+use std::io::BufRead;
+use crate::brain::format::ResumePoint;
+
+/// Tracks articles between the parser and the workers so the saved position only covers
+/// pages whose updates are actually in the brain.
+pub struct ProgressTracker {
+    done: ResumePoint,
+    in_flight: BTreeMap<u64, (Option<u64>, bool)>,
+    next_seq: u64,
+}
+
+impl ProgressTracker {
+    pub fn new(start: ResumePoint) -> Self {
+        Self {
+            done: start,
+            in_flight: BTreeMap::new(),
+            next_seq: 0,
+        }
+    }
+
+    /// Registers an article handed to the workers, in dump order.
+    pub fn queue(&mut self, page_id: Option<u64>) -> u64 {
+        let seq = self.next_seq;
+        self.next_seq += 1;
+        self.in_flight.insert(seq, (page_id, false));
+        seq
+    }
+
+    /// Marks an article as trained and moves the watermark past every article queued
+    /// before it that has also finished.
+    pub fn finish(&mut self, seq: u64) {
+        if let Some(entry) = self.in_flight.get_mut(&seq) {
+            entry.1 = true;
+        }
+        while let Some(entry) = self.in_flight.first_entry() {
+            if !entry.get().1 {
+                break;
+            }
+            if let Some(page_id) = entry.remove().0 {
+                self.done.page_id = Some(page_id);
+            }
+        }
+        if let Some(last) = self.done.page_id {
+            self.done.done_ahead.retain(|&id| id > last);
+        }
+    }
+
+    pub fn point(&self) -> ResumePoint {
+        let mut point = self.done.clone();
+        point.done_ahead.extend(
+            self.in_flight.values().filter(|(_, finished)| *finished).filter_map(|(id, _)| *id),
+        );
+        point
+    }
+}
+
+/// Byte offset of the bz2 stream holding `page_id` in a multistream dump, read from its
+/// `offset:page_id:title` index. `None` when the page is before the first entry.
+pub fn stream_offset<R: BufRead>(index: R, page_id: u64) -> std::io::Result<Option<u64>> {
+    let mut best = None;
+    for line in index.lines() {
+        let line = line?;
+        let mut fields = line.splitn(3, ':');
+        let (Some(offset), Some(id)) = (fields.next(), fields.next()) else {
+            continue;
+        };
+        let (Ok(offset), Ok(id)) = (offset.parse::<u64>(), id.parse::<u64>()) else {
+            continue;
+        };
+        if id > page_id {
+            break;
+        }
+        best = Some(offset);
+    }
+    Ok(best)
+}
+
+/// Last page id of a shard named like `enwiki-...-pages-articles3.xml-p151574p311329.bz2`.
+pub fn shard_last_page(file_name: &str) -> Option<u64> {
+    let range = &file_name[file_name.rfind("-p")? + 2..];
+    let end = &range[range.find('p')? + 1..];
+    let digits: String = end.chars().take_while(|c| c.is_ascii_digit()).collect();
+    digits.parse().ok()
+}
@@ -0,0 +1,34 @@
+use std::sync::Arc;
+use parking_lot::RwLock;
+use crate::brain::model::SemanticBrain;
+
+/// Read-only copy of the brain served to `/predict/*`. Handlers clone the `Arc` and drop
+/// the lock straight away, so a query never waits for training to release the live brain.
+pub struct BrainSnapshot {
+    current: RwLock<Arc<SemanticBrain>>,
+}
+
+impl BrainSnapshot {
+    pub fn new(brain: &SemanticBrain) -> Self {
+        Self {
+            current: RwLock::new(Arc::new(brain.clone())),
+        }
+    }
+
+    pub fn get(&self) -> Arc<SemanticBrain> {
+        self.current.read().clone()
+    }
+
+    /// Copies `live` and swaps it in. Readers holding the previous snapshot keep it
+    /// until they finish.
+    pub fn publish(&self, live: &RwLock<SemanticBrain>) {
+        let copy = Arc::new(live.read().clone());
+        *self.current.write() = copy;
+    }
+
+    /// Swaps in a brain that already matches the live one, such as the copy a
+    /// checkpoint decodes to verify what it wrote, without copying it again.
+    pub fn replace(&self, brain: Arc<SemanticBrain>) {
+        *self.current.write() = brain;
+    }
+}
@@ -1,24 +1,183 @@
+use std::collections::VecDeque;
+use std::fmt;
 use std::sync::Arc;
-use parking_lot::RwLock;
-use crate::brain::model::SemanticBrain;
+use std::sync::mpsc::{Receiver, SyncSender};
+use parking_lot::{Mutex, RwLock, RwLockWriteGuard};
+use crate::brain::eval::Benchmarks;
+use crate::brain::format::{self, ResumePoint};
+use crate::brain::model::{Loss, Objective, SemanticBrain, WindowParams, DEFAULT_STOP_WORDS};
+use crate::brain::sampling::Rng;
+use crate::brain::tokenizer::Tokenizer;
+use crate::train::corpus::{CorpusSource, CorpusSpec, Document};
+use crate::train::dictionary::{self, DictionaryConfig, DictionaryProgress};
+use crate::train::metrics::{self, CheckpointEval, CheckpointEvalConfig, MetricsRecord};
+use crate::train::phrases::{PhraseConfig, PhraseModel, PhraseSample};
+use crate::train::resume::{self, ProgressTracker};
+use crate::train::snapshot::BrainSnapshot;
+use crate::train::wikitext;
 use serde::{Serialize, Deserialize};
 use std::fs;
-use std::path::Path;
-use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::io::{BufReader, Read, Seek, SeekFrom};
+use std::time::{SystemTime, UNIX_EPOCH};
 use bzip2::read::MultiBzDecoder;
 use quick_xml::reader::Reader;
 use quick_xml::events::Event;
-use regex::Regex;
 use futures_util::StreamExt;
+use tokio::sync::OwnedMutexGuard;
 
 #[derive(Clone, Serialize, Deserialize)]
 pub struct TrainerConfig {
-    pub dump_url: String,
+    pub source: CorpusSpec,
+    /// `offset:page_id:title` index of a multistream wiki dump (URL or path, `.bz2` or plain).
+    /// Lets a resumed run seek to the block holding its last page instead of re-reading
+    /// everything before it.
+    pub multistream_index: Option<String>,
+    /// Skip-gram or CBOW. Recorded in the model metadata, and a saved model keeps
+    /// training with the objective it was last trained with.
+    pub objective: Objective,
+    /// Negative sampling or hierarchical softmax; kept across runs the same way.
+    pub loss: Loss,
     pub learning_rate: f32,
     pub window_size: usize,
     pub negative_samples: usize,
     pub max_articles: Option<usize>,
     pub checkpoint_every_articles: usize,
+    /// Seed for negative sampling, so a run over the same dump is reproducible.
+    pub seed: u64,
+    /// Subsampling threshold `t`: words more frequent than this are randomly dropped
+    /// with probability `1 - sqrt(t / f)`. Zero disables subsampling.
+    pub subsample_threshold: f32,
+    /// Words dropped from dictionary definition contexts. Read from `data/stop_words.txt`
+    /// (one word per line) when present.
+    pub stop_words: Vec<String>,
+    /// Words whose target vectors no run may change, such as pretrained anchors. Read
+    /// from `data/frozen_words.txt` (one word per line) when present.
+    pub frozen_words: Vec<String>,
+    /// Threads applying training updates in parallel. With one worker a seeded run is
+    /// fully reproducible; with more, updates interleave Hogwild-style.
+    pub workers: usize,
+    /// Collocation detection, so "NEW YORK" trains as the single token `NEW_YORK`.
+    pub phrases: PhraseConfig,
+    /// Benchmark scores and probe-word neighbours logged to `data/metrics_history.jsonl`
+    /// at every Nth checkpoint.
+    pub eval: CheckpointEvalConfig,
+}
+
+const PHRASES_PATH: &str = "data/phrases.json";
+
+/// Where a training run reads its pages-articles XML from. Names ending in `.bz2` go
+/// through `MultiBzDecoder`; anything else is read as plain XML.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DumpSource {
+    /// Streamed over HTTP without touching the disk.
+    Url(String),
+    /// A single dump file already on disk.
+    File(PathBuf),
+    /// Every `.bz2` and `.xml` shard in the directory, read one after another in page
+    /// order.
+    Directory(PathBuf),
+}
+
+impl DumpSource {
+    /// `http://` and `https://` strings are URLs; anything else must be an existing
+    /// file or directory.
+    pub fn parse(source: &str) -> Result<Self, String> {
+        if source.starts_with("http://") || source.starts_with("https://") {
+            return Ok(DumpSource::Url(source.to_string()));
+        }
+        let path = PathBuf::from(source);
+        if path.is_dir() {
+            Ok(DumpSource::Directory(path))
+        } else if path.is_file() {
+            Ok(DumpSource::File(path))
+        } else {
+            Err(format!("Dump source {} is neither a URL nor an existing file or directory", source))
+        }
+    }
+
+    fn is_compressed(name: &str) -> bool {
+        name.ends_with(".bz2")
+    }
+
+    /// Files to read for a local source, in order. Empty for URLs.
+    fn local_files(&self) -> Result<Vec<PathBuf>, String> {
+        match self {
+            DumpSource::Url(_) => Ok(Vec::new()),
+            DumpSource::File(path) => Ok(vec![path.clone()]),
+            DumpSource::Directory(dir) => {
+                let entries = fs::read_dir(dir).map_err(|e| format!("Cannot read {}: {}", dir.display(), e))?;
+                let mut shards: Vec<PathBuf> = entries
+                    .filter_map(|entry| entry.ok().map(|e| e.path()))
+                    .filter(|path| path.is_file())
+                    .filter(|path| {
+                        let name = path.to_string_lossy();
+                        name.ends_with(".bz2") || name.ends_with(".xml")
+                    })
+                    .collect();
+                // Wikimedia shard names carry their page range, which also sorts
+                // `articles10` after `articles9`; anything else goes by name.
+                shards.sort_by_cached_key(|path| {
+                    let name = path.file_name().map(|n| n.to_string_lossy().into_owned()).unwrap_or_default();
+                    (resume::shard_last_page(&name), name)
+                });
+                if shards.is_empty() {
+                    return Err(format!("No .bz2 or .xml dump shards in {}", dir.display()));
+                }
+                Ok(shards)
+            }
+        }
+    }
+}
+
+impl fmt::Display for DumpSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DumpSource::Url(url) => write!(f, "{}", url),
+            DumpSource::File(path) => write!(f, "{}", path.display()),
+            DumpSource::Directory(dir) => write!(f, "{} (directory)", dir.display()),
+        }
+    }
+}
+
+/// Blocking `Read` over the chunks the download task pushes into a channel.
+struct ChannelReader {
+    rx: Receiver<Vec<u8>>,
+    current: std::io::Cursor<Vec<u8>>,
+}
+
+impl Read for ChannelReader {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        if self.current.position() >= self.current.get_ref().len() as u64 {
+            match self.rx.recv() {
+                Ok(bytes) => self.current = std::io::Cursor::new(bytes),
+                Err(_) => return Ok(0),
+            }
+        }
+        self.current.read(buf)
+    }
+}
+
+/// Turns document text into training tokens.
+struct ArticleCleaner {
+    tokenizer: Box<dyn Tokenizer + Send + Sync>,
+    phrases: Option<PhraseModel>,
+}
+
+impl ArticleCleaner {
+    fn new(tokenizer: Box<dyn Tokenizer + Send + Sync>, phrases: Option<PhraseModel>) -> Self {
+        Self { tokenizer, phrases }
+    }
+
+    fn tokens(&self, text: &str) -> Vec<String> {
+        let mut tokens = self.tokenizer.tokenize(text);
+        if let Some(phrases) = &self.phrases {
+            tokens = phrases.apply(tokens);
+        }
+        tokens.truncate(2000);
+        tokens
+    }
 }
 
 #[derive(Default, Serialize, Deserialize, Clone)]
@@ -28,54 +187,185 @@ pub struct TrainerState {
     pub last_title: Option<String>,
     pub running: bool,
     pub error: Option<String>,
+    /// Corpus position the next run continues from.
+    #[serde(default)]
+    pub resume: ResumePoint,
+    /// The latest `/train/dictionary` run since the server started.
+    #[serde(skip)]
+    pub dictionary: Option<DictionaryProgress>,
+    /// Latest checkpoint evaluations, oldest first, starting with those already in the
+    /// history file.
+    #[serde(skip)]
+    pub metrics: Vec<MetricsRecord>,
+}
+
+/// Per-run settings sent to `/train/wiki/start`.
+#[derive(Default)]
+pub struct RunOverrides {
+    pub source: Option<CorpusSpec>,
+    /// Multistream index for a wiki `source`; only used together with it.
+    pub index: Option<String>,
+    pub max_articles: Option<usize>,
+    pub objective: Option<Objective>,
+    pub loss: Option<Loss>,
+    /// Replaces `config.frozen_words` for this run.
+    pub frozen_words: Option<Vec<String>>,
+    /// Evaluate every Nth checkpoint instead of `config.eval.every_checkpoints`; zero
+    /// turns evaluation off.
+    pub eval_every: Option<usize>,
+    /// Ignore the saved position and read the corpus from the beginning.
+    pub from_start: bool,
+}
+
+/// Checkpointing for a wiki or corpus run: saves, evaluates when due, and publishes the
+/// saved weights to readers.
+struct Checkpointer<'a> {
+    snapshot: &'a BrainSnapshot,
+    eval: CheckpointEval,
+}
+
+impl Checkpointer<'_> {
+    fn save(
+        &self,
+        brain: &Arc<RwLock<SemanticBrain>>,
+        state: &Arc<RwLock<TrainerState>>,
+    ) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        let saved = WikipediaTrainer::save_checkpoint_sync(brain, state, Some(&self.eval))?;
+        self.snapshot.replace(saved);
+        Ok(())
+    }
+}
+
+/// A document handed from the feeder to the training workers.
+struct QueuedArticle {
+    seq: u64,
+    title: String,
+    tokens: Vec<String>,
 }
 
 pub struct WikipediaTrainer {
     pub config: TrainerConfig,
     pub state: Arc<RwLock<TrainerState>>,
     pub brain: Arc<RwLock<SemanticBrain>>,
+    pub snapshot: Arc<BrainSnapshot>,
+    /// Held by a run until its task has exited, and by jobs that replace the live brain
+    /// outside a run. `running` only asks a run to stop; this says when it has.
+    job: Arc<tokio::sync::Mutex<()>>,
 }
 
 impl WikipediaTrainer {
-    pub fn new(brain: Arc<RwLock<SemanticBrain>>) -> Self {
+    pub fn new(brain: Arc<RwLock<SemanticBrain>>, snapshot: Arc<BrainSnapshot>) -> Self {
         let state = if let Ok(data) = fs::read_to_string("data/wiki_progress.json") {
             serde_json::from_str(&data).unwrap_or_default()
         } else {
             TrainerState::default()
         };
 
-        let mut initial_state = state;
+        let mut initial_state: TrainerState = state;
         initial_state.running = false;
+        initial_state.metrics = metrics::load_recent();
+
+        // The model file is written before the progress file, so after a crash between
+        // the two its metadata is the one that matches the weights.
+        let (objective, loss) = {
+            let b = brain.read();
+            if b.metadata.saved_at_unix > 0 {
+                initial_state.articles_processed = b.metadata.articles_processed as usize;
+                initial_state.tokens_processed = b.metadata.tokens_processed;
+                initial_state.last_title = b.metadata.last_title.clone();
+                initial_state.resume = b.metadata.resume.clone();
+            }
+            (b.metadata.objective, b.metadata.loss)
+        };
 
         Self {
             config: TrainerConfig {
-                dump_url: "https://dumps.wikimedia.org/enwiki/latest/enwiki-latest-pages-articles-multistream.xml.bz2".to_string(),
+                source: CorpusSpec::Wiki(DumpSource::Url("https://dumps.wikimedia.org/enwiki/latest/enwiki-latest-pages-articles-multistream.xml.bz2".to_string())),
+                multistream_index: Some("https://dumps.wikimedia.org/enwiki/latest/enwiki-latest-pages-articles-multistream-index.txt.bz2".to_string()),
+                objective,
+                loss,
                 learning_rate: 0.025,
                 window_size: 5,
                 negative_samples: 5,
                 max_articles: None,
                 checkpoint_every_articles: 100,
+                seed: 1,
+                subsample_threshold: 1e-4,
+                stop_words: Self::load_word_list("data/stop_words.txt").unwrap_or_else(|| {
+                    DEFAULT_STOP_WORDS.iter().map(|w| w.to_string()).collect()
+                }),
+                frozen_words: Self::load_word_list("data/frozen_words.txt").unwrap_or_default(),
+                workers: std::thread::available_parallelism()
+                    .map(|n| n.get().saturating_sub(1).max(1))
+                    .unwrap_or(1),
+                phrases: PhraseConfig::default(),
+                eval: CheckpointEvalConfig {
+                    probe_words: metrics::load_probe_words(),
+                    ..CheckpointEvalConfig::default()
+                },
             },
             state: Arc::new(RwLock::new(initial_state)),
             brain,
+            snapshot,
+            job: Arc::new(tokio::sync::Mutex::new(())),
         }
     }
 
-    pub fn start(&self) {
+    /// One word per line, skipping blanks and `#` comments. `None` when the file can't
+    /// be read.
+    fn load_word_list(path: &str) -> Option<Vec<String>> {
+        let text = fs::read_to_string(path).ok()?;
+        Some(text
+            .lines()
+            .map(|l| l.trim().to_string())
+            .filter(|l| !l.is_empty() && !l.starts_with('#'))
+            .collect())
+    }
+
+    /// Starts a run in the background, continuing from the saved dump position unless
+    /// `overrides.from_start` is set. Overrides apply to this run only. Returns `false`
+    /// while another run or model job hasn't finished.
+    pub fn start(&self, overrides: RunOverrides) -> bool {
+        let Ok(job) = self.job.clone().try_lock_owned() else {
+            return false;
+        };
         let mut state = self.state.write();
         if state.running {
-            return;
+            return false;
         }
         state.running = true;
         state.error = None;
+        if overrides.from_start {
+            state.resume = ResumePoint::default();
+        }
         drop(state);
 
         let trainer_state = self.state.clone();
         let brain = self.brain.clone();
-        let config = self.config.clone();
+        let snapshot = self.snapshot.clone();
+        let mut config = self.config.clone();
+        if let Some(source) = overrides.source {
+            config.source = source;
+            config.multistream_index = overrides.index;
+        }
+        if overrides.max_articles.is_some() {
+            config.max_articles = overrides.max_articles;
+        }
+        if let Some(objective) = overrides.objective {
+            config.objective = objective;
+        }
+        if let Some(loss) = overrides.loss {
+            config.loss = loss;
+        }
+        if let Some(words) = overrides.frozen_words {
+            config.frozen_words = words;
+        }
+        if let Some(every) = overrides.eval_every {
+            config.eval.every_checkpoints = every;
+        }
 
         tokio::spawn(async move {
-            if let Err(e) = Self::run_training(brain, trainer_state.clone(), config).await {
+            if let Err(e) = Self::run_training(brain, snapshot, trainer_state.clone(), config).await {
                 let mut state = trainer_state.write();
                 state.running = false;
                 state.error = Some(e.to_string());
@@ -83,7 +373,46 @@ impl WikipediaTrainer {
                 let mut state = trainer_state.write();
                 state.running = false;
             }
+            drop(job);
         });
+        true
+    }
+
+    /// Starts a dictionary run in the background. Shares the running flag with `start`,
+    /// so only one of the two trains at a time and `stop` ends either.
+    pub fn start_dictionary(&self, config: DictionaryConfig) -> bool {
+        let Ok(job) = self.job.clone().try_lock_owned() else {
+            return false;
+        };
+        let mut state = self.state.write();
+        if state.running {
+            return false;
+        }
+        state.running = true;
+        state.error = None;
+        state.dictionary = Some(DictionaryProgress {
+            path: config.path.display().to_string(),
+            epochs: config.epochs,
+            learning_rate: config.learning_rate,
+            ..DictionaryProgress::default()
+        });
+        drop(state);
+
+        let trainer_state = self.state.clone();
+        let brain = self.brain.clone();
+        let snapshot = self.snapshot.clone();
+        tokio::task::spawn_blocking(move || {
+            let result = dictionary::run(&brain, &snapshot, &trainer_state, &config);
+            let mut state = trainer_state.write();
+            state.running = false;
+            if let Err(e) = result {
+                eprintln!("> Dictionary training failed: {}", e);
+                state.error = Some(e.to_string());
+            }
+            drop(state);
+            drop(job);
+        });
+        true
     }
 
     pub fn stop(&self) {
@@ -91,32 +420,307 @@ impl WikipediaTrainer {
         state.running = false;
     }
 
+    /// Takes the job slot for work that replaces the live brain outside a run, such as
+    /// pruning or retrofitting in place, and holds it until the guard is dropped. A run
+    /// that was asked to stop is waited for; `None` while one is still training.
+    pub async fn reserve(&self) -> Option<OwnedMutexGuard<()>> {
+        if let Ok(job) = self.job.clone().try_lock_owned() {
+            return Some(job);
+        }
+        if self.state.read().running {
+            return None;
+        }
+        Some(self.job.clone().lock_owned().await)
+    }
+
     async fn run_training(
         brain: Arc<RwLock<SemanticBrain>>,
+        snapshot: Arc<BrainSnapshot>,
         state: Arc<RwLock<TrainerState>>,
         config: TrainerConfig,
     ) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
-        println!("> Starting Wikipedia training pipeline...");
-        println!("> URL: {}", config.dump_url);
+        println!("> Starting training pipeline...");
+        println!("> Source: {}", config.source);
+        println!("> Objective: {:?} with {:?}", config.objective, config.loss);
+        {
+            let mut b = brain.write();
+            b.seed_sampler(config.seed);
+            b.metadata.objective = config.objective;
+            b.metadata.loss = config.loss;
+            if config.loss == Loss::HierarchicalSoftmax {
+                b.enable_hierarchical_softmax();
+            }
+            let frozen = b.freeze(&config.frozen_words);
+            if !config.frozen_words.is_empty() {
+                println!("> Freezing {} of {} listed words.", frozen, config.frozen_words.len());
+            }
+        }
+
+        // Wiki pages and corpus documents are numbered differently, so a position saved
+        // for another corpus means nothing here. The state keeps it until the workers
+        // finish their first document.
+        let corpus = config.source.resume_key();
+        let mut resume_point = state.read().resume.clone();
+        if resume_point.corpus != corpus {
+            if resume_point.page_id.is_some() {
+                println!("> Saved position belongs to another corpus; reading from the beginning.");
+            }
+            resume_point = ResumePoint { corpus, ..ResumePoint::default() };
+        }
+        if let Some(page_id) = resume_point.page_id {
+            println!("> Resuming after document id {}.", page_id);
+        }
+
+        let phrases = Self::prepare_phrases(&brain, &state, &config).await?;
+        if !state.read().running {
+            return Ok(());
+        }
+
+        let mut source = Self::open_corpus(&config, &resume_point, &state).await?;
+
+        let blocking_state = state.clone();
+        tokio::task::spawn_blocking(move || {
+            let progress = Mutex::new(ProgressTracker::new(resume_point.clone()));
+            let checkpoints = Checkpointer { snapshot: &snapshot, eval: Self::checkpoint_eval(&config) };
+
+            let workers = config.workers.max(1);
+            let (article_tx, article_rx) = std::sync::mpsc::sync_channel::<QueuedArticle>(workers * 4);
+            let article_rx = Mutex::new(article_rx);
+
+            println!("> Reading the corpus and training on {} workers (a bzip2 dump may take a minute to start)...", workers);
+
+            std::thread::scope(|scope| {
+                let handles: Vec<_> = (0..workers)
+                    .map(|worker_id| {
+                        let (queue, progress, brain, state, config, checkpoints) = (&article_rx, &progress, &brain, &blocking_state, &config, &checkpoints);
+                        scope.spawn(move || Self::train_worker(worker_id, queue, progress, brain, state, config, checkpoints))
+                    })
+                    .collect();
+
+                let tokenizer = brain.read().tokenizer();
+                let mut feeder = CorpusFeeder {
+                    cleaner: ArticleCleaner::new(Box::new(tokenizer), phrases),
+                    article_tx,
+                    state: &blocking_state,
+                    progress: &progress,
+                    skip: resume_point,
+                    max_articles: config.max_articles,
+                    article_count: 0,
+                    skipped: 0,
+                };
+
+                feeder.feed(source.as_mut());
+                drop(feeder);
+                let mut result = Ok(());
+                for handle in handles {
+                    match handle.join() {
+                        Ok(Ok(())) => {}
+                        Ok(Err(e)) => result = Err(e),
+                        Err(_) => result = Err("Training worker panicked".into()),
+                    }
+                }
+                result
+            })?;
+
+            checkpoints.save(&brain, &blocking_state)?;
+            println!("> Training pipeline finished.");
+            Ok::<(), Box<dyn std::error::Error + Send + Sync>>(())
+        }).await?
+    }
+
+    /// Loads the checkpoint benchmarks from `data/eval`. A broken benchmark file only
+    /// costs the scores; the norms and probe neighbours are still logged.
+    fn checkpoint_eval(config: &TrainerConfig) -> CheckpointEval {
+        let benchmarks = if config.eval.every_checkpoints == 0 {
+            Benchmarks::default()
+        } else {
+            Benchmarks::load_or_default(None, None).unwrap_or_else(|e| {
+                println!("> Checkpoint benchmarks unusable ({}), logging norms and probes only.", e);
+                Benchmarks::default()
+            })
+        };
+        CheckpointEval::new(config.eval.clone(), benchmarks)
+    }
+
+    /// Opens the corpus a run reads. File corpora start at their first document and rely
+    /// on the feeder to skip what `skip` covers; a wiki dump also drops shards that end
+    /// before it and seeks into a multistream dump when the index allows.
+    async fn open_corpus(
+        config: &TrainerConfig,
+        skip: &ResumePoint,
+        state: &Arc<RwLock<TrainerState>>,
+    ) -> Result<Box<dyn CorpusSource>, Box<dyn std::error::Error + Send + Sync>> {
+        let dump = match &config.source {
+            CorpusSpec::Wiki(dump) => dump,
+            files => return Ok(files.open_files()?),
+        };
+
+        let mut shards = dump.local_files()?;
+        if let Some(page_id) = skip.page_id {
+            shards.retain(|path| {
+                let name = path.file_name().map(|n| n.to_string_lossy().into_owned()).unwrap_or_default();
+                resume::shard_last_page(&name).is_none_or(|last| last > page_id)
+            });
+        }
+
+        let offset = match (&config.multistream_index, skip.page_id) {
+            (Some(index), Some(page_id)) if !matches!(dump, DumpSource::Directory(_)) => {
+                match Self::find_stream_offset(index, page_id, state.clone()).await {
+                    Ok(Some(offset)) => {
+                        println!("> Page {} is in the bz2 stream at byte {}.", page_id, offset);
+                        offset
+                    }
+                    Ok(None) => 0,
+                    Err(e) => {
+                        println!("> Multistream index unusable ({}), skipping through the dump instead.", e);
+                        0
+                    }
+                }
+            }
+            _ => 0,
+        };
+
+        let mut inputs = VecDeque::new();
+        if let DumpSource::Url(url) = dump {
+            let stream = Self::open_stream(url, offset, state.clone()).await?;
+            inputs.push_back(DumpInput::Stream(stream, DumpSource::is_compressed(url)));
+        }
+        for (i, path) in shards.into_iter().enumerate() {
+            inputs.push_back(DumpInput::Shard(path, if i == 0 { offset } else { 0 }));
+        }
+        Ok(Box::new(WikiDumpSource::new(inputs, skip.clone())))
+    }
+
+    /// Loads the phrase table from `data/phrases.json`, or learns it from the first
+    /// `config.phrases.sample_articles` documents of the corpus and saves it there. Every
+    /// later run reuses the saved table so a phrase always maps to the same token;
+    /// delete the file to learn it again.
+    async fn prepare_phrases(
+        brain: &Arc<RwLock<SemanticBrain>>,
+        state: &Arc<RwLock<TrainerState>>,
+        config: &TrainerConfig,
+    ) -> Result<Option<PhraseModel>, Box<dyn std::error::Error + Send + Sync>> {
+        if config.phrases.sample_articles == 0 {
+            return Ok(None);
+        }
+        if let Ok(json) = fs::read_to_string(PHRASES_PATH) {
+            let phrases: PhraseModel = serde_json::from_str(&json).map_err(|e| format!("{}: {}", PHRASES_PATH, e))?;
+            println!("> Loaded {} phrases from {}.", phrases.len(), PHRASES_PATH);
+            return Ok(Some(phrases));
+        }
+
+        println!("> Learning phrases from the first {} documents...", config.phrases.sample_articles);
+        let mut source = Self::open_corpus(config, &ResumePoint::default(), state).await?;
+        let tokenizer = brain.read().tokenizer();
+        let sample_state = state.clone();
+        let phrase_config = config.phrases.clone();
+
+        let sample = tokio::task::spawn_blocking(move || {
+            let state = sample_state;
+            let progress = Mutex::new(ProgressTracker::new(ResumePoint::default()));
+            let (article_tx, article_rx) = std::sync::mpsc::sync_channel::<QueuedArticle>(64);
+
+            std::thread::scope(|scope| {
+                let collector = scope.spawn(move || {
+                    let mut sample = PhraseSample::default();
+                    for article in article_rx {
+                        sample.add(&article.tokens);
+                    }
+                    sample
+                });
 
+                let mut feeder = CorpusFeeder {
+                    cleaner: ArticleCleaner::new(Box::new(tokenizer), None),
+                    article_tx,
+                    state: &state,
+                    progress: &progress,
+                    skip: ResumePoint::default(),
+                    max_articles: Some(phrase_config.sample_articles),
+                    article_count: 0,
+                    skipped: 0,
+                };
+                feeder.feed(source.as_mut());
+                drop(feeder);
+                collector.join().map_err(|_| "Phrase sampling panicked".to_string())
+            })
+        }).await??;
+
+        // A stopped run leaves a partial sample; learn from a full one next time.
+        if !state.read().running {
+            return Ok(None);
+        }
+
+        let articles = sample.len();
+        let phrase_config = config.phrases.clone();
+        let phrases = tokio::task::spawn_blocking(move || PhraseModel::learn(sample, &phrase_config)).await?;
+        println!("> Learned {} phrases from {} documents.", phrases.len(), articles);
+
+        if !Path::new("data").exists() {
+            fs::create_dir_all("data")?;
+        }
+        fs::write(PHRASES_PATH, serde_json::to_string(&phrases)?)?;
+        Ok(Some(phrases))
+    }
+
+    /// Looks `page_id` up in a multistream index, downloading it first when `index` is
+    /// a URL.
+    async fn find_stream_offset(
+        index: &str,
+        page_id: u64,
+        state: Arc<RwLock<TrainerState>>,
+    ) -> Result<Option<u64>, Box<dyn std::error::Error + Send + Sync>> {
+        println!("> Reading multistream index {}...", index);
+        let input: Box<dyn Read + Send> = if index.starts_with("http://") || index.starts_with("https://") {
+            Box::new(Self::open_stream(index, 0, state).await?)
+        } else {
+            Box::new(fs::File::open(index)?)
+        };
+        let compressed = DumpSource::is_compressed(index);
+
+        let offset = tokio::task::spawn_blocking(move || {
+            let input: Box<dyn Read + Send> = if compressed {
+                Box::new(MultiBzDecoder::new(input))
+            } else {
+                input
+            };
+            resume::stream_offset(BufReader::new(input), page_id)
+        }).await??;
+        Ok(offset)
+    }
+
+    /// Starts the HTTP download and returns a blocking reader over the body, fed by a
+    /// background task so the parser never holds the async runtime. A non-zero `offset`
+    /// is requested with a `Range` header; servers that ignore it send the whole body,
+    /// which the resume skip handles anyway.
+    async fn open_stream(
+        url: &str,
+        offset: u64,
+        state: Arc<RwLock<TrainerState>>,
+    ) -> Result<ChannelReader, Box<dyn std::error::Error + Send + Sync>> {
         let client = reqwest::Client::builder()
             .user_agent("VecorsTrainer/1.0 (https://github.com/zoren-games/vecors; contact@example.com) Vecors/0.1.0")
             .build()?;
             
-        let response = client.get(&config.dump_url).send().await?;
+        let mut request = client.get(url);
+        if offset > 0 {
+            request = request.header(reqwest::header::RANGE, format!("bytes={}-", offset));
+        }
+        let response = request.send().await?;
         
         if !response.status().is_success() {
             let err_msg = format!("HTTP Error: {}", response.status());
             eprintln!("> {}", err_msg);
             return Err(err_msg.into());
         }
+        if offset > 0 && response.status() != reqwest::StatusCode::PARTIAL_CONTENT {
+            println!("> Server ignored the range request; skipping through the dump from the start.");
+        }
 
         let bytes_stream = response.bytes_stream();
         println!("> Download stream established. Waiting for chunks...");
 
         let (tx, rx) = std::sync::mpsc::sync_channel(128);
         
-        let stream_state = state.clone();
         tokio::spawn(async move {
             let mut stream = bytes_stream;
             while let Some(item) = stream.next().await {
@@ -129,7 +733,7 @@ impl WikipediaTrainer {
                     Err(e) => {
                         let err_msg = format!("Stream error: {}", e);
                         eprintln!("> {}", err_msg);
-                        let mut s = stream_state.write();
+                        let mut s = state.write();
                         s.error = Some(err_msg);
                         break;
                     }
@@ -137,191 +741,376 @@ impl WikipediaTrainer {
             }
         });
 
-        let blocking_state = state.clone();
-        tokio::task::spawn_blocking(move || {
-            struct ChannelReader {
-                rx: std::sync::mpsc::Receiver<Vec<u8>>,
-                current: std::io::Cursor<Vec<u8>>,
+        Ok(ChannelReader {
+            rx,
+            current: std::io::Cursor::new(Vec::new()),
+        })
+    }
+
+    fn xml_reader(input: Box<dyn Read + Send>, compressed: bool) -> Reader<BufReader<Box<dyn Read + Send>>> {
+        let input: Box<dyn Read + Send> = if compressed {
+            Box::new(MultiBzDecoder::new(input))
+        } else {
+            input
+        };
+        let mut xml_reader = Reader::from_reader(BufReader::new(input));
+        xml_reader.trim_text(true);
+        // A seek into a multistream dump starts mid-document, so the closing
+        // `</mediawiki>` has no matching start tag.
+        xml_reader.check_end_names(false);
+        xml_reader
+    }
+
+    /// Takes parsed articles off the queue and trains them. Token bookkeeping happens
+    /// under a short write lock, the skip-gram pass itself under a read lock shared
+    /// with the other workers, and the resulting delta is merged under a second short
+    /// write lock together with the progress counters, so a checkpoint never sees one
+    /// without the other.
+    fn train_worker(
+        worker_id: usize,
+        queue: &Mutex<Receiver<QueuedArticle>>,
+        progress: &Mutex<ProgressTracker>,
+        brain: &Arc<RwLock<SemanticBrain>>,
+        state: &Arc<RwLock<TrainerState>>,
+        config: &TrainerConfig,
+        checkpoints: &Checkpointer,
+    ) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        let mut rng = Rng::new(config.seed.wrapping_add(worker_id as u64 + 1));
+
+        loop {
+            let next = queue.lock().recv();
+            let article = match next {
+                Ok(article) => article,
+                Err(_) => return Ok(()),
+            };
+            if !state.read().running {
+                continue;
             }
 
-            impl Read for ChannelReader {
-                fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
-                    if self.current.position() >= self.current.get_ref().len() as u64 {
-                        match self.rx.recv() {
-                            Ok(bytes) => self.current = std::io::Cursor::new(bytes),
-                            Err(_) => return Ok(0),
-                        }
-                    }
-                    self.current.read(buf)
+            let (kept, ids) = {
+                let mut b = brain.write();
+                b.observe_tokens(&article.tokens);
+                let kept: Vec<String> = article.tokens
+                    .iter()
+                    .filter(|t| b.keep_token(t, config.subsample_threshold))
+                    .cloned()
+                    .collect();
+                let ids = b.index_tokens(&kept);
+                (kept, ids)
+            };
+
+            let current_tokens = { state.read().tokens_processed };
+            let params = WindowParams {
+                objective: config.objective,
+                loss: config.loss,
+                window_size: config.window_size,
+                learning_rate: (config.learning_rate * (1.0 - (current_tokens as f32 / 500_000_000.0))).max(0.0001),
+                negative_samples: config.negative_samples,
+            };
+            let delta = {
+                let b = brain.read();
+                let ngrams = b.subword_buckets(&kept);
+                b.train_window_delta(&ids, &ngrams, &params, &mut rng)
+            };
+
+            let checkpoint_due = {
+                let mut b = brain.write();
+                b.apply_delta(delta);
+                let mut s = state.write();
+                let mut tracker = progress.lock();
+                tracker.finish(article.seq);
+                s.resume = tracker.point();
+                s.articles_processed += 1;
+                s.tokens_processed += article.tokens.len() as u64;
+                s.last_title = Some(article.title);
+                s.articles_processed.is_multiple_of(config.checkpoint_every_articles.max(1))
+            };
+
+            if checkpoint_due {
+                if let Err(e) = checkpoints.save(brain, state) {
+                    state.write().running = false;
+                    return Err(e);
                 }
             }
+        }
+    }
 
-            let reader = ChannelReader {
-                rx,
-                current: std::io::Cursor::new(Vec::new()),
-            };
+    /// Writes the model and progress files. With `eval`, every Nth call also scores the
+    /// saved weights and appends the result to the metrics history. Returns the brain
+    /// decoded from the written bytes to check them, ready to publish as the snapshot.
+    pub fn save_checkpoint_sync(
+        brain: &Arc<RwLock<SemanticBrain>>,
+        state: &Arc<RwLock<TrainerState>>,
+        eval: Option<&CheckpointEval>,
+    ) -> Result<Arc<SemanticBrain>, Box<dyn std::error::Error + Send + Sync>> {
+        // Lock order is brain then state, as in the workers. The brain stays locked
+        // until the model is serialized so the counters match the weights exactly.
+        let mut b = brain.write();
+        let progress = state.read().clone();
+        b.metadata.articles_processed = progress.articles_processed as u64;
+        b.metadata.tokens_processed = progress.tokens_processed;
+        b.metadata.last_title = progress.last_title.clone();
+        b.metadata.resume = progress.resume.clone();
+        b.metadata.saved_at_unix = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or(0);
 
-            let bz_decoder = MultiBzDecoder::new(reader);
-            let mut xml_reader = Reader::from_reader(std::io::BufReader::new(bz_decoder));
-            xml_reader.trim_text(true);
+        let b = RwLockWriteGuard::downgrade(b);
+        let bytes = b.to_bytes()?;
+        let final_path = "data/model.bin";
 
-            let mut buf = Vec::new();
-            let mut current_title = String::new();
-            let mut current_text = String::new();
-            let mut in_text = false;
-            let mut in_title = false;
-            let mut article_count = 0;
+        let mut saved = match SemanticBrain::from_bytes(&bytes) {
+            Ok(reloaded) if reloaded.vocabulary.len() == b.vocabulary.len() => reloaded,
+            Ok(reloaded) => {
+                return Err(format!(
+                    "Refusing to overwrite {}: checkpoint reloads with {} words instead of {}",
+                    final_path, reloaded.vocabulary.len(), b.vocabulary.len()
+                ).into());
+            }
+            Err(e) => {
+                return Err(format!("Refusing to overwrite {}: {}", final_path, e).into());
+            }
+        };
+        saved.adopt_index(&b);
+        
+        if !Path::new("data").exists() {
+            fs::create_dir_all("data")?;
+        }
+        
+        // Replaced rather than rewritten, since the brain may still be reading the old
+        // file's tables through a memory map.
+        format::replace_file(Path::new(final_path), &bytes)?;
 
-            let re_link = Regex::new(r"\[\[([^|\]]+\|)?([^\]]+)\]\]").unwrap();
-            let re_template = Regex::new(r"\{\{[^}]+\}\}").unwrap();
-            let re_clean = Regex::new(r"[^A-Z\s]").unwrap();
+        // A stale graph is left out; the one already on disk no longer matches the new
+        // model's checksum, so a restart ignores it too.
+        if let Some(index) = b.ann_index.as_ref().filter(|_| !b.index_is_stale()) {
+            fs::write("data/model.hnsw.tmp", index.to_bytes(&b.embeddings)?)?;
+            fs::rename("data/model.hnsw.tmp", "data/model.hnsw")?;
+        }
 
-            println!("> Parsing XML and training (this may take a minute to start as bzip2 decompresses)...");
+        let progress_json = serde_json::to_string(&progress)?;
+        fs::write("data/wiki_progress.json", progress_json)?;
+        
+        println!("Checkpoint saved: {} articles processed", progress.articles_processed);
 
-            loop {
-                {
-                    let s = blocking_state.read();
-                    if !s.running {
-                        println!("> Training stop requested.");
-                        break;
-                    }
-                }
+        // Still under the read lock: the workers wait, but the scores are for exactly
+        // the weights just written.
+        if let Some(eval) = eval.filter(|eval| eval.next_is_due()) {
+            let record = eval.evaluate(&b, progress.articles_processed, progress.tokens_processed, b.metadata.saved_at_unix);
+            println!(
+                "> Checkpoint eval: {} non-finite vectors, mean norm {:.3}, analogy accuracy {}",
+                record.non_finite,
+                record.mean_norm,
+                record.analogy_accuracy.map_or("-".to_string(), |a| format!("{:.3}", a)),
+            );
+            metrics::append(&record)?;
+            metrics::push_recent(&mut state.write().metrics, record);
+        }
+        Ok(Arc::new(saved))
+    }
+}
 
-                match xml_reader.read_event_into(&mut buf) {
-                    Ok(Event::Start(ref e)) => {
-                        match e.name().as_ref() {
-                            b"title" => {
-                                in_title = true;
-                                current_title.clear();
-                            }
-                            b"text" => {
-                                in_text = true;
-                                current_text.clear();
-                            }
-                            _ => {}
-                        }
-                    }
-                    Ok(Event::Text(ref e)) => {
-                        if in_title {
-                            current_title.push_str(&e.unescape().unwrap_or_default());
-                        } else if in_text {
-                            current_text.push_str(&e.unescape().unwrap_or_default());
+/// One input of a wiki dump: the download stream, or a local shard read from a byte
+/// offset.
+enum DumpInput {
+    Stream(ChannelReader, bool),
+    Shard(PathBuf, u64),
+}
+
+type XmlReader = Reader<BufReader<Box<dyn Read + Send>>>;
+
+/// Articles of a MediaWiki XML dump, read from each input in turn. Redirects, pages
+/// outside the main namespace and empty pages are left out; pages `skip` covers come
+/// back with empty text so their wikitext is never collected.
+struct WikiDumpSource {
+    inputs: VecDeque<DumpInput>,
+    reader: Option<XmlReader>,
+    skip: ResumePoint,
+    buf: Vec<u8>,
+}
+
+impl WikiDumpSource {
+    fn new(inputs: VecDeque<DumpInput>, skip: ResumePoint) -> Self {
+        Self { inputs, reader: None, skip, buf: Vec::new() }
+    }
+
+    /// Opens the next input. `Ok(None)` once they are all read.
+    fn open_next(&mut self) -> Result<Option<XmlReader>, String> {
+        let Some(input) = self.inputs.pop_front() else {
+            return Ok(None);
+        };
+        let (input, compressed): (Box<dyn Read + Send>, bool) = match input {
+            DumpInput::Stream(stream, compressed) => (Box::new(stream), compressed),
+            DumpInput::Shard(path, offset) => {
+                println!("> Reading {}", path.display());
+                let file = fs::File::open(&path)
+                    .and_then(|mut file| {
+                        if offset > 0 {
+                            file.seek(SeekFrom::Start(offset))?;
                         }
-                    }
-                    Ok(Event::CData(ref e)) => {
-                        let text = String::from_utf8_lossy(e.as_ref()).to_string();
-                        if in_title {
-                            current_title.push_str(&text);
-                        } else if in_text {
-                            current_text.push_str(&text);
+                        Ok(file)
+                    })
+                    .map_err(|e| format!("Cannot open {}: {}", path.display(), e))?;
+                (Box::new(file), DumpSource::is_compressed(&path.to_string_lossy()))
+            }
+        };
+        Ok(Some(WikipediaTrainer::xml_reader(input, compressed)))
+    }
+
+    /// Reads `reader` up to the end of the next page worth returning. `Ok(None)` at the
+    /// end of the input.
+    fn next_page(&mut self, reader: &mut XmlReader) -> Result<Option<Document>, String> {
+        let mut current_title = String::new();
+        let mut current_text = String::new();
+        let mut page_id: Option<u64> = None;
+        let mut in_text = false;
+        let mut in_title = false;
+        let mut in_page_id = false;
+        let mut skipping = false;
+
+        loop {
+            self.buf.clear();
+            match reader.read_event_into(&mut self.buf) {
+                Ok(Event::Start(ref e)) => {
+                    match e.name().as_ref() {
+                        b"page" => page_id = None,
+                        // The page's own id comes before the revision and contributor ids.
+                        b"id" if page_id.is_none() => in_page_id = true,
+                        b"title" => {
+                            in_title = true;
+                            current_title.clear();
                         }
-                    }
-                    Ok(Event::End(ref e)) => {
-                        match e.name().as_ref() {
-                            b"title" => in_title = false,
-                            b"text" => {
-                                in_text = false;
-                                if !current_title.contains(':') && !current_text.is_empty() {
-                                    println!("> Training on article: {}", current_title);
-                                    let cleaned = re_template.replace_all(&current_text, "");
-                                    let cleaned = re_link.replace_all(&cleaned, "$2");
-                                    let cleaned = cleaned.to_uppercase();
-                                    let cleaned = re_clean.replace_all(&cleaned, " ");
-                                    
-                                    let tokens: Vec<String> = cleaned
-                                        .split_whitespace()
-                                        .filter(|t| t.len() >= 3)
-                                        .map(|t| t.to_string())
-                                        .take(2000)
-                                        .collect();
-
-                                    if !tokens.is_empty() {
-                                        {
-                                            let mut b = brain.write();
-                                            let current_tokens = { state.read().tokens_processed };
-                                            let alpha = (config.learning_rate * (1.0 - (current_tokens as f32 / 500_000_000.0))).max(0.0001);
-                                            
-                                            for i in 0..tokens.len() {
-                                                let center = &tokens[i];
-                                                let start = (i as usize).saturating_sub(config.window_size);
-                                                let end = (i + config.window_size + 1).min(tokens.len());
-                                                let context: Vec<String> = tokens[start..i]
-                                                    .iter()
-                                                    .chain(tokens[i + 1..end].iter())
-                                                    .cloned()
-                                                    .collect();
-                                                
-                                                b.train_step(center, &context, &[], alpha, config.negative_samples);
-                                            }
-                                        }
-                                        
-                                        article_count += 1;
-                                        {
-                                            let mut s = blocking_state.write();
-                                            s.articles_processed += 1;
-                                            s.tokens_processed += tokens.len() as u64;
-                                            s.last_title = Some(current_title.clone());
-                                        }
-
-                                        if article_count % config.checkpoint_every_articles == 0 {
-                                            Self::save_checkpoint_sync(&brain, &blocking_state)?;
-                                        }
-                                    }
-                                }
-                            }
-                            _ => {}
+                        b"text" => {
+                            in_text = true;
+                            current_text.clear();
+                            skipping = page_id.is_some_and(|id| self.skip.is_done(id));
                         }
+                        _ => {}
                     }
-                    Ok(Event::Eof) => {
-                        println!("> XML end of file reached.");
-                        break;
+                }
+                Ok(Event::Text(ref e)) => {
+                    if in_page_id {
+                        page_id = e.unescape().ok().and_then(|t| t.trim().parse().ok());
+                    } else if in_title {
+                        current_title.push_str(&e.unescape().unwrap_or_default());
+                    } else if in_text && !skipping {
+                        current_text.push_str(&e.unescape().unwrap_or_default());
                     }
-                    Err(e) => {
-                        let err_msg = format!("XML error: {}", e);
-                        eprintln!("> {}", err_msg);
-                        let mut s = blocking_state.write();
-                        s.error = Some(err_msg);
-                        break;
+                }
+                Ok(Event::CData(ref e)) => {
+                    let text = String::from_utf8_lossy(e.as_ref()).to_string();
+                    if in_title {
+                        current_title.push_str(&text);
+                    } else if in_text && !skipping {
+                        current_text.push_str(&text);
                     }
-                    _ => {}
                 }
-                buf.clear();
-
-                if let Some(max) = config.max_articles {
-                    if article_count >= max {
-                        println!("> Max articles reached ({}).", max);
-                        break;
+                Ok(Event::End(ref e)) => {
+                    match e.name().as_ref() {
+                        b"id" => in_page_id = false,
+                        b"title" => in_title = false,
+                        b"text" => {
+                            in_text = false;
+                            if skipping {
+                                return Ok(Some(Document { id: page_id, title: current_title, text: String::new() }));
+                            }
+                            if !current_title.contains(':') && !current_text.is_empty() && !wikitext::is_redirect(&current_text) {
+                                let text = wikitext::to_plain_text(&current_text);
+                                return Ok(Some(Document { id: page_id, title: current_title, text }));
+                            }
+                        }
+                        _ => {}
                     }
                 }
+                Ok(Event::Eof) => {
+                    println!("> XML end of file reached.");
+                    return Ok(None);
+                }
+                Err(e) => return Err(format!("XML error: {}", e)),
+                _ => {}
             }
-
-            Self::save_checkpoint_sync(&brain, &blocking_state)?;
-            println!("> Training pipeline finished.");
-            Ok::<(), Box<dyn std::error::Error + Send + Sync>>(())
-        }).await?
+        }
     }
+}
 
-    fn save_checkpoint_sync(
-        brain: &Arc<RwLock<SemanticBrain>>,
-        state: &Arc<RwLock<TrainerState>>,
-    ) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
-        let b = brain.read();
-        let bytes = b.to_bytes()?;
-        let temp_path = "data/model.bin.tmp";
-        let final_path = "data/model.bin";
-        
-        if !Path::new("data").exists() {
-            fs::create_dir_all("data")?;
+impl CorpusSource for WikiDumpSource {
+    fn next_document(&mut self) -> Result<Option<Document>, String> {
+        loop {
+            let mut reader = match self.reader.take() {
+                Some(reader) => reader,
+                None => match self.open_next()? {
+                    Some(reader) => reader,
+                    None => return Ok(None),
+                },
+            };
+            if let Some(document) = self.next_page(&mut reader)? {
+                self.reader = Some(reader);
+                return Ok(Some(document));
+            }
         }
-        
-        fs::write(temp_path, bytes)?;
-        fs::rename(temp_path, final_path)?;
+    }
+}
 
-        let s = state.read();
-        let progress_json = serde_json::to_string(&*s)?;
-        fs::write("data/wiki_progress.json", progress_json)?;
-        
-        println!("Checkpoint saved: {} articles processed", s.articles_processed);
-        Ok(())
+/// Pulls documents from a corpus and queues them for the workers, skipping documents the
+/// saved resume point already covers.
+struct CorpusFeeder<'a> {
+    cleaner: ArticleCleaner,
+    article_tx: SyncSender<QueuedArticle>,
+    state: &'a RwLock<TrainerState>,
+    progress: &'a Mutex<ProgressTracker>,
+    skip: ResumePoint,
+    max_articles: Option<usize>,
+    article_count: usize,
+    skipped: u64,
+}
+
+impl CorpusFeeder<'_> {
+    /// Queues documents until the corpus runs out or training should stop (stop
+    /// requested, article limit hit, workers gone or a read error, which is recorded in
+    /// the state).
+    fn feed(&mut self, source: &mut dyn CorpusSource) {
+        loop {
+            if !self.state.read().running {
+                println!("> Training stop requested.");
+                return;
+            }
+            if let Some(max) = self.max_articles {
+                if self.article_count >= max {
+                    println!("> Max articles reached ({}).", max);
+                    return;
+                }
+            }
+
+            let document = match source.next_document() {
+                Ok(Some(document)) => document,
+                Ok(None) => return,
+                Err(e) => {
+                    eprintln!("> {}", e);
+                    self.state.write().error = Some(e);
+                    return;
+                }
+            };
+
+            if let Some(id) = document.id.filter(|&id| self.skip.is_done(id)) {
+                self.skipped += 1;
+                if self.skipped.is_multiple_of(10_000) {
+                    println!("> Skipped {} already trained documents (at id {})", self.skipped, id);
+                }
+                continue;
+            }
+
+            let tokens = self.cleaner.tokens(&document.text);
+            if tokens.is_empty() {
+                continue;
+            }
+            println!("> Queued article: {}", document.title);
+            let seq = self.progress.lock().queue(document.id);
+            let article = QueuedArticle { seq, title: document.title, tokens };
+            if self.article_tx.send(article).is_err() {
+                return;
+            }
+            self.article_count += 1;
+        }
     }
 }
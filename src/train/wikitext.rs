@@ -0,0 +1,506 @@
+/// Sections that hold citations or link lists rather than prose. Everything from one of
+/// these headings to the next heading of the same or a higher level is dropped.
+const SKIPPED_SECTIONS: &[&str] = &[
+    "references", "notes", "footnotes", "citations", "sources", "bibliography",
+    "notes and references", "further reading", "external links", "see also",
+];
+
+/// Tags removed together with everything inside them.
+const OPAQUE_TAGS: &[&str] = &[
+    "ref", "references", "math", "chem", "ce", "gallery", "timeline", "syntaxhighlight",
+    "source", "score", "hiero", "imagemap", "graph", "mapframe", "templatedata",
+];
+
+/// Link prefixes that are namespaces or other wikis rather than part of a title.
+const NAMESPACES: &[&str] = &[
+    "file", "image", "media", "category", "template", "wikipedia", "wp", "help", "portal",
+    "user", "talk", "special", "wikt", "wiktionary", "commons", "meta", "mw", "species",
+    "s", "q", "n", "b", "v", "d", "w", "voy",
+];
+
+/// Namespaced links that never render as article text, even when piped.
+const DROPPED_NAMESPACES: &[&str] = &["file", "image", "media", "category"];
+
+#[derive(PartialEq)]
+enum Frame {
+    /// `{{...}}`, dropped with its content.
+    Template,
+    /// `{| ... |}`, dropped with its content.
+    Table,
+    /// `[[target|label]]`, replaced by its label.
+    Link,
+    /// `[url label]`, replaced by its label.
+    ExternalLink,
+}
+
+/// True for `#REDIRECT [[Target]]` pages, which have no prose of their own.
+pub fn is_redirect(wikitext: &str) -> bool {
+    wikitext
+        .trim_start()
+        .get(..9)
+        .is_some_and(|prefix| prefix.eq_ignore_ascii_case("#redirect"))
+}
+
+/// Renders article wikitext as the plain prose a reader would see: templates, tables,
+/// references, comments, file and category links and reference sections are removed,
+/// links and external links are replaced by their labels, and headings keep only their
+/// title. One output line per paragraph, list item or heading.
+pub fn to_plain_text(wikitext: &str) -> String {
+    let text = strip_comments(wikitext);
+    let mut parser = Parser {
+        src: &text,
+        lower: text.to_ascii_lowercase(),
+        pos: 0,
+        frames: Vec::new(),
+        out: String::new(),
+        skip_level: None,
+    };
+    parser.run();
+    normalize_whitespace(&parser.out)
+}
+
+fn strip_comments(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find("<!--") {
+        out.push_str(&rest[..start]);
+        match rest[start + 4..].find("-->") {
+            Some(end) => rest = &rest[start + 4 + end + 3..],
+            None => return out,
+        }
+    }
+    out.push_str(rest);
+    out
+}
+
+struct Parser<'a> {
+    src: &'a str,
+    /// ASCII-lowercased copy of `src` for case-insensitive tag searches; byte offsets
+    /// match `src`.
+    lower: String,
+    pos: usize,
+    /// Open constructs, innermost last, each with the text rendered inside it so far.
+    frames: Vec<(Frame, String)>,
+    out: String,
+    /// Heading level of the reference section being skipped.
+    skip_level: Option<usize>,
+}
+
+impl Parser<'_> {
+    fn run(&mut self) {
+        while self.pos < self.src.len() {
+            let rest = &self.src[self.pos..];
+
+            if self.frames.is_empty() && self.at_line_start() && self.line_markup() {
+                continue;
+            }
+
+            if rest.starts_with("{|") && self.after_indent() {
+                self.open(Frame::Table, 2);
+            } else if rest.starts_with("|}") && self.top() == Some(&Frame::Table) && self.after_indent() {
+                self.frames.pop();
+                self.pos += 2;
+            } else if rest.starts_with("{{") {
+                self.open(Frame::Template, 2);
+            } else if rest.starts_with("}}") && self.frames.iter().any(|(f, _)| *f == Frame::Template) {
+                // Anything left open inside the template goes with it.
+                while let Some((frame, _)) = self.frames.pop() {
+                    if frame == Frame::Template {
+                        break;
+                    }
+                }
+                self.pos += 2;
+            } else if rest.starts_with("[[") {
+                self.open(Frame::Link, 2);
+            } else if rest.starts_with("]]") && self.top() == Some(&Frame::Link) {
+                let (_, content) = self.frames.pop().unwrap_or((Frame::Link, String::new()));
+                if let Some(label) = link_label(&content) {
+                    self.emit(&label);
+                }
+                self.pos += 2;
+            } else if rest.starts_with('[') && starts_with_url(&rest[1..]) {
+                self.open(Frame::ExternalLink, 1);
+            } else if rest.starts_with(']') && self.top() == Some(&Frame::ExternalLink) {
+                let (_, content) = self.frames.pop().unwrap_or((Frame::ExternalLink, String::new()));
+                if let Some((_, label)) = content.split_once(char::is_whitespace) {
+                    self.emit(label);
+                }
+                self.pos += 1;
+            } else if rest.starts_with("''") {
+                self.pos += rest.len() - rest.trim_start_matches('\'').len();
+            } else if (rest.starts_with('<') && self.tag())
+                || (rest.starts_with("__") && self.magic_word())
+                || (rest.starts_with('&') && self.entity())
+            {
+                continue;
+            } else if self.top() != Some(&Frame::ExternalLink) && starts_with_url(rest) {
+                // Bare URLs in running text. They stop at markup so a URL that is the
+                // last template argument doesn't take the closing braces with it.
+                self.pos += rest
+                    .find(|c: char| c.is_whitespace() || "|{}[]<\"".contains(c))
+                    .unwrap_or(rest.len());
+            } else {
+                let c = rest.chars().next().unwrap_or(' ');
+                self.emit_char(c);
+                self.pos += c.len_utf8();
+            }
+        }
+
+        // Unterminated links keep their text; unterminated templates and tables don't.
+        while let Some((frame, content)) = self.frames.pop() {
+            if matches!(frame, Frame::Link | Frame::ExternalLink) {
+                self.emit(&content);
+            }
+        }
+    }
+
+    fn top(&self) -> Option<&Frame> {
+        self.frames.last().map(|(frame, _)| frame)
+    }
+
+    fn open(&mut self, frame: Frame, width: usize) {
+        self.frames.push((frame, String::new()));
+        self.pos += width;
+    }
+
+    fn emit(&mut self, text: &str) {
+        match self.frames.last_mut() {
+            Some((_, buf)) => buf.push_str(text),
+            None => self.out.push_str(text),
+        }
+    }
+
+    fn emit_char(&mut self, c: char) {
+        match self.frames.last_mut() {
+            Some((_, buf)) => buf.push(c),
+            None => self.out.push(c),
+        }
+    }
+
+    fn at_line_start(&self) -> bool {
+        self.pos == 0 || self.src.as_bytes()[self.pos - 1] == b'\n'
+    }
+
+    /// Only indentation or list markers between the last newline and `pos`, which is
+    /// where table delimiters are recognised.
+    fn after_indent(&self) -> bool {
+        self.src[..self.pos]
+            .bytes()
+            .rev()
+            .take_while(|&b| b != b'\n')
+            .all(|b| matches!(b, b' ' | b'\t' | b':' | b'*' | b'#' | b';'))
+    }
+
+    fn line_end(&self) -> usize {
+        self.src[self.pos..].find('\n').map_or(self.src.len(), |i| self.pos + i)
+    }
+
+    /// Handles markup that only means something at the start of a top-level line:
+    /// headings, skipped sections, list markers and horizontal rules. Returns `true`
+    /// when it consumed input.
+    fn line_markup(&mut self) -> bool {
+        let end = self.line_end();
+        let line = &self.src[self.pos..end];
+
+        if let Some((level, title)) = heading(line) {
+            if self.skip_level.is_some_and(|skip| level > skip) {
+                self.pos = (end + 1).min(self.src.len());
+                return true;
+            }
+            let title = to_plain_text(title);
+            if SKIPPED_SECTIONS.contains(&title.trim().to_lowercase().as_str()) {
+                self.skip_level = Some(level);
+            } else {
+                self.skip_level = None;
+                self.out.push('\n');
+                self.out.push_str(title.trim());
+                self.out.push('\n');
+            }
+            self.pos = end;
+            return true;
+        }
+
+        if self.skip_level.is_some() {
+            self.pos = (end + 1).min(self.src.len());
+            return true;
+        }
+
+        if line.starts_with("----") {
+            self.pos += line.len() - line.trim_start_matches('-').len();
+            return true;
+        }
+
+        let markers = line.len() - line.trim_start_matches(['*', '#', ':', ';']).len();
+        if markers > 0 && !line[markers..].trim_start().starts_with("{|") {
+            self.pos += markers;
+            return true;
+        }
+        false
+    }
+
+    /// Handles an HTML or extension tag at `pos`. Returns `false` when the `<` is just
+    /// text.
+    fn tag(&mut self) -> bool {
+        let rest = &self.lower[self.pos + 1..];
+        let closing = rest.starts_with('/');
+        let name: String = rest[closing as usize..]
+            .chars()
+            .take_while(|c| c.is_ascii_alphanumeric())
+            .collect();
+        if name.is_empty() || !name.starts_with(|c: char| c.is_ascii_alphabetic()) {
+            return false;
+        }
+        let Some(gt) = rest.find('>') else {
+            return false;
+        };
+        let self_closing = rest[..gt].ends_with('/');
+        let after_tag = self.pos + 1 + gt + 1;
+
+        if closing || self_closing {
+            if name == "br" {
+                self.emit_char(' ');
+            }
+            self.pos = after_tag;
+            return true;
+        }
+
+        let close = format!("</{}", name);
+        let close_at = self.lower[after_tag..].find(&close).map(|i| after_tag + i);
+        let past_close = close_at.map(|at| {
+            self.lower[at..].find('>').map_or(self.src.len(), |i| at + i + 1)
+        });
+
+        if OPAQUE_TAGS.contains(&name.as_str()) {
+            self.pos = past_close.unwrap_or(after_tag);
+        } else if name == "nowiki" {
+            let end = close_at.unwrap_or(after_tag);
+            let literal = &self.src[after_tag..end];
+            self.emit(literal);
+            self.pos = past_close.unwrap_or(after_tag);
+        } else {
+            if name == "br" {
+                self.emit_char(' ');
+            }
+            self.pos = after_tag;
+        }
+        true
+    }
+
+    /// `__TOC__`, `__NOTOC__` and friends.
+    fn magic_word(&mut self) -> bool {
+        let rest = &self.src[self.pos + 2..];
+        let word = rest.len() - rest.trim_start_matches(|c: char| c.is_ascii_uppercase()).len();
+        if word > 0 && rest[word..].starts_with("__") {
+            self.pos += 2 + word + 2;
+            return true;
+        }
+        false
+    }
+
+    fn entity(&mut self) -> bool {
+        let rest = &self.src[self.pos + 1..];
+        let Some(semi) = rest.find(';').filter(|&i| i > 0 && i <= 10) else {
+            return false;
+        };
+        let name = &rest[..semi];
+        let decoded = if let Some(code) = name.strip_prefix("#x").or_else(|| name.strip_prefix("#X")) {
+            u32::from_str_radix(code, 16).ok().and_then(char::from_u32)
+        } else if let Some(code) = name.strip_prefix('#') {
+            code.parse().ok().and_then(char::from_u32)
+        } else if name.chars().all(|c| c.is_ascii_alphanumeric()) {
+            Some(match name {
+                "amp" => '&',
+                "quot" => '"',
+                "apos" => '\'',
+                "lt" => '<',
+                "gt" => '>',
+                _ => ' ',
+            })
+        } else {
+            None
+        };
+        match decoded {
+            Some(c) => {
+                self.emit_char(c);
+                self.pos += 1 + semi + 1;
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+/// `== Title ==` as `(2, " Title ")`.
+fn heading(line: &str) -> Option<(usize, &str)> {
+    let line = line.trim_end();
+    let open = line.len() - line.trim_start_matches('=').len();
+    let close = line.len() - line.trim_end_matches('=').len();
+    let level = open.min(close).min(6);
+    if level == 0 || line.len() <= level * 2 {
+        return None;
+    }
+    Some((level, &line[level..line.len() - level]))
+}
+
+fn starts_with_url(text: &str) -> bool {
+    ["http://", "https://", "ftp://", "//"]
+        .iter()
+        .any(|scheme| text.get(..scheme.len()).is_some_and(|p| p.eq_ignore_ascii_case(scheme)))
+}
+
+/// Display text for the content of a `[[...]]` link, or `None` when it shows nothing
+/// (files, categories, interlanguage and unpiped interwiki links).
+fn link_label(content: &str) -> Option<String> {
+    let (target, label) = match content.split_once('|') {
+        Some((target, label)) => (target.trim(), Some(label.trim())),
+        None => (content.trim(), None),
+    };
+
+    // `[[:Category:Foo|Foo]]` links to the page rather than filing the article.
+    if let Some(target) = target.strip_prefix(':') {
+        return Some(label.filter(|l| !l.is_empty()).unwrap_or(target).to_string());
+    }
+
+    if let Some((prefix, _)) = target.split_once(':') {
+        let namespace = prefix.trim().to_lowercase();
+        let language = (2..=3).contains(&prefix.len()) && prefix.chars().all(|c| c.is_ascii_lowercase());
+        if DROPPED_NAMESPACES.contains(&namespace.as_str()) || (language && label.is_none()) {
+            return None;
+        }
+        if language || NAMESPACES.contains(&namespace.as_str()) {
+            return label.filter(|l| !l.is_empty()).map(str::to_string);
+        }
+    }
+
+    Some(label.filter(|l| !l.is_empty()).unwrap_or(target).to_string())
+}
+
+fn normalize_whitespace(text: &str) -> String {
+    text.lines()
+        .map(|line| line.split_whitespace().collect::<Vec<_>>().join(" "))
+        .filter(|line| !line.is_empty())
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fixture(name: &str) -> String {
+        let path = format!("{}/tests/fixtures/wikitext/{}", env!("CARGO_MANIFEST_DIR"), name);
+        std::fs::read_to_string(&path).unwrap_or_else(|e| panic!("{}: {}", path, e))
+    }
+
+    fn assert_clean(text: &str) {
+        for markup in ["{{", "}}", "[[", "]]", "{|", "|}", "<ref", "</", "<!--", "''", "http", "cite", "Category:", "thumb|"] {
+            assert!(!text.contains(markup), "{:?} left in output:\n{}", markup, text);
+        }
+    }
+
+    #[test]
+    fn nested_templates_are_removed() {
+        let text = to_plain_text("Before {{Infobox person|birth_date={{birth date|1912|6|23|df={{yes}}}}|name=X}} after.");
+        assert_eq!(text, "Before after.");
+    }
+
+    #[test]
+    fn links_render_their_labels() {
+        assert_eq!(to_plain_text("[[Cambridge]] and [[King's College, Cambridge|King's]]"), "Cambridge and King's");
+        assert_eq!(to_plain_text("many [[computer]]s"), "many computers");
+        assert_eq!(to_plain_text("see [[:Category:Logicians|logicians]]"), "see logicians");
+        assert_eq!(to_plain_text("a [[wikt:word]] and [[wikt:term|term]]"), "a and term");
+        assert_eq!(to_plain_text("[[Star Wars: Episode I]] opened"), "Star Wars: Episode I opened");
+    }
+
+    #[test]
+    fn file_category_and_language_links_are_dropped() {
+        let text = to_plain_text(
+            "[[File:Turing.jpg|thumb|upright|Turing at [[Princeton University|Princeton]] in 1936]]Text.\n[[Category:1912 births]]\n[[de:Alan Turing]]",
+        );
+        assert_eq!(text, "Text.");
+    }
+
+    #[test]
+    fn external_links_keep_labels_and_bare_urls_go() {
+        assert_eq!(to_plain_text("[https://example.org The archive] and [http://example.org]"), "The archive and");
+        assert_eq!(to_plain_text("Visit https://example.org/page today."), "Visit today.");
+        assert_eq!(to_plain_text("{{URL|https://example.org/}} Site."), "Site.");
+    }
+
+    #[test]
+    fn refs_comments_and_opaque_tags_are_dropped() {
+        let text = to_plain_text(
+            "Fact.<ref name=\"a\">{{cite book|title=X}}</ref> More<ref name=\"a\" /> text<!-- hidden -->. <math>x^2</math>Done<br/>now.",
+        );
+        assert_eq!(text, "Fact. More text. Done now.");
+        assert_eq!(to_plain_text("<REF>shouted</REF>kept"), "kept");
+    }
+
+    #[test]
+    fn tables_are_dropped_even_when_nested() {
+        let text = to_plain_text("Intro.\n{| class=\"wikitable\"\n|-\n| a || {{flag|UK}}\n|\n{|\n| inner\n|}\n|}\nOutro.");
+        assert_eq!(text, "Intro.\nOutro.");
+    }
+
+    #[test]
+    fn headings_keep_their_titles_and_reference_sections_are_skipped() {
+        let text = to_plain_text("Lead.\n== Early life ==\nBorn.\n== References ==\n{{reflist}}\n* [https://x.org Source]\n=== Sub ===\nStill refs.\n== Legacy ==\nRemembered.");
+        assert_eq!(text, "Lead.\nEarly life\nBorn.\nLegacy\nRemembered.");
+    }
+
+    #[test]
+    fn formatting_lists_entities_and_magic_words() {
+        let text = to_plain_text("__NOTOC__\n'''Bold''' and ''italic''.\n* item one\n# item two\n: indented\n----\nA&nbsp;B &amp; C&#8212;D &lt;tag&gt; <nowiki>[[raw]]</nowiki>");
+        assert_eq!(text, "Bold and italic.\nitem one\nitem two\nindented\nA B & C\u{2014}D <tag> [[raw]]");
+    }
+
+    #[test]
+    fn unterminated_markup_does_not_swallow_the_article() {
+        assert_eq!(to_plain_text("Text [[unclosed link"), "Text unclosed link");
+        assert_eq!(to_plain_text("Text {{unclosed template"), "Text");
+        assert_eq!(to_plain_text("a < b and 1 <3"), "a < b and 1 <3");
+    }
+
+    #[test]
+    fn redirects_are_detected() {
+        assert!(is_redirect("#REDIRECT [[Alan Turing]]"));
+        assert!(is_redirect("  #redirect[[Turing]]"));
+        assert!(!is_redirect("'''Alan Turing''' was"));
+    }
+
+    #[test]
+    fn alan_turing_fixture() {
+        let text = to_plain_text(&fixture("alan_turing.wiki"));
+        assert_clean(&text);
+        assert!(text.starts_with("Alan Mathison Turing ("), "{}", text);
+        assert!(text.contains("was an English mathematician, computer scientist, logician, cryptanalyst, philosopher and theoretical biologist."));
+        assert!(text.contains("formalisation of the concepts of algorithm and computation with the Turing machine"));
+        assert!(text.contains("\nEarly life and education\n"));
+        assert!(!text.contains("Infobox"));
+        assert!(!text.contains("Hodges"), "citation text leaked:\n{}", text);
+        assert!(!text.contains("The Turing Digital Archive"), "external links section leaked:\n{}", text);
+        assert!(!text.contains("Maida Vale.jpg"));
+    }
+
+    #[test]
+    fn mount_everest_fixture() {
+        let text = to_plain_text(&fixture("mount_everest.wiki"));
+        assert_clean(&text);
+        assert!(text.contains("is Earth's highest mountain above sea level, located in the Mahalangur Himal sub-range of the Himalayas."));
+        assert!(text.contains("The China–Nepal border runs across its summit point."));
+        assert!(!text.contains("Kangchenjunga"), "table content leaked:\n{}", text);
+        assert!(!text.contains("8,848.86"), "convert template leaked:\n{}", text);
+        assert!(!text.contains("Lhotse"), "see also section leaked:\n{}", text);
+    }
+
+    #[test]
+    fn rust_fixture() {
+        let text = to_plain_text(&fixture("rust_programming_language.wiki"));
+        assert_clean(&text);
+        assert!(text.contains("Rust is a general-purpose programming language emphasizing performance, type safety, and concurrency."));
+        assert!(text.contains("\nSyntax and features\n"));
+        assert!(!text.contains("println!"), "code sample leaked:\n{}", text);
+        assert!(!text.contains("Graydon Hoare"), "infobox leaked:\n{}", text);
+    }
+}
@@ -1,8 +1,18 @@
 mod brain;
 mod train;
 
-use crate::brain::model::SemanticBrain;
-use crate::train::wiki::WikipediaTrainer;
+use crate::brain::eval::{Benchmarks, EVAL_DIR};
+use crate::brain::export::{self, ImportedVectors, VectorFormat, VectorSet};
+use crate::brain::hnsw::{HnswIndex, HnswParams};
+use crate::brain::model::{Loss, Objective, SemanticBrain, DEFAULT_EMBEDDING_DIM};
+use crate::brain::quantize::Quantization;
+use crate::brain::retrofit::{self, Lexicon, RetrofitConfig};
+use crate::brain::subword::SubwordConfig;
+use crate::brain::tokenizer::TokenizerConfig;
+use crate::train::snapshot::BrainSnapshot;
+use crate::train::corpus::CorpusSpec;
+use crate::train::dictionary::DictionaryConfig;
+use crate::train::wiki::{DumpSource, RunOverrides, WikipediaTrainer};
 use axum::{
     routing::{get, post},
     extract::{State, Query},
@@ -13,15 +23,127 @@ use parking_lot::RwLock;
 use serde_json::json;
 use serde::Deserialize;
 
-#[derive(Deserialize)]
+/// Optional JSON body for `/train/wiki/start`. `source` is a dump URL, a dump file
+/// (`.xml.bz2` or `.xml`) or a directory of dump shards, or a corpus spec such as
+/// `{"format": "jsonl", "path": "corpus/", "text_field": "body"}`; `index` is the
+/// multistream index of a dump. `from_start` ignores the saved resume position.
+/// `objective` is `"skip_gram"`, `{"cbow": "mean"}` or `{"cbow": "sum"}`, and `loss` is
+/// `"negative_sampling"` or `"hierarchical_softmax"`. `eval_every` scores every Nth
+/// checkpoint into the metrics history, 0 for none. `frozen_words` replaces the list
+/// from `data/frozen_words.txt` for this run.
+#[derive(Deserialize, Default)]
 struct StartParams {
+    source: Option<SourceParam>,
+    index: Option<String>,
     max_articles: Option<usize>,
+    objective: Option<Objective>,
+    loss: Option<Loss>,
+    eval_every: Option<usize>,
+    frozen_words: Option<Vec<String>>,
+    from_start: Option<bool>,
+}
+
+/// A bare string is a wiki dump, as before corpus specs existed.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum SourceParam {
+    Dump(String),
+    Corpus {
+        /// `wiki`, `text`, `jsonl` or `markdown`.
+        format: String,
+        path: String,
+        /// JSONL only; defaults to `text`.
+        text_field: Option<String>,
+    },
+}
+
+impl SourceParam {
+    fn parse(self) -> Result<CorpusSpec, String> {
+        match self {
+            SourceParam::Dump(source) => DumpSource::parse(&source).map(CorpusSpec::Wiki),
+            SourceParam::Corpus { format, path, text_field } => CorpusSpec::parse(&format, &path, text_field),
+        }
+    }
+}
+
+/// Optional JSON body for `/train/dictionary`. `path` is a dictionary JSON file or a
+/// directory of them and defaults to `data/dictionary.json`.
+#[derive(Deserialize, Default)]
+struct DictionaryParams {
+    path: Option<String>,
+    epochs: Option<usize>,
+    learning_rate: Option<f32>,
 }
 
 #[derive(Deserialize)]
 struct SimilarParams {
     word: String,
     n: Option<usize>,
+    exact: Option<bool>,
+}
+
+#[derive(Deserialize)]
+struct IndexBuildParams {
+    m: Option<usize>,
+    ef_construction: Option<usize>,
+    ef_search: Option<usize>,
+}
+
+/// Query for `/model/export/vectors`. `format` is `word2vec`, `word2vec_bin` or `glove`
+/// (by default binary word2vec for a `.bin` path and text otherwise) and `vectors` is
+/// `target` or `average`.
+#[derive(Deserialize)]
+struct ExportVectorsParams {
+    format: Option<String>,
+    vectors: Option<String>,
+    path: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct QuantizeParams {
+    scheme: Option<String>,
+    subspaces: Option<usize>,
+    context: Option<bool>,
+    path: Option<String>,
+}
+
+/// `lexicon` is a dictionary JSON file (`format=dictionary`, the default for `.json`) or
+/// an edge list (`format=edges`). Without `output` the live model is retrofitted and
+/// saved; with it the result goes to that file and the live model is left alone.
+#[derive(Deserialize)]
+struct RetrofitParams {
+    lexicon: String,
+    format: Option<String>,
+    iterations: Option<usize>,
+    alpha: Option<f32>,
+    beta: Option<f32>,
+    output: Option<String>,
+    analogies: Option<String>,
+    /// Comma-separated similarity TSV files.
+    similarity: Option<String>,
+}
+
+/// Query for `/model/prune`. Without `output` the live model is pruned and saved.
+#[derive(Deserialize)]
+struct PruneParams {
+    min_count: Option<u64>,
+    max_words: Option<usize>,
+    output: Option<String>,
+}
+
+/// Optional JSON body for `/eval/run`. Benchmarks default to the ones in `data/eval/`.
+#[derive(Deserialize, Default)]
+struct EvalParams {
+    analogies: Option<String>,
+    similarity: Option<Vec<String>>,
+    /// Most frequent words searched for analogy answers.
+    candidates: Option<usize>,
+}
+
+#[derive(Deserialize)]
+struct RecallParams {
+    k: Option<usize>,
+    samples: Option<usize>,
 }
 
 #[derive(Deserialize)]
@@ -48,34 +170,94 @@ struct RelationshipParams {
 struct AppState {
     trainer: WikipediaTrainer,
     brain: Arc<RwLock<SemanticBrain>>,
+    snapshot: Arc<BrainSnapshot>,
 }
 
 #[tokio::main]
 async fn main() {
+    let args: Vec<String> = std::env::args().collect();
+    let command = match args.get(1).map(String::as_str) {
+        Some("eval") => Some(eval_command(&args[2..])),
+        Some("export") => Some(export_command(&args[2..])),
+        Some("import") => Some(import_command(&args[2..])),
+        _ => None,
+    };
+    if let Some(result) = command {
+        if let Err(e) = result {
+            eprintln!("> {}", e);
+            std::process::exit(1);
+        }
+        return;
+    }
+
     println!("============================================");
     println!("   SEMANTIC BRAIN SERVER - WIKI TRAINER     ");
     println!("============================================");
 
-    let brain_data = if let Ok(bytes) = std::fs::read("data/model.bin") {
-        println!("> Loading existing model from data/model.bin...");
-        SemanticBrain::from_bytes(&bytes).unwrap_or_else(|_| SemanticBrain::new())
-    } else if let Ok(bytes) = std::fs::read("trained_brain.bin") {
-        println!("> Loading existing model from trained_brain.bin...");
-        SemanticBrain::from_bytes(&bytes).unwrap_or_else(|_| SemanticBrain::new())
+    let saved = ["data/model.bin", "trained_brain.bin"].into_iter().find(|path| std::path::Path::new(path).is_file());
+    let brain_data = if let Some(path) = saved {
+        println!("> Loading existing model from {}...", path);
+        if std::env::args().any(|a| a == "--init-vectors") {
+            println!("> Ignoring --init-vectors: pretrained vectors only seed a fresh brain.");
+        }
+        load_model_or_exit(path)
     } else {
-        println!("> Starting with a fresh brain...");
-        SemanticBrain::new()
+        let pretrained = init_vectors_arg();
+        let dim = pretrained.as_ref().map(|vectors| vectors.dim).or_else(embedding_dim_arg).unwrap_or(DEFAULT_EMBEDDING_DIM);
+        println!("> Starting with a fresh {}D brain...", dim);
+        let mut brain = SemanticBrain::with_dim(dim);
+        if let Some(config) = tokenizer_arg() {
+            println!("> Tokenizer: {:?}", config);
+            brain.metadata.tokenizer = config;
+        }
+        if let Some(vectors) = pretrained {
+            let read = vectors.rows.len();
+            let added = brain.seed_vectors(vectors).unwrap_or_default();
+            println!("> Seeded {} of {} pretrained vectors; other words start from hashed vectors.", added, read);
+        }
+        brain
     };
 
+    if let Some(dim) = embedding_dim_arg() {
+        if dim != brain_data.dim {
+            println!("> Ignoring --dim {}: the model is {}D.", dim, brain_data.dim);
+        }
+    }
+
+    let mut brain_data = brain_data;
+    if let Some(config) = subword_arg() {
+        if brain_data.subwords.is_none() {
+            println!("> Enabling subword vectors ({} buckets).", config.buckets);
+            brain_data.enable_subwords(config);
+        }
+    }
+    if let Ok(bytes) = std::fs::read("data/model.hnsw") {
+        println!("> Loading similarity index from data/model.hnsw...");
+        let loaded_at = brain_data.updates();
+        match HnswIndex::from_bytes(&bytes, &brain_data.embeddings).and_then(|index| brain_data.attach_index(index, loaded_at)) {
+            Ok(()) => println!("> Similarity index ready."),
+            Err(e) => println!("> Ignoring similarity index: {}", e),
+        }
+    }
+
+    let snapshot = Arc::new(BrainSnapshot::new(&brain_data));
     let brain = Arc::new(RwLock::new(brain_data));
-    let trainer = WikipediaTrainer::new(brain.clone());
-    let app_state = Arc::new(AppState { trainer, brain: brain.clone() });
+    let trainer = WikipediaTrainer::new(brain.clone(), snapshot.clone());
+    let app_state = Arc::new(AppState { trainer, brain: brain.clone(), snapshot });
 
     let app = Router::new()
         .route("/train/wiki/start", post(start_training))
         .route("/train/wiki/stop", post(stop_training))
         .route("/train/wiki/status", get(get_status))
         .route("/train/wiki/sanitize", post(sanitize_model))
+        .route("/train/dictionary", post(start_dictionary))
+        .route("/model/index/build", post(build_index))
+        .route("/model/index/recall", get(index_recall))
+        .route("/model/export/quantized", post(export_quantized))
+        .route("/model/export/vectors", post(export_vectors))
+        .route("/model/retrofit", post(retrofit_model))
+        .route("/model/prune", post(prune_model))
+        .route("/eval/run", post(run_eval))
         .route("/predict/similar", get(predict_similar))
         .route("/predict/vector", get(get_vector))
         .route("/predict/analogy", get(predict_analogy))
@@ -88,12 +270,274 @@ async fn main() {
     axum::serve(listener, app).await.unwrap();
 }
 
+/// `trainer eval [--model data/model.bin] [--analogies FILE] [--similarity FILE]...
+/// [--candidates N] [--json]` scores a saved model on the benchmarks and exits.
+fn eval_command(args: &[String]) -> Result<(), String> {
+    let mut model = "data/model.bin".to_string();
+    let mut analogies = None;
+    let mut similarity: Option<Vec<String>> = None;
+    let mut candidates = None;
+    let mut as_json = false;
+    let mut rest = args.iter();
+    while let Some(arg) = rest.next() {
+        let mut value = || rest.next().cloned().ok_or_else(|| format!("{} needs a value", arg));
+        match arg.as_str() {
+            "--model" => model = value()?,
+            "--analogies" => analogies = Some(value()?),
+            "--similarity" => similarity.get_or_insert_with(Vec::new).push(value()?),
+            "--candidates" => candidates = Some(value()?.parse().map_err(|_| "--candidates needs a number".to_string())?),
+            "--json" => as_json = true,
+            other => return Err(format!("Unknown eval option {}", other)),
+        }
+    }
+
+    let mut benchmarks = load_benchmarks(analogies, similarity)?;
+    if benchmarks.is_empty() {
+        return Err(format!("No benchmarks given and none found in {}", EVAL_DIR));
+    }
+    if let Some(candidates) = candidates {
+        benchmarks.candidates = candidates;
+    }
+    let brain = SemanticBrain::load(&model).map_err(|e| format!("{}: {}", model, e))?;
+    let scores = benchmarks.score(&brain);
+
+    if as_json {
+        println!("{}", serde_json::to_string_pretty(&scores).map_err(|e| e.to_string())?);
+        return Ok(());
+    }
+    println!("{}: {} words, {}D, {} tokens trained", model, brain.vocabulary.len(), brain.dim, brain.metadata.tokens_processed);
+    if let Some(report) = &scores.analogy {
+        println!();
+        println!("Analogies (top {} words as candidates)", benchmarks.candidates);
+        for section in &report.sections {
+            let score = &section.score;
+            println!("  {:<32} {:>6.2}%  {:>5}/{:<5} covered {}", section.section, score.accuracy * 100.0, score.correct, score.covered, score.questions);
+        }
+        for (name, score) in [("semantic", &report.semantic), ("syntactic", &report.syntactic), ("total", &report.total)] {
+            println!("  {:<32} {:>6.2}%  {:>5}/{:<5} covered {}", name, score.accuracy * 100.0, score.correct, score.covered, score.questions);
+        }
+    }
+    if !scores.similarity.is_empty() {
+        println!();
+        println!("Word similarity (Spearman)");
+        for score in &scores.similarity {
+            let rho = score.spearman.map_or_else(|| "n/a".to_string(), |rho| format!("{:.3}", rho));
+            println!("  {:<32} {:>7}  {}/{} pairs covered", score.name, rho, score.covered, score.pairs);
+        }
+    }
+    Ok(())
+}
+
+/// `trainer export --output FILE [--model data/model.bin] [--format word2vec|word2vec_bin|glove]
+/// [--vectors target|average]` writes a saved model's vectors for other tools.
+fn export_command(args: &[String]) -> Result<(), String> {
+    let mut model = "data/model.bin".to_string();
+    let mut output = None;
+    let mut format = None;
+    let mut set = VectorSet::default();
+    let mut rest = args.iter();
+    while let Some(arg) = rest.next() {
+        let mut value = || rest.next().cloned().ok_or_else(|| format!("{} needs a value", arg));
+        match arg.as_str() {
+            "--model" => model = value()?,
+            "--output" => output = Some(value()?),
+            "--format" => format = Some(VectorFormat::parse(&value()?)?),
+            "--vectors" => set = VectorSet::parse(&value()?)?,
+            other => return Err(format!("Unknown export option {}", other)),
+        }
+    }
+    let output = output.ok_or("export needs --output FILE")?;
+    let format = format.unwrap_or_else(|| VectorFormat::from_path(std::path::Path::new(&output)));
+
+    let brain = SemanticBrain::load(&model).map_err(|e| format!("{}: {}", model, e))?;
+    let file = std::fs::File::create(&output).map_err(|e| format!("Cannot write {}: {}", output, e))?;
+    let stats = export::write_vectors(&brain, format, set, &mut std::io::BufWriter::new(file)).map_err(|e| format!("{}: {}", output, e))?;
+    println!("> Wrote {} {}D vectors to {} ({} non-finite rows left out).", stats.words, brain.dim, output, stats.skipped);
+    Ok(())
+}
+
+/// `trainer import --vectors FILE --output MODEL [--format word2vec|word2vec_bin|glove]
+/// [--limit N]` turns word2vec or GloVe vectors into a model file, such as
+/// `trained_brain.bin` for the Bevy frontend.
+fn import_command(args: &[String]) -> Result<(), String> {
+    let mut vectors = None;
+    let mut output = None;
+    let mut format = None;
+    let mut limit = None;
+    let mut rest = args.iter();
+    while let Some(arg) = rest.next() {
+        let mut value = || rest.next().cloned().ok_or_else(|| format!("{} needs a value", arg));
+        match arg.as_str() {
+            "--vectors" => vectors = Some(value()?),
+            "--output" => output = Some(value()?),
+            "--format" => format = Some(VectorFormat::parse(&value()?)?),
+            "--limit" => limit = Some(value()?.parse().map_err(|_| "--limit needs a number".to_string())?),
+            other => return Err(format!("Unknown import option {}", other)),
+        }
+    }
+    let vectors = vectors.ok_or("import needs --vectors FILE")?;
+    let output = output.ok_or("import needs --output FILE")?;
+    let format = format.unwrap_or_else(|| VectorFormat::from_path(std::path::Path::new(&vectors)));
+
+    let file = std::fs::File::open(&vectors).map_err(|e| format!("Cannot read {}: {}", vectors, e))?;
+    let imported = export::read_vectors(std::io::BufReader::new(file), format, limit).map_err(|e| format!("{}: {}", vectors, e))?;
+    let read = imported.rows.len();
+    if read == 0 {
+        return Err(format!("No vectors in {}", vectors));
+    }
+    let brain = SemanticBrain::from_vectors(imported);
+    brain.save(&output).map_err(|e| e.to_string())?;
+    println!("> Imported {} of {} {}D vectors from {} into {}.", brain.vocabulary.len(), read, brain.dim, vectors, output);
+    Ok(())
+}
+
+/// `--dim N` picks the embedding dimension when no saved model exists.
+fn embedding_dim_arg() -> Option<usize> {
+    let args: Vec<String> = std::env::args().collect();
+    args.iter()
+        .position(|a| a == "--dim")
+        .and_then(|i| args.get(i + 1))
+        .and_then(|v| v.parse().ok())
+}
+
+/// `--tokenizer path.json` sets the tokenizer of a fresh brain. Loaded models keep the
+/// tokenizer their vocabulary was built with.
+fn tokenizer_arg() -> Option<TokenizerConfig> {
+    let args: Vec<String> = std::env::args().collect();
+    let path = args.iter().position(|a| a == "--tokenizer").and_then(|i| args.get(i + 1))?;
+    match std::fs::read_to_string(path).map_err(|e| e.to_string()).and_then(|json| serde_json::from_str(&json).map_err(|e| e.to_string())) {
+        Ok(config) => Some(config),
+        Err(e) => {
+            eprintln!("> {}: {}", path, e);
+            std::process::exit(1);
+        }
+    }
+}
+
+/// `--init-vectors FILE` seeds a fresh brain from word2vec or GloVe vectors, read as
+/// `--init-format` (by default binary word2vec for `.bin`, text otherwise), keeping at
+/// most `--init-limit N` of them.
+fn init_vectors_arg() -> Option<ImportedVectors> {
+    let args: Vec<String> = std::env::args().collect();
+    let value = |flag: &str| args.iter().position(|a| a == flag).and_then(|i| args.get(i + 1));
+    let path = value("--init-vectors")?;
+    let format = match value("--init-format").map(|name| VectorFormat::parse(name)).transpose() {
+        Ok(format) => format.unwrap_or_else(|| VectorFormat::from_path(std::path::Path::new(path))),
+        Err(e) => {
+            eprintln!("> {}", e);
+            std::process::exit(1);
+        }
+    };
+    let limit = value("--init-limit").and_then(|v| v.parse().ok());
+    println!("> Reading pretrained vectors from {}...", path);
+    let file = std::fs::File::open(path).map_err(|e| e.to_string());
+    match file.and_then(|file| export::read_vectors(std::io::BufReader::new(file), format, limit)) {
+        Ok(vectors) if !vectors.rows.is_empty() => Some(vectors),
+        Ok(_) => {
+            eprintln!("> {}: no vectors", path);
+            std::process::exit(1);
+        }
+        Err(e) => {
+            eprintln!("> {}: {}", path, e);
+            std::process::exit(1);
+        }
+    }
+}
+
+/// `--subwords` adds character n-gram vectors to a brain that doesn't have them yet, and
+/// `--subword-buckets N` sizes the table.
+fn subword_arg() -> Option<SubwordConfig> {
+    let args: Vec<String> = std::env::args().collect();
+    let buckets = args.iter()
+        .position(|a| a == "--subword-buckets")
+        .and_then(|i| args.get(i + 1))
+        .and_then(|v| v.parse().ok());
+    if !args.iter().any(|a| a == "--subwords") && buckets.is_none() {
+        return None;
+    }
+    let mut config = SubwordConfig::default();
+    if let Some(buckets) = buckets {
+        config.buckets = buckets;
+    }
+    Some(config)
+}
+
+/// Starting from a blank brain when a checkpoint exists but can't be read would
+/// overwrite it at the next checkpoint, so bail out instead.
+fn load_model_or_exit(path: &str) -> SemanticBrain {
+    match SemanticBrain::load(path) {
+        Ok(brain) => {
+            println!(
+                "> Loaded {} {}D words ({} articles, {} tokens trained).",
+                brain.vocabulary.len(), brain.dim, brain.metadata.articles_processed, brain.metadata.tokens_processed
+            );
+            if brain.embeddings.is_mapped() {
+                println!("> Reading vectors from {} in place; training copies them into memory.", path);
+            }
+            brain
+        }
+        Err(e) => {
+            eprintln!("> {}: {}", path, e);
+            eprintln!("> Refusing to start. Move {} aside to train a fresh brain.", path);
+            std::process::exit(1);
+        }
+    }
+}
+
 async fn start_training(
     State(state): State<Arc<AppState>>,
-    Query(_params): Query<StartParams>,
+    params: Option<Json<StartParams>>,
+) -> Json<serde_json::Value> {
+    let params = params.map(|Json(p)| p).unwrap_or_default();
+    let source = match params.source.map(SourceParam::parse).transpose() {
+        Ok(source) => source,
+        Err(e) => return Json(json!({ "started": false, "error": e })),
+    };
+    let spec = source.as_ref().unwrap_or(&state.trainer.config.source);
+    let shown = spec.to_string();
+    let from_start = params.from_start.unwrap_or(false);
+    let resume = state.trainer.state.read().resume.clone();
+    let resume_page_id = if from_start || resume.corpus != spec.resume_key() { None } else { resume.page_id };
+    let started = state.trainer.start(RunOverrides {
+        source,
+        index: params.index,
+        max_articles: params.max_articles,
+        objective: params.objective,
+        loss: params.loss,
+        eval_every: params.eval_every,
+        frozen_words: params.frozen_words,
+        from_start,
+    });
+    if !started {
+        return Json(json!({ "started": false, "error": "Training is already running" }));
+    }
+    Json(json!({ "started": true, "source": shown, "resume_after_page_id": resume_page_id }))
+}
+
+async fn start_dictionary(
+    State(state): State<Arc<AppState>>,
+    params: Option<Json<DictionaryParams>>,
 ) -> Json<serde_json::Value> {
-    state.trainer.start();
-    Json(json!({ "started": true }))
+    let params = params.map(|Json(p)| p).unwrap_or_default();
+    let path = std::path::PathBuf::from(params.path.unwrap_or_else(|| "data/dictionary.json".to_string()));
+    if !path.exists() {
+        return Json(json!({ "started": false, "error": format!("Dictionary {} does not exist", path.display()) }));
+    }
+    let defaults = &state.trainer.config;
+    let config = DictionaryConfig {
+        path,
+        epochs: params.epochs.unwrap_or(5),
+        learning_rate: params.learning_rate.unwrap_or(0.01),
+        negative_samples: defaults.negative_samples,
+        seed: defaults.seed,
+        stop_words: defaults.stop_words.clone(),
+        frozen_words: defaults.frozen_words.clone(),
+    };
+    let (epochs, learning_rate) = (config.epochs, config.learning_rate);
+    if !state.trainer.start_dictionary(config) {
+        return Json(json!({ "started": false, "error": "Training is already running" }));
+    }
+    Json(json!({ "started": true, "epochs": epochs, "learning_rate": learning_rate }))
 }
 
 async fn stop_training(
@@ -107,40 +551,332 @@ async fn get_status(
     State(state): State<Arc<AppState>>,
 ) -> Json<serde_json::Value> {
     let s = state.trainer.state.read();
-    let b = state.brain.read();
+    let b = state.snapshot.get();
     
     Json(json!({
         "running": s.running,
         "articles_processed": s.articles_processed,
         "tokens_processed": s.tokens_processed,
         "last_title": s.last_title,
+        "resume_after_page_id": s.resume.page_id,
+        "dictionary": s.dictionary,
+        "metrics": s.metrics,
         "error": s.error,
         "vocab_size": b.vocabulary.len(),
         "embeddings_len": b.embeddings.len(),
+        "embedding_dim": b.dim,
+        "tokenizer": b.metadata.tokenizer,
+        "objective": b.metadata.objective,
+        "loss": b.metadata.loss,
+        "subwords": b.subwords.as_ref().map(|table| &table.config),
     }))
 }
 
 async fn sanitize_model(
     State(state): State<Arc<AppState>>,
 ) -> Json<serde_json::Value> {
-    let mut b = state.brain.write();
-    println!("> Sanitizing model (healing NaNs and centering vectors)...");
-    b.balance_vectors();
-    Json(json!({ "sanitized": true, "vocab_size": b.vocabulary.len() }))
+    // Re-centering under a running job would leave its pending deltas aimed at rows
+    // that have moved.
+    let Some(_job) = state.trainer.reserve().await else {
+        return Json(json!({ "sanitized": false, "error": "Training is running; stop it first" }));
+    };
+    let vocab_size = {
+        let mut b = state.brain.write();
+        println!("> Sanitizing model (healing NaNs and centering vectors)...");
+        b.balance_vectors();
+        b.vocabulary.len()
+    };
+    state.snapshot.publish(&state.brain);
+    Json(json!({ "sanitized": true, "vocab_size": vocab_size }))
+}
+
+async fn build_index(
+    State(state): State<Arc<AppState>>,
+    Query(params): Query<IndexBuildParams>,
+) -> Json<serde_json::Value> {
+    let defaults = HnswParams::default();
+    let hnsw_params = HnswParams {
+        m: params.m.unwrap_or(defaults.m),
+        ef_construction: params.ef_construction.unwrap_or(defaults.ef_construction),
+        ef_search: params.ef_search.unwrap_or(defaults.ef_search),
+    };
+
+    let brain = state.brain.clone();
+    let snapshot = state.snapshot.clone();
+    let result = tokio::task::spawn_blocking(move || {
+        println!("> Building similarity index (M={}, ef_construction={})...", hnsw_params.m, hnsw_params.ef_construction);
+        let start = std::time::Instant::now();
+        let built_from = snapshot.get();
+        let index = HnswIndex::build(hnsw_params, &built_from.embeddings);
+        let (nodes, bytes) = {
+            let mut b = brain.write();
+            b.attach_index(index, built_from.updates())?;
+            // Saved now as well as with checkpoints, which skip it once training has
+            // moved the rows. It only loads again alongside these exact vectors.
+            let bytes = match &b.ann_index {
+                Some(index) if !b.index_is_stale() => Some(index.to_bytes(&b.embeddings)?),
+                _ => None,
+            };
+            (b.embeddings.len(), bytes)
+        };
+        snapshot.publish(&brain);
+        let saved = match bytes {
+            Some(bytes) => {
+                std::fs::create_dir_all("data")
+                    .and_then(|()| std::fs::write("data/model.hnsw.tmp", &bytes))
+                    .and_then(|()| std::fs::rename("data/model.hnsw.tmp", "data/model.hnsw"))
+                    .map_err(|e| format!("Index built but not saved: {}", e))?;
+                true
+            }
+            None => false,
+        };
+        Ok::<_, String>((nodes, saved, start.elapsed().as_secs_f32()))
+    }).await;
+
+    match result {
+        Ok(Ok((nodes, saved, seconds))) => Json(json!({ "built": true, "nodes": nodes, "saved": saved, "seconds": seconds })),
+        Ok(Err(e)) => Json(json!({ "built": false, "error": e })),
+        Err(e) => Json(json!({ "built": false, "error": e.to_string() })),
+    }
+}
+
+async fn index_recall(
+    State(state): State<Arc<AppState>>,
+    Query(params): Query<RecallParams>,
+) -> Json<serde_json::Value> {
+    let b = state.snapshot.get();
+    match b.index_recall(params.k.unwrap_or(10), params.samples.unwrap_or(100)) {
+        Some(report) => Json(json!({
+            "k": report.k,
+            "queries": report.queries,
+            "recall": report.recall,
+            "stale": b.index_is_stale(),
+            "params": b.ann_index.as_ref().map(|index| &index.params),
+        })),
+        None => Json(json!({ "error": "No similarity index. POST /model/index/build first." })),
+    }
+}
+
+fn load_benchmarks(analogies: Option<String>, similarity: Option<Vec<String>>) -> Result<Benchmarks, String> {
+    Benchmarks::load_or_default(analogies.map(Into::into), similarity.map(|paths| paths.into_iter().map(Into::into).collect()))
+}
+
+async fn retrofit_model(
+    State(state): State<Arc<AppState>>,
+    Query(params): Query<RetrofitParams>,
+) -> Json<serde_json::Value> {
+    let defaults = RetrofitConfig::default();
+    let config = RetrofitConfig {
+        iterations: params.iterations.unwrap_or(defaults.iterations),
+        alpha: params.alpha.unwrap_or(defaults.alpha),
+        beta: params.beta.unwrap_or(defaults.beta),
+    };
+    let format = params.format.unwrap_or_else(|| {
+        if params.lexicon.ends_with(".json") { "dictionary" } else { "edges" }.to_string()
+    });
+    if format != "dictionary" && format != "edges" {
+        return Json(json!({ "retrofitted": false, "error": format!("Unknown lexicon format '{}', expected dictionary or edges", format) }));
+    }
+    let similarity = params.similarity.map(|list| list.split(',').map(|p| p.trim().to_string()).collect());
+    let benchmarks = match load_benchmarks(params.analogies, similarity) {
+        Ok(benchmarks) => benchmarks,
+        Err(e) => return Json(json!({ "retrofitted": false, "error": e })),
+    };
+
+    // Retrofitting the live model holds the job slot, so no run writes to it while
+    // its vectors are being replaced.
+    let _job = match params.output {
+        Some(_) => None,
+        None => match state.trainer.reserve().await {
+            Some(job) => Some(job),
+            None => return Json(json!({ "retrofitted": false, "error": "Training is running; stop it or pass an output path" })),
+        },
+    };
+
+    let worker_state = state.clone();
+    let output = params.output.clone();
+    let result = tokio::task::spawn_blocking(move || {
+        let state = worker_state;
+        let mut brain = state.brain.read().clone();
+        let text = std::fs::read_to_string(&params.lexicon).map_err(|e| format!("Cannot read {}: {}", params.lexicon, e))?;
+        let lexicon = if format == "dictionary" {
+            Lexicon::from_dictionary(&brain, &text)?
+        } else {
+            Lexicon::from_edge_list(&brain, &text)
+        };
+
+        println!("> Retrofitting to {} ({} edges, {} iterations)...", params.lexicon, lexicon.edges(), config.iterations);
+        let before = benchmarks.score(&brain);
+        let stats = retrofit::retrofit(&mut brain, &lexicon, &config);
+        let after = benchmarks.score(&brain);
+
+        match &output {
+            Some(path) => {
+                brain.save(path).map_err(|e| e.to_string())?;
+            }
+            None => {
+                *state.brain.write() = brain;
+                let saved = WikipediaTrainer::save_checkpoint_sync(&state.brain, &state.trainer.state, None).map_err(|e| e.to_string())?;
+                state.snapshot.replace(saved);
+            }
+        }
+        println!("> Retrofitted {} words.", stats.words);
+        let change = after.change_since(&before);
+        Ok::<_, String>(json!({ "stats": stats, "before": before, "after": after, "change": change }))
+    }).await;
+
+    let saved_to = params.output.unwrap_or_else(|| "data/model.bin".to_string());
+    match result {
+        Ok(Ok(mut report)) => {
+            report["retrofitted"] = json!(true);
+            report["output"] = json!(saved_to);
+            Json(report)
+        }
+        Ok(Err(e)) => Json(json!({ "retrofitted": false, "error": e })),
+        Err(e) => Json(json!({ "retrofitted": false, "error": e.to_string() })),
+    }
+}
+
+async fn prune_model(
+    State(state): State<Arc<AppState>>,
+    Query(params): Query<PruneParams>,
+) -> Json<serde_json::Value> {
+    let min_count = params.min_count.unwrap_or(5);
+    let _job = match params.output {
+        Some(_) => None,
+        None => match state.trainer.reserve().await {
+            Some(job) => Some(job),
+            None => return Json(json!({ "pruned": false, "error": "Training is running; stop it or pass an output path" })),
+        },
+    };
+
+    let worker_state = state.clone();
+    let output = params.output.clone();
+    let result = tokio::task::spawn_blocking(move || {
+        let state = worker_state;
+        println!("> Pruning words below count {}{}...", min_count, params.max_words.map_or(String::new(), |max| format!(", keeping at most {}", max)));
+        let stats = match &output {
+            Some(path) => {
+                let mut brain = state.brain.read().clone();
+                let stats = brain.prune(min_count, params.max_words)?;
+                brain.save(path).map_err(|e| e.to_string())?;
+                stats
+            }
+            None => {
+                let stats = state.brain.write().prune(min_count, params.max_words)?;
+                let saved = WikipediaTrainer::save_checkpoint_sync(&state.brain, &state.trainer.state, None).map_err(|e| e.to_string())?;
+                state.snapshot.replace(saved);
+                stats
+            }
+        };
+        println!("> Pruned {} words down to {}.", stats.words_before, stats.words_after);
+        Ok::<_, String>(stats)
+    }).await;
+
+    let saved_to = params.output.unwrap_or_else(|| "data/model.bin".to_string());
+    match result {
+        Ok(Ok(stats)) => Json(json!({ "pruned": true, "stats": stats, "output": saved_to })),
+        Ok(Err(e)) => Json(json!({ "pruned": false, "error": e })),
+        Err(e) => Json(json!({ "pruned": false, "error": e.to_string() })),
+    }
+}
+
+async fn run_eval(
+    State(state): State<Arc<AppState>>,
+    params: Option<Json<EvalParams>>,
+) -> Json<serde_json::Value> {
+    let params = params.map(|Json(p)| p).unwrap_or_default();
+    let mut benchmarks = match load_benchmarks(params.analogies, params.similarity) {
+        Ok(benchmarks) => benchmarks,
+        Err(e) => return Json(json!({ "error": e })),
+    };
+    if benchmarks.is_empty() {
+        return Json(json!({ "error": format!("No benchmarks given and none found in {}", EVAL_DIR) }));
+    }
+    if let Some(candidates) = params.candidates {
+        benchmarks.candidates = candidates;
+    }
+
+    let brain = state.snapshot.get();
+    match tokio::task::spawn_blocking(move || benchmarks.score(&brain)).await {
+        Ok(scores) => Json(json!(scores)),
+        Err(e) => Json(json!({ "error": e.to_string() })),
+    }
+}
+
+async fn export_vectors(
+    State(state): State<Arc<AppState>>,
+    Query(params): Query<ExportVectorsParams>,
+) -> Json<serde_json::Value> {
+    let path = params.path.unwrap_or_else(|| "data/vectors.txt".to_string());
+    let format = match params.format.as_deref().map(VectorFormat::parse).transpose() {
+        Ok(format) => format.unwrap_or_else(|| VectorFormat::from_path(std::path::Path::new(&path))),
+        Err(e) => return Json(json!({ "exported": false, "error": e })),
+    };
+    let set = match params.vectors.as_deref().map(VectorSet::parse).transpose() {
+        Ok(set) => set.unwrap_or_default(),
+        Err(e) => return Json(json!({ "exported": false, "error": e })),
+    };
+
+    let brain = state.snapshot.get();
+    let export_path = path.clone();
+    let result = tokio::task::spawn_blocking(move || {
+        let file = std::fs::File::create(&export_path).map_err(|e| format!("Cannot write {}: {}", export_path, e))?;
+        export::write_vectors(&brain, format, set, &mut std::io::BufWriter::new(file)).map_err(|e| e.to_string())
+    }).await;
+
+    match result {
+        Ok(Ok(stats)) => Json(json!({ "exported": true, "path": path, "format": format, "vectors": set, "stats": stats })),
+        Ok(Err(e)) => Json(json!({ "exported": false, "error": e })),
+        Err(e) => Json(json!({ "exported": false, "error": e.to_string() })),
+    }
+}
+
+async fn export_quantized(
+    State(state): State<Arc<AppState>>,
+    Query(params): Query<QuantizeParams>,
+) -> Json<serde_json::Value> {
+    let scheme = match params.scheme.as_deref().unwrap_or("int8") {
+        "int8" => Quantization::Int8,
+        "pq" => Quantization::Product { subspaces: params.subspaces.unwrap_or(16) },
+        other => return Json(json!({ "exported": false, "error": format!("Unknown scheme '{}', expected int8 or pq", other) })),
+    };
+    let path = params.path.unwrap_or_else(|| "data/model.quantized.bin".to_string());
+    let include_context = params.context.unwrap_or(false);
+
+    let brain = state.snapshot.get();
+    let export_path = path.clone();
+    let result = tokio::task::spawn_blocking(move || {
+        let bytes = brain.to_quantized_bytes(scheme, include_context)?;
+        std::fs::write(&export_path, &bytes).map_err(|e| e.to_string())?;
+        Ok::<usize, String>(bytes.len())
+    }).await;
+
+    match result {
+        Ok(Ok(size)) => Json(json!({ "exported": true, "path": path, "bytes": size })),
+        Ok(Err(e)) => Json(json!({ "exported": false, "error": e })),
+        Err(e) => Json(json!({ "exported": false, "error": e.to_string() })),
+    }
 }
 
 async fn predict_similar(
     State(state): State<Arc<AppState>>,
     Query(params): Query<SimilarParams>,
 ) -> Json<serde_json::Value> {
-    let b = state.brain.read();
-    let word_upper = params.word.to_uppercase();
-    let found = b.vocabulary.contains_key(&word_upper);
-    let results = b.find_most_similar(&params.word, params.n.unwrap_or(10));
+    let b = state.snapshot.get();
+    let found = b.contains_word(&params.word);
+    let source = b.vector_source(&params.word);
+    let results = if params.exact.unwrap_or(false) {
+        b.find_most_similar_exact(&params.word, params.n.unwrap_or(10))
+    } else {
+        b.find_most_similar(&params.word, params.n.unwrap_or(10))
+    };
     Json(json!({ 
         "word": params.word, 
         "found": found,
+        "source": source,
         "vocab_size": b.vocabulary.len(),
+        "indexed": b.ann_index.is_some() && !b.index_is_stale(),
         "similar": results 
     }))
 }
@@ -149,11 +885,12 @@ async fn get_vector(
     State(state): State<Arc<AppState>>,
     Query(params): Query<SimilarParams>,
 ) -> Json<serde_json::Value> {
-    let b = state.brain.read();
-    let vec = b.get_embedding(&params.word);
+    let b = state.snapshot.get();
+    let embedding = b.get_embedding(&params.word);
     Json(json!({ 
         "word": params.word, 
-        "vector": vec.map(|v| &v.data[..5])
+        "source": embedding.as_ref().map(|e| e.source),
+        "vector": embedding.as_ref().map(|e| &e.vector.data[..e.vector.data.len().min(5)])
     }))
 }
 
@@ -161,10 +898,11 @@ async fn predict_analogy(
     State(state): State<Arc<AppState>>,
     Query(params): Query<AnalogyParams>,
 ) -> Json<serde_json::Value> {
-    let b = state.brain.read();
+    let b = state.snapshot.get();
     let results = b.calculate_analogy(&params.a, &params.b, &params.c, 10);
     Json(json!({ 
         "analogy": format!("{} is to {} as {} is to ...", params.a, params.b, params.c),
+        "sources": [b.vector_source(&params.a), b.vector_source(&params.b), b.vector_source(&params.c)],
         "results": results 
     }))
 }
@@ -173,7 +911,7 @@ async fn predict_attention(
     State(state): State<Arc<AppState>>,
     Json(params): Json<AttentionParams>,
 ) -> Json<serde_json::Value> {
-    let b = state.brain.read();
+    let b = state.snapshot.get();
     let results = b.calculate_attention(&params.target, &params.context);
     Json(json!({ "target": params.target, "attention": results }))
 }
@@ -182,7 +920,7 @@ async fn predict_relationship(
     State(state): State<Arc<AppState>>,
     Query(params): Query<RelationshipParams>,
 ) -> Json<serde_json::Value> {
-    let b = state.brain.read();
+    let b = state.snapshot.get();
     let score = b.compare_relationships(&params.a1, &params.b1, &params.a2, &params.b2);
     Json(json!({ 
         "pair1": format!("{} -> {}", params.a1, params.b1),
@@ -1,4 +1,4 @@
 window.PUBLIC_CONFIG = {
   API_BASE_URL: "", // Leave empty to use Cloudflare Pages Functions at /api
-  BRAIN_URL: "trained_brain.bin" // Change this if you host the 159MB file elsewhere
+  BRAIN_URL: "trained_brain.bin" // Full-precision models are ~159MB; a quantized export is a fraction of that
 };